@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use wasminspect_vm::*;
+use wasmparser::{FuncType, ImportSectionEntryType, Type};
+
+mod value;
+pub use value::{read_string, ElementType, Format};
+
+/// Whether `module` imports the `env` functions of the AssemblyScript
+/// runtime. Emscripten also imports `env.abort`, but without parameters.
+pub fn is_assemblyscript_module(module: &Module) -> bool {
+    module.imports().iter().any(|import| {
+        let ty = match import.ty {
+            ImportSectionEntryType::Function(index) if import.module == "env" => {
+                match module.types().get(index as usize) {
+                    Some(ty) => ty,
+                    None => return false,
+                }
+            }
+            _ => return false,
+        };
+        match import.field.as_str() {
+            "abort" => ty.params.len() == 4,
+            "trace" | "seed" => true,
+            _ => false,
+        }
+    })
+}
+
+/// `env` functions of the AssemblyScript runtime imported by `module`
+pub fn instantiate_assemblyscript(module: &Module) -> HashMap<String, HostValue> {
+    let mut funcs = HashMap::new();
+    for import in module.imports() {
+        let type_index = match import.ty {
+            ImportSectionEntryType::Function(index) => index,
+            _ => continue,
+        };
+        if import.module != "env" {
+            continue;
+        }
+        // A broken type index is reported by the import stub or validation
+        let ty: FuncType = match module.types().get(type_index as usize) {
+            Some(ty) => ty.clone(),
+            None => continue,
+        };
+        let func = match import.field.as_str() {
+            "abort" => HostFuncBody::new(ty, |args, _ret, ctx, _store| {
+                Err(Trap::Abort(abort_message(args, ctx.mem)))
+            }),
+            "trace" => HostFuncBody::new(ty, |args, _ret, ctx, _store| {
+                eprintln!("{}", trace_message(args, ctx.mem));
+                Ok(())
+            }),
+            "seed" => {
+                let returns = ty.returns.to_vec();
+                HostFuncBody::new(ty, move |_args, ret, _ctx, _store| {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default();
+                    match returns.first() {
+                        Some(Type::F64) => ret.push(WasmValue::from(now.as_secs_f64())),
+                        Some(ty) => ret.push(WasmValue::zero(*ty).map_err(Trap::Value)?),
+                        None => (),
+                    }
+                    Ok(())
+                })
+            }
+            _ => continue,
+        };
+        funcs.insert(import.field.clone(), HostValue::Func(func));
+    }
+    funcs
+}
+
+fn arg(args: &[WasmValue], index: usize) -> u32 {
+    args.get(index).and_then(|v| v.as_i32()).unwrap_or(0) as u32
+}
+
+fn string_arg(args: &[WasmValue], index: usize, mem: &[u8]) -> String {
+    match arg(args, index) {
+        0 => "null".to_string(),
+        ptr => read_string(mem, ptr).unwrap_or_else(|| format!("0x{:x}", ptr)),
+    }
+}
+
+/// `abort(message, fileName, line, column)`
+fn abort_message(args: &[WasmValue], mem: &[u8]) -> String {
+    format!(
+        "{} at {}:{}:{}",
+        string_arg(args, 0, mem),
+        string_arg(args, 1, mem),
+        arg(args, 2),
+        arg(args, 3)
+    )
+}
+
+/// `trace(message, n, a0, .., a4)` shows the first `n` numbers
+fn trace_message(args: &[WasmValue], mem: &[u8]) -> String {
+    let count = arg(args, 1) as usize;
+    let values: Vec<String> = args
+        .iter()
+        .skip(2)
+        .take(count)
+        .filter_map(|v| v.as_f64())
+        .map(|v| v.to_string())
+        .collect();
+    let message = string_arg(args, 0, mem);
+    if values.is_empty() {
+        format!("trace: {}", message)
+    } else {
+        format!("trace: {} {}", message, values.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A module importing `env.field` typed as the `type_index`th type,
+    /// where the only type is `ty` encoded after `0x60`
+    fn importing(field: &str, ty: &[u8], type_index: u8) -> Module {
+        let mut bytes = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        bytes.extend_from_slice(&[0x01, 2 + ty.len() as u8, 0x01, 0x60]);
+        bytes.extend_from_slice(ty);
+        bytes.extend_from_slice(&[0x02, 8 + field.len() as u8, 0x01]);
+        bytes.extend_from_slice(&[0x03, b'e', b'n', b'v', field.len() as u8]);
+        bytes.extend_from_slice(field.as_bytes());
+        bytes.extend_from_slice(&[0x00, type_index]);
+        Module::from_bytes(&bytes).unwrap()
+    }
+
+    /// The string "hi" at 8 with its size at 4
+    const MEM: [u8; 12] = [0, 0, 0, 0, 4, 0, 0, 0, b'h', 0, b'i', 0];
+
+    fn i32s(values: &[i32]) -> Vec<WasmValue> {
+        values.iter().map(|v| WasmValue::I32(*v)).collect()
+    }
+
+    #[test]
+    fn detect_assemblyscript_module() {
+        const ABORT: &[u8] = &[0x04, 0x7f, 0x7f, 0x7f, 0x7f, 0x00];
+        const NO_PARAMS: &[u8] = &[0x00, 0x00];
+        let cases = [
+            ("abort", ABORT, 0, true),
+            ("abort", NO_PARAMS, 0, false),
+            ("trace", NO_PARAMS, 0, true),
+            ("seed", NO_PARAMS, 0, true),
+            ("seed", NO_PARAMS, 1, false),
+            ("memcpy", NO_PARAMS, 0, false),
+        ];
+        for (field, ty, type_index, expected) in cases.iter() {
+            let module = importing(field, ty, *type_index);
+            assert_eq!(is_assemblyscript_module(&module), *expected, "{}", field);
+        }
+        let funcs = instantiate_assemblyscript(&importing("seed", NO_PARAMS, 1));
+        assert!(funcs.is_empty());
+    }
+
+    #[test]
+    fn test_abort_message() {
+        let cases = [
+            (vec![8, 8, 3, 14], "hi at hi:3:14"),
+            (vec![0, 0, 1, 2], "null at null:1:2"),
+            // Pointers out of the memory are shown as they are
+            (vec![100, 2, 0, 0], "0x64 at 0x2:0:0"),
+            (vec![8], "hi at null:0:0"),
+        ];
+        for (args, expected) in cases.iter() {
+            assert_eq!(abort_message(&i32s(args), &MEM), *expected);
+        }
+    }
+
+    #[test]
+    fn test_trace_message() {
+        let numbers = |message: i32, n: i32, values: &[f64]| {
+            let mut args = i32s(&[message, n]);
+            args.extend(values.iter().map(|v| WasmValue::from(*v)));
+            args
+        };
+        let cases = [
+            (numbers(8, 0, &[1.0]), "trace: hi"),
+            (numbers(8, 2, &[1.5, -2.0, 3.0]), "trace: hi 1.5, -2"),
+            (numbers(0, 5, &[1.0]), "trace: null 1"),
+            (numbers(100, 1, &[0.25]), "trace: 0x64 0.25"),
+        ];
+        for (args, expected) in cases.iter() {
+            assert_eq!(trace_message(args, &MEM), *expected);
+        }
+    }
+
+    #[test]
+    fn seed_returns_the_declared_type() {
+        let store = Store::new();
+        let cases: [(&[u8], Option<Type>); 3] = [
+            (&[0x00, 0x01, 0x7c], Some(Type::F64)),
+            (&[0x00, 0x01, 0x7f], Some(Type::I32)),
+            (&[0x00, 0x00], None),
+        ];
+        for (ty, expected) in cases.iter() {
+            let funcs = instantiate_assemblyscript(&importing("seed", ty, 0));
+            let func = match funcs.get("seed") {
+                Some(HostValue::Func(func)) => func,
+                _ => panic!("seed is not defined"),
+            };
+            let mut results = Vec::new();
+            func.call(&[], &mut results, &store, ModuleIndex(0), None)
+                .unwrap();
+            let types: Vec<Type> = results.iter().map(|v| v.value_type()).collect();
+            assert_eq!(types.first(), expected.as_ref());
+            if let Some(Type::I32) = expected {
+                assert_eq!(results, vec![WasmValue::I32(0)]);
+            }
+        }
+    }
+}
@@ -0,0 +1,227 @@
+// Layouts of managed objects in linear memory. Every object is preceded by
+// a header whose last field is the size of the object in bytes.
+
+/// Element types of `Array<T>`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ElementType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    I64,
+    U64,
+    F32,
+    F64,
+    String,
+}
+
+impl ElementType {
+    fn size(&self) -> u32 {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 | Self::String => 4,
+            Self::I64 | Self::U64 | Self::F64 => 8,
+        }
+    }
+}
+
+impl std::str::FromStr for ElementType {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "i8" => Ok(Self::I8),
+            "u8" => Ok(Self::U8),
+            "i16" => Ok(Self::I16),
+            "u16" => Ok(Self::U16),
+            "i32" => Ok(Self::I32),
+            "u32" => Ok(Self::U32),
+            "i64" => Ok(Self::I64),
+            "u64" => Ok(Self::U64),
+            "f32" => Ok(Self::F32),
+            "f64" => Ok(Self::F64),
+            "string" => Ok(Self::String),
+            _ => Err(format!("unknown element type {}", s)),
+        }
+    }
+}
+
+/// How to show a value which points to a managed object
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    String,
+    Array(ElementType),
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some("string"), None) => Ok(Self::String),
+            (Some("array"), Some(elem)) => Ok(Self::Array(elem.parse()?)),
+            _ => Err(format!("expected string or array:TYPE: {}", s)),
+        }
+    }
+}
+
+impl Format {
+    /// Show the object at `ptr`, or `None` if it lies out of `mem`
+    pub fn display(&self, mem: &[u8], ptr: u32) -> Option<String> {
+        match self {
+            Self::String => display_string(mem, ptr),
+            Self::Array(elem) => display_array(mem, ptr, *elem),
+        }
+    }
+}
+
+fn read_bytes(mem: &[u8], ptr: u32, len: u32) -> Option<&[u8]> {
+    mem.get(ptr as usize..ptr as usize + len as usize)
+}
+
+fn read_u32(mem: &[u8], ptr: u32) -> Option<u32> {
+    let bytes = read_bytes(mem, ptr, 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Contents of the UTF-16 `string` at `ptr`
+pub fn read_string(mem: &[u8], ptr: u32) -> Option<String> {
+    let len = read_u32(mem, ptr.checked_sub(4)?)?;
+    let units: Vec<u16> = read_bytes(mem, ptr, len)?
+        .chunks(2)
+        .filter(|unit| unit.len() == 2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    Some(String::from_utf16_lossy(&units))
+}
+
+fn display_string(mem: &[u8], ptr: u32) -> Option<String> {
+    if ptr == 0 {
+        return Some("null".to_string());
+    }
+    read_string(mem, ptr).map(|s| format!("{:?}", s))
+}
+
+/// `Array<T>` has `buffer`, `dataStart`, `byteLength` and `length` fields
+fn display_array(mem: &[u8], ptr: u32, elem: ElementType) -> Option<String> {
+    if ptr == 0 {
+        return Some("null".to_string());
+    }
+    let data_start = read_u32(mem, ptr.checked_add(4)?)?;
+    let length = read_u32(mem, ptr.checked_add(12)?)?;
+    let data = read_bytes(mem, data_start, length.checked_mul(elem.size())?)?;
+    let elems = data
+        .chunks(elem.size() as usize)
+        .map(|bytes| {
+            let mut raw = [0; 8];
+            raw[..bytes.len()].copy_from_slice(bytes);
+            let raw = u64::from_le_bytes(raw);
+            Some(match elem {
+                ElementType::I8 => (raw as i8).to_string(),
+                ElementType::U8 => (raw as u8).to_string(),
+                ElementType::I16 => (raw as i16).to_string(),
+                ElementType::U16 => (raw as u16).to_string(),
+                ElementType::I32 => (raw as i32).to_string(),
+                ElementType::U32 => (raw as u32).to_string(),
+                ElementType::I64 => (raw as i64).to_string(),
+                ElementType::U64 => raw.to_string(),
+                ElementType::F32 => f32::from_bits(raw as u32).to_string(),
+                ElementType::F64 => f64::from_bits(raw).to_string(),
+                ElementType::String => display_string(mem, raw as u32)?,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(format!("[{}]", elems.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `mem` with `bytes` placed at `ptr`
+    fn place(mut mem: Vec<u8>, ptr: usize, bytes: &[u8]) -> Vec<u8> {
+        mem[ptr..ptr + bytes.len()].copy_from_slice(bytes);
+        mem
+    }
+
+    /// A string "hi" at 8 with its size at 4
+    fn string_mem() -> Vec<u8> {
+        place(vec![0; 12], 4, &[4, 0, 0, 0, b'h', 0, b'i', 0])
+    }
+
+    #[test]
+    fn test_read_string() {
+        let mem = string_mem();
+        assert_eq!(read_string(&mem, 8), Some("hi".to_string()));
+        // An odd size leaves out the incomplete unit
+        let odd = place(mem.clone(), 4, &[3]);
+        assert_eq!(read_string(&odd, 8), Some("h".to_string()));
+        // The size comes before the string
+        assert_eq!(read_string(&mem, 0), None);
+        assert_eq!(read_string(&mem, 2), None);
+        // The contents or the size out of `mem`
+        assert_eq!(read_string(&mem, 10), None);
+        assert_eq!(read_string(&mem, 100), None);
+        assert_eq!(read_string(&mem, u32::MAX), None);
+    }
+
+    #[test]
+    fn test_display_array() {
+        // The elements at 16 and the `Array<T>` fields at 32
+        let mem = place(vec![0; 48], 16, &[1, 0, 0, 0, 0xfe, 0xff, 0xff, 0xff]);
+        let mem = place(mem, 32, &[0, 0, 0, 0, 16, 0, 0, 0, 8, 0, 0, 0, 2, 0, 0, 0]);
+        let cases = [
+            (ElementType::I32, Some("[1, -2]")),
+            (ElementType::U32, Some("[1, 4294967294]")),
+            (ElementType::I16, Some("[1, 0]")),
+            (ElementType::U8, Some("[1, 0]")),
+            (ElementType::I64, Some("[-8589934591, 0]")),
+        ];
+        for (elem, expected) in cases.iter() {
+            let expected = expected.map(str::to_string);
+            assert_eq!(display_array(&mem, 32, *elem), expected, "{:?}", elem);
+        }
+        assert_eq!(
+            display_array(&mem, 0, ElementType::I32),
+            Some("null".into())
+        );
+        assert_eq!(display_array(&mem, 40, ElementType::I32), None);
+        assert_eq!(display_array(&mem, u32::MAX, ElementType::I32), None);
+        // Elements running past `mem`
+        let past_end = place(mem.clone(), 36, &[44]);
+        assert_eq!(display_array(&past_end, 32, ElementType::I32), None);
+        // A length whose size overflows
+        let overflowing = place(mem.clone(), 44, &[0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(display_array(&overflowing, 32, ElementType::I64), None);
+
+        // Strings and nulls as elements
+        let mut mem = string_mem();
+        mem.extend_from_slice(&[8, 0, 0, 0, 0, 0, 0, 0]);
+        mem.extend_from_slice(&[0, 0, 0, 0, 12, 0, 0, 0, 8, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(
+            display_array(&mem, 20, ElementType::String),
+            Some(r#"["hi", null]"#.to_string())
+        );
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        let cases = [
+            ("string", Ok(Format::String)),
+            ("array:i32", Ok(Format::Array(ElementType::I32))),
+            ("array:string", Ok(Format::Array(ElementType::String))),
+        ];
+        for (s, expected) in cases.iter() {
+            assert_eq!(s.parse::<Format>(), *expected, "{}", s);
+        }
+        assert_eq!(
+            "array:u128".parse::<Format>(),
+            Err("unknown element type u128".to_string())
+        );
+        for s in ["", "array", "string:i32", "map:i32"].iter() {
+            assert!(s.parse::<Format>().is_err(), "{}", s);
+        }
+    }
+}
@@ -14,6 +14,10 @@ impl AliasCommand for BacktraceCommand {
         "bt"
     }
 
+    fn description(&self) -> &'static str {
+        "Show the current thread's call stack. Same as 'thread backtrace'."
+    }
+
     fn run(&self, _args: Vec<&str>) -> Result<String> {
         Ok("thread backtrace".to_string())
     }
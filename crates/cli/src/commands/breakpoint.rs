@@ -18,6 +18,16 @@ enum Opts {
     Set {
         #[structopt(name = "SYMBOL NAME")]
         name: String,
+        /// Only stop in the module loaded with this name
+        #[structopt(short, long)]
+        module: Option<String>,
+    },
+    /// Stops when memory.grow fails or grows a memory over the threshold
+    #[structopt(name = "memory-grow")]
+    MemoryGrow {
+        /// Also stop when a memory grows over this many bytes
+        #[structopt(long)]
+        threshold: Option<usize>,
     },
 }
 
@@ -33,11 +43,15 @@ impl<D: Debugger> Command<D> for BreakpointCommand {
     fn run(&self, debugger: &mut D, _context: &CommandContext, args: Vec<&str>) -> Result<()> {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
-            Opts::Set { name } => {
-                let breakpoint = Breakpoint::Function { name };
+            Opts::Set { name, module } => {
+                let breakpoint = Breakpoint::Function { name, module };
                 debugger.set_breakpoint(breakpoint);
                 Ok(())
             }
+            Opts::MemoryGrow { threshold } => {
+                debugger.set_breakpoint(Breakpoint::MemoryGrow { threshold });
+                Ok(())
+            }
         }
     }
 }
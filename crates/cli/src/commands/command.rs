@@ -2,10 +2,55 @@ use super::debugger::Debugger;
 use super::sourcemap::SourceMap;
 use super::subroutine::SubroutineMap;
 use anyhow::Result;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasminspect_vm::ModuleIndex;
 
-pub struct CommandContext<'a> {
+pub struct DebugInfo {
     pub sourcemap: Box<dyn SourceMap>,
-    pub subroutine: Box<dyn SubroutineMap + 'a>,
+    pub subroutine: Box<dyn SubroutineMap>,
+}
+
+/// Debug info of each loaded module. Modules can be added by commands,
+/// so the maps are behind `RefCell`.
+pub struct CommandContext {
+    debug_infos: RefCell<HashMap<ModuleIndex, Rc<DebugInfo>>>,
+    directory_maps: RefCell<Vec<(String, String)>>,
+}
+
+impl CommandContext {
+    pub fn new() -> Self {
+        Self {
+            debug_infos: RefCell::new(HashMap::new()),
+            directory_maps: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn add_debug_info(&self, module_index: ModuleIndex, debug_info: DebugInfo) {
+        for (from, to) in self.directory_maps.borrow().iter() {
+            debug_info
+                .sourcemap
+                .set_directory_map(from.clone(), to.clone());
+        }
+        self.debug_infos
+            .borrow_mut()
+            .insert(module_index, Rc::new(debug_info));
+    }
+
+    pub fn debug_info(&self, module_index: ModuleIndex) -> Option<Rc<DebugInfo>> {
+        self.debug_infos.borrow().get(&module_index).cloned()
+    }
+
+    /// Remap source paths of all modules, including ones added later
+    pub fn set_directory_map(&self, from: String, to: String) {
+        for debug_info in self.debug_infos.borrow().values() {
+            debug_info
+                .sourcemap
+                .set_directory_map(from.clone(), to.clone());
+        }
+        self.directory_maps.borrow_mut().push((from, to));
+    }
 }
 
 pub trait Command<D: Debugger> {
@@ -1,13 +1,26 @@
 use anyhow::Result;
-use wasminspect_vm::{Instruction, ModuleIndex, Signal, Store, WasmValue};
+use wasminspect_vm::{Instruction, Module, ModuleIndex, Signal, Store, WasmValue};
+use wasminspect_wasi::WasiTrace;
 
 pub enum Breakpoint {
-    Function { name: String },
+    Function {
+        name: String,
+        /// Only stop in functions of the module loaded with this name
+        module: Option<String>,
+    },
+    /// Stop when `memory.grow` fails, or grows a memory over `threshold` bytes
+    MemoryGrow { threshold: Option<usize> },
 }
 
 pub enum RunResult {
     Finish(Vec<WasmValue>),
     Breakpoint,
+    /// The program exited with the status
+    Exit(i32),
+    /// `_initialize` of a reactor returned, so its exports can be run
+    Initialized,
+    /// Stopped at the start of `_initialize` of a reactor
+    Initializing,
 }
 
 #[derive(Clone, Copy)]
@@ -17,20 +30,62 @@ pub enum StepStyle {
     StepOut,
 }
 
+#[derive(Clone, Copy)]
+pub enum MockValue {
+    Int(i64),
+    Float(f64),
+}
+
+/// An argument given to `run`. Numbers without a type take the type of the
+/// parameter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunArg {
+    Int(i64),
+    Float(f64),
+    Typed(WasmValue),
+}
+
+#[derive(Clone)]
+pub struct ImportMock {
+    pub module: String,
+    pub field: String,
+    pub results: Vec<MockValue>,
+    /// Stop execution after the mock is called
+    pub stop: bool,
+}
+
 pub struct FunctionFrame {
     pub module_index: ModuleIndex,
     pub argument_count: usize,
 }
 
 pub trait Debugger {
-    fn run(&mut self, name: Option<String>) -> Result<RunResult>;
+    fn run(&mut self, name: Option<String>, args: Vec<RunArg>) -> Result<RunResult>;
     fn is_running(&self) -> bool;
     fn frame(&self) -> Vec<String>;
     fn current_frame(&self) -> Option<FunctionFrame>;
     fn locals(&self) -> Vec<WasmValue>;
+    /// Name of a local of the current function from the name section
+    fn local_name(&self, index: usize) -> Option<String>;
     fn memory(&self) -> Result<Vec<u8>>;
     fn store(&self) -> &Store;
+    /// Index of the module which `run` runs
+    fn main_module(&self) -> Option<ModuleIndex>;
+    /// Arguments passed to the program after its name from the next run
+    fn set_args(&mut self, args: Vec<String>);
+    /// Environment variables of the program from the next run
+    fn set_envs(&mut self, envs: Vec<(String, String)>);
+    /// Whether `run` stops at the start of `_initialize` of a reactor
+    fn set_stop_at_initialize(&mut self, stop: bool);
+    fn wasi_trace(&self) -> &WasiTrace;
+    /// Change which WASI calls are logged
+    fn set_wasi_trace(&mut self, trace: WasiTrace);
+    /// The decoded module loaded at `module_index`
+    fn module(&self, module_index: ModuleIndex) -> Option<&Module>;
     fn set_breakpoint(&mut self, breakpoint: Breakpoint);
+    fn imports(&self) -> Result<Vec<String>>;
+    fn add_module(&mut self, name: String, bytes: &[u8]) -> Result<ModuleIndex>;
+    fn mock_import(&mut self, mock: ImportMock) -> Result<()>;
     fn stack_values(&self) -> Vec<String>;
     fn instructions(&self) -> Result<(&[Instruction], usize)>;
     fn step(&self, style: StepStyle) -> Result<Signal>;
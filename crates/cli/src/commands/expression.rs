@@ -1,7 +1,9 @@
 use super::command::{Command, CommandContext};
 use super::debugger::Debugger;
+use super::list::current_debug_info;
 use anyhow::{anyhow, Result};
 use std::convert::TryInto;
+use wasminspect_assemblyscript::Format;
 use wasminspect_vm::WasmValue;
 
 pub struct ExpressionCommand {}
@@ -17,6 +19,10 @@ use structopt::StructOpt;
 struct Opts {
     #[structopt(name = "SYMBOL")]
     symbol: String,
+    /// Show the local named SYMBOL as an AssemblyScript `string` or
+    /// `array:TYPE`, like `array:i32`
+    #[structopt(long = "as")]
+    format: Option<Format>,
 }
 
 impl<D: Debugger> Command<D> for ExpressionCommand {
@@ -30,6 +36,9 @@ impl<D: Debugger> Command<D> for ExpressionCommand {
 
     fn run(&self, debugger: &mut D, context: &CommandContext, args: Vec<&str>) -> Result<()> {
         let opts = Opts::from_iter_safe(args)?;
+        if let Some(format) = opts.format {
+            return display_managed(debugger, &opts.symbol, format);
+        }
         let (insts, next_index) = debugger.instructions()?;
         let current_index = if next_index == 0 { 0 } else { next_index - 1 };
         let current_inst = insts[current_index].clone();
@@ -45,7 +54,8 @@ impl<D: Debugger> Command<D> for ExpressionCommand {
             WasmValue::I32(v) => v,
             x => return Err(anyhow!("invalid type rbp: '{:?}'", x)),
         };
-        context.subroutine.display_variable(
+        let debug_info = current_debug_info(debugger, context)?;
+        debug_info.subroutine.display_variable(
             current_inst.offset,
             TryInto::<u32>::try_into(*rbp)?,
             &debugger.memory()?,
@@ -54,3 +64,23 @@ impl<D: Debugger> Command<D> for ExpressionCommand {
         Ok(())
     }
 }
+
+/// Show a local pointing to a managed object. Locals are found by the name
+/// section since AssemblyScript emits no DWARF.
+fn display_managed<D: Debugger>(debugger: &D, symbol: &str, format: Format) -> Result<()> {
+    let locals = debugger.locals();
+    let index = (0..locals.len())
+        .find(|index| debugger.local_name(*index).as_deref() == Some(symbol))
+        .ok_or(anyhow!("no local named {}", symbol))?;
+    let ptr = match locals[index] {
+        WasmValue::I32(v) => v as u32,
+        x => return Err(anyhow!("invalid type of pointer: '{:?}'", x)),
+    };
+    let text = format.display(&debugger.memory()?, ptr).ok_or(anyhow!(
+        "{} points out of memory: 0x{:x}",
+        symbol,
+        ptr
+    ))?;
+    println!("{}", text);
+    Ok(())
+}
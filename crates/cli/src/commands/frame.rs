@@ -1,5 +1,6 @@
 use super::command::{Command, CommandContext};
 use super::debugger::Debugger;
+use super::list::current_debug_info;
 use anyhow::Result;
 
 use structopt::StructOpt;
@@ -34,7 +35,10 @@ impl<D: Debugger> Command<D> for FrameCommand {
                 let (insts, next_index) = debugger.instructions()?;
                 let current_index = if next_index == 0 { 0 } else { next_index - 1 };
                 let current_inst = insts[current_index].clone();
-                let variable_names = context.subroutine.variable_name_list(current_inst.offset)?;
+                let debug_info = current_debug_info(debugger, context)?;
+                let variable_names = debug_info
+                    .subroutine
+                    .variable_name_list(current_inst.offset)?;
                 for variable in variable_names {
                     println!("{}: {}", variable.name, variable.type_name);
                 }
@@ -1,6 +1,7 @@
 use super::command::{Command, CommandContext};
 use super::debugger::Debugger;
 use anyhow::{anyhow, Result};
+use wasminspect_vm::{ExternType, ModuleIndex};
 
 use structopt::StructOpt;
 
@@ -14,10 +15,15 @@ impl GlobalCommand {
 
 #[derive(StructOpt)]
 enum Opts {
+    /// Show globals of the current module with their types and values
+    #[structopt(name = "list")]
+    List,
+    /// Show the value of a global
     #[structopt(name = "read")]
     Read {
-        #[structopt(name = "INDEX")]
-        index: usize,
+        /// Name from the name section or an export name, or an index
+        #[structopt(name = "NAME_OR_INDEX")]
+        name: String,
     },
 }
 
@@ -34,16 +40,63 @@ impl<D: Debugger> Command<D> for GlobalCommand {
         let opts = Opts::from_iter_safe(args)?;
         use wasminspect_vm::*;
         match opts {
-            Opts::Read { index } => {
+            Opts::List => {
+                let store: &Store = debugger.store();
+                let mod_index = match debugger.current_frame() {
+                    Some(frame) => frame.module_index,
+                    None => match debugger.main_module() {
+                        Some(index) => index,
+                        None => return Err(anyhow!("No module loaded")),
+                    },
+                };
+                let exports = store.exports(mod_index);
+                for (addr, global) in store.globals(mod_index) {
+                    let global = global.borrow();
+                    let ty = ExternType::Global(*global.ty());
+                    let mut line = format!("{:3}: {} = {:?}", addr.index(), ty, global.value());
+                    for export in &exports {
+                        if let (ExternType::Global(_), Some(index)) = (&export.ty, export.index) {
+                            if index == addr.index() {
+                                line.push_str(&format!(" (export \"{}\")", export.name));
+                            }
+                        }
+                    }
+                    println!("{}", line);
+                }
+                Ok(())
+            }
+            Opts::Read { name } => {
                 let store: &Store = debugger.store();
                 let mod_index = match debugger.current_frame() {
                     Some(frame) => frame.module_index,
                     None => return Err(anyhow!("function frame not found")),
                 };
-                let global = store.global(GlobalAddr::new_unsafe(mod_index, index));
+                let index = global_index(debugger, mod_index, &name)
+                    .ok_or(anyhow!("no global named {}", name))?;
+                let global = store
+                    .try_global(GlobalAddr::new_unsafe(mod_index, index))
+                    .ok_or(anyhow!("no global at index {}", index))?;
                 println!("{:?}", global.borrow().value());
                 Ok(())
             }
         }
     }
 }
+
+/// Resolve a global by its name in the name section, then by export name,
+/// and fall back to an index
+fn global_index<D: Debugger>(debugger: &D, module_index: ModuleIndex, name: &str) -> Option<usize> {
+    let by_name = debugger
+        .module(module_index)
+        .and_then(|module| module.names().global_index(name));
+    if let Some(index) = by_name {
+        return Some(index as usize);
+    }
+    let by_export = debugger
+        .store()
+        .exports(module_index)
+        .into_iter()
+        .find(|export| export.name == name && matches!(export.ty, ExternType::Global(_)))
+        .and_then(|export| export.index);
+    by_export.or_else(|| name.parse().ok())
+}
@@ -0,0 +1,78 @@
+use super::command::{Command, CommandContext};
+use super::debugger::Debugger;
+use anyhow::Result;
+
+use structopt::StructOpt;
+
+pub struct ImageCommand {}
+
+impl ImageCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// List loaded modules including host modules
+    #[structopt(name = "list")]
+    List {
+        /// Also show imports and exports with their types
+        #[structopt(short, long)]
+        verbose: bool,
+    },
+}
+
+impl<D: Debugger> Command<D> for ImageCommand {
+    fn name(&self) -> &'static str {
+        "image"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for accessing information for one or more modules."
+    }
+
+    fn run(&self, debugger: &mut D, _context: &CommandContext, args: Vec<&str>) -> Result<()> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::List { verbose } => {
+                for line in list_modules(debugger, verbose) {
+                    println!("{}", line);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Lines describing the loaded modules, shared with `target module list`
+pub fn list_modules<D: Debugger>(debugger: &D, verbose: bool) -> Vec<String> {
+    let store = debugger.store();
+    let main_module = debugger.main_module();
+    let mut lines = Vec::new();
+    for module in store.modules() {
+        let name = match module.name {
+            Some(name) => name,
+            None if Some(module.index) == main_module => "<main>".to_string(),
+            None => "<anonymous>".to_string(),
+        };
+        if module.is_host {
+            lines.push(format!("[{:3}] {} (host)", module.index.0, name));
+        } else {
+            lines.push(format!("[{:3}] {}", module.index.0, name));
+        }
+        if !verbose {
+            continue;
+        }
+        for import in store.imports(module.index) {
+            lines.push(format!(
+                "      import {}.{}: {}",
+                import.module, import.field, import.ty
+            ));
+        }
+        for export in store.exports(module.index) {
+            lines.push(format!("      export {}: {}", export.name, export.ty));
+        }
+    }
+    lines
+}
@@ -0,0 +1,107 @@
+use super::command::{Command, CommandContext};
+use super::debugger::{Debugger, ImportMock, MockValue};
+use anyhow::{anyhow, Result};
+
+use structopt::StructOpt;
+
+pub struct ImportCommand {}
+
+impl ImportCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// Show all imports and what they resolve to
+    #[structopt(name = "list")]
+    List,
+    /// Replace an imported function with a mock returning fixed values
+    #[structopt(name = "mock")]
+    Mock {
+        /// The import to mock, like `env.get_config`
+        #[structopt(name = "MODULE.FIELD")]
+        name: String,
+        /// Values to return, repeated for each result
+        #[structopt(long = "return", number_of_values = 1)]
+        returns: Vec<String>,
+        /// Return this errno as the only result
+        #[structopt(long, conflicts_with = "returns")]
+        errno: Option<i32>,
+        /// Stop execution like a breakpoint after the mock is called
+        #[structopt(long)]
+        stop: bool,
+    },
+}
+
+impl<D: Debugger> Command<D> for ImportCommand {
+    fn name(&self) -> &'static str {
+        "import"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for operating on imports."
+    }
+
+    fn run(&self, debugger: &mut D, _context: &CommandContext, args: Vec<&str>) -> Result<()> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::List => {
+                for import in debugger.imports()? {
+                    println!("{}", import);
+                }
+                Ok(())
+            }
+            Opts::Mock {
+                name,
+                returns,
+                errno,
+                stop,
+            } => {
+                let mut parts = name.splitn(2, '.');
+                let (module, field) = match (parts.next(), parts.next()) {
+                    (Some(module), Some(field)) => (module.to_string(), field.to_string()),
+                    _ => return Err(anyhow!("import name must be MODULE.FIELD: {}", name)),
+                };
+                let results = match errno {
+                    Some(errno) => vec![MockValue::Int(errno as i64)],
+                    None => returns
+                        .iter()
+                        .map(|value| parse_mock_value(value))
+                        .collect::<Result<Vec<_>>>()?,
+                };
+                debugger.mock_import(ImportMock {
+                    module,
+                    field,
+                    results,
+                    stop,
+                })
+            }
+        }
+    }
+}
+
+fn parse_mock_value(value: &str) -> Result<MockValue> {
+    if let Ok(value) = value.parse::<i64>() {
+        Ok(MockValue::Int(value))
+    } else if let Ok(value) = value.parse::<f64>() {
+        Ok(MockValue::Float(value))
+    } else {
+        Err(anyhow!("invalid return value: {}", value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Opts;
+    use structopt::StructOpt;
+
+    #[test]
+    fn mock_errno_conflicts_with_return() {
+        let args = vec!["import", "mock", "env.f", "--return", "1", "--errno", "8"];
+        assert!(Opts::from_iter_safe(args).is_err());
+        let args = vec!["import", "mock", "env.f", "--errno", "8"];
+        assert!(Opts::from_iter_safe(args).is_ok());
+    }
+}
@@ -1,7 +1,8 @@
-use super::command::{Command, CommandContext};
+use super::command::{Command, CommandContext, DebugInfo};
 use super::debugger::Debugger;
-use super::sourcemap::{ColumnType, LineInfo, SourceMap};
+use super::sourcemap::{ColumnType, LineInfo};
 use anyhow::{anyhow, Result};
+use std::rc::Rc;
 
 pub struct ListCommand {}
 
@@ -21,17 +22,31 @@ impl<D: Debugger> Command<D> for ListCommand {
     }
 
     fn run(&self, debugger: &mut D, context: &CommandContext, _args: Vec<&str>) -> Result<()> {
-        let line_info = next_line_info(debugger, &context.sourcemap)?;
+        let line_info = next_line_info(debugger, context)?;
         display_source(line_info)
     }
 }
 
-pub fn next_line_info<D: Debugger>(
+/// Debug info of the module which the current frame belongs to
+pub fn current_debug_info<D: Debugger>(
     debugger: &D,
-    sourcemap: &Box<dyn SourceMap>,
-) -> Result<LineInfo> {
+    context: &CommandContext,
+) -> Result<Rc<DebugInfo>> {
+    let frame = debugger
+        .current_frame()
+        .ok_or(anyhow!("function frame not found"))?;
+    context
+        .debug_info(frame.module_index)
+        .ok_or(anyhow!("Debug info not found"))
+}
+
+pub fn next_line_info<D: Debugger>(debugger: &D, context: &CommandContext) -> Result<LineInfo> {
+    let debug_info = current_debug_info(debugger, context)?;
     let (insts, next_index) = debugger.instructions()?;
-    match sourcemap.find_line_info(insts[next_index].offset) {
+    match debug_info
+        .sourcemap
+        .find_line_info(insts[next_index].offset)
+    {
         Some(info) => Ok(info),
         None => Err(anyhow!("Source info not found")),
     }
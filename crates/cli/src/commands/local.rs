@@ -35,7 +35,10 @@ impl<D: Debugger> Command<D> for LocalCommand {
         match opts {
             Opts::Read { index: None } => {
                 for (index, value) in debugger.locals().iter().enumerate() {
-                    println!("{: <3}: {:?}", index, value);
+                    match debugger.local_name(index) {
+                        Some(name) => println!("{: <3}: {} = {:?}", index, name, value),
+                        None => println!("{: <3}: {:?}", index, value),
+                    }
                 }
             }
             Opts::Read { index: Some(index) } => {
@@ -11,6 +11,8 @@ pub mod disassemble;
 pub mod expression;
 pub mod frame;
 pub mod global;
+pub mod image;
+pub mod import;
 pub mod list;
 pub mod local;
 pub mod memory;
@@ -18,4 +20,6 @@ pub mod process;
 pub mod run;
 pub mod settings;
 pub mod stack;
+pub mod target;
 pub mod thread;
+pub mod vfs;
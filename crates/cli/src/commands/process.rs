@@ -1,7 +1,10 @@
 use super::command::{Command, CommandContext};
 use super::debugger::Debugger;
-use anyhow::Result;
+use super::run::launch;
+use anyhow::{anyhow, Result};
+use std::io::Write;
 use wasminspect_vm::Signal;
+use wasminspect_wasi::WasiContext;
 
 use structopt::StructOpt;
 
@@ -17,6 +20,20 @@ impl ProcessCommand {
 enum Opts {
     #[structopt(name = "continue")]
     Continue,
+    /// Launch the program with new arguments, like `run`
+    #[structopt(name = "launch")]
+    Launch {
+        /// Arguments passed to the program
+        #[structopt(last = true)]
+        args: Vec<String>,
+    },
+    /// Show the output captured with `--stdout capture` or `--stderr capture`
+    #[structopt(name = "output")]
+    Output {
+        /// Show stderr instead of stdout
+        #[structopt(long)]
+        stderr: bool,
+    },
 }
 
 impl<D: Debugger> Command<D> for ProcessCommand {
@@ -37,7 +54,33 @@ impl<D: Debugger> Command<D> for ProcessCommand {
                 Signal::Breakpoint => {
                     println!("Hit breakpoint");
                 }
+                Signal::Exit(code) => {
+                    println!("Process exited with status {}", code);
+                }
             },
+            Opts::Launch { args } => {
+                debugger.set_args(args);
+                launch(debugger, None, Vec::new())?;
+            }
+            Opts::Output { stderr } => {
+                let ctx = debugger
+                    .store()
+                    .get_embed_context::<WasiContext>()
+                    .ok_or(anyhow!("No WASI context"))?;
+                let output = ctx.captured_output();
+                let (bytes, dropped) = if stderr {
+                    (&output.stderr, output.stderr_dropped)
+                } else {
+                    (&output.stdout, output.stdout_dropped)
+                };
+                if dropped > 0 {
+                    eprintln!("({} earlier bytes were dropped)", dropped);
+                }
+                std::io::stdout().write_all(bytes)?;
+                if !bytes.is_empty() && !bytes.ends_with(b"\n") {
+                    println!();
+                }
+            }
         }
         Ok(())
     }
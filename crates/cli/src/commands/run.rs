@@ -1,10 +1,11 @@
 use super::command::{Command, CommandContext};
-use super::debugger::{Debugger, RunResult};
+use super::debugger::{Debugger, RunArg, RunResult};
 use std::io::Write;
+use wasminspect_vm::{type_name, WasmValue};
 
 use structopt::StructOpt;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 pub struct RunCommand {}
 
@@ -18,6 +19,10 @@ impl RunCommand {
 struct Opts {
     #[structopt(name = "FUNCTION NAME")]
     name: Option<String>,
+    /// Arguments like `1`, `0x10`, `2.5` or with a type like `f32:1.5`.
+    /// Put them after `--` if any starts with `-`.
+    #[structopt(name = "ARGS", parse(try_from_str = parse_arg))]
+    args: Vec<RunArg>,
 }
 impl<D: Debugger> Command<D> for RunCommand {
     fn name(&self) -> &'static str {
@@ -29,27 +34,175 @@ impl<D: Debugger> Command<D> for RunCommand {
     }
     fn run(&self, debugger: &mut D, _context: &CommandContext, args: Vec<&str>) -> Result<()> {
         let opts = Opts::from_iter_safe(args)?;
-        if debugger.is_running() {
-            print!("There is a running process, kill it and restart?: [Y/n] ");
-            std::io::stdout().flush().unwrap();
-            let stdin = std::io::stdin();
-            let mut input = String::new();
-            stdin.read_line(&mut input).unwrap();
-            if input != "Y\n" {
-                return Ok(());
+        launch(debugger, opts.name, opts.args)
+    }
+}
+
+/// Parse a number, or a value with its type like `i64:0x10`
+fn parse_arg(arg: &str) -> Result<RunArg> {
+    let mut parts = arg.splitn(2, ':');
+    let parsed = match (parts.next(), parts.next()) {
+        (Some("i32"), Some(value)) => parse_int(value)
+            .filter(|v| *v >= i32::MIN as i64 && *v <= u32::MAX as i64)
+            .map(|v| RunArg::Typed(WasmValue::I32(v as i32))),
+        (Some("i64"), Some(value)) => parse_int(value).map(|v| RunArg::Typed(WasmValue::I64(v))),
+        (Some("f32"), Some(value)) => value
+            .parse::<f32>()
+            .ok()
+            .map(|v| RunArg::Typed(WasmValue::from(v))),
+        (Some("f64"), Some(value)) => value
+            .parse::<f64>()
+            .ok()
+            .map(|v| RunArg::Typed(WasmValue::from(v))),
+        (Some(ty), Some(_)) => return Err(anyhow!("unknown type {}", ty)),
+        _ => parse_int(arg)
+            .map(RunArg::Int)
+            .or_else(|| arg.parse::<f64>().ok().map(RunArg::Float)),
+    };
+    parsed.ok_or_else(|| anyhow!("invalid argument: {}", arg))
+}
+
+/// Parse a decimal or `0x` prefixed integer. Unsigned values over the
+/// signed range wrap around.
+fn parse_int(text: &str) -> Option<i64> {
+    let negative = text.starts_with('-');
+    let digits = if negative { &text[1..] } else { text };
+    let value = if digits.starts_with("0x") {
+        u64::from_str_radix(digits.trim_start_matches("0x"), 16).ok()?
+    } else {
+        digits.parse::<u64>().ok()?
+    };
+    if !negative {
+        Some(value as i64)
+    } else if value <= 1 << 63 {
+        Some((value as i64).wrapping_neg())
+    } else {
+        None
+    }
+}
+
+/// Values with their types like `[i32:3, f32:1.5]`
+fn format_values(values: &[WasmValue]) -> String {
+    let values: Vec<String> = values
+        .iter()
+        .map(|value| {
+            let ty = type_name(value.value_type());
+            match *value {
+                WasmValue::I32(v) => format!("{}:{}", ty, v),
+                WasmValue::I64(v) => format!("{}:{}", ty, v),
+                WasmValue::F32(_) => format!("{}:{}", ty, value.as_f32().unwrap()),
+                WasmValue::F64(_) => format!("{}:{}", ty, value.as_f64().unwrap()),
             }
+        })
+        .collect();
+    format!("[{}]", values.join(", "))
+}
+
+/// Run `name` with `args` or the entry function, asking before restarting a
+/// running process. Shared with `process launch`.
+pub fn launch<D: Debugger>(
+    debugger: &mut D,
+    name: Option<String>,
+    args: Vec<RunArg>,
+) -> Result<()> {
+    if debugger.is_running() {
+        print!("There is a running process, kill it and restart?: [Y/n] ");
+        std::io::stdout().flush().unwrap();
+        let stdin = std::io::stdin();
+        let mut input = String::new();
+        stdin.read_line(&mut input).unwrap();
+        if input != "Y\n" {
+            return Ok(());
         }
-        match debugger.run(opts.name) {
-            Ok(RunResult::Finish(values)) => {
-                println!("{:?}", values);
-            }
-            Ok(RunResult::Breakpoint) => {
-                println!("Hit breakpoint");
-            }
-            Err(msg) => {
-                eprintln!("{}", msg);
-            }
+    }
+    match debugger.run(name, args) {
+        Ok(RunResult::Finish(values)) => {
+            println!("{}", format_values(&values));
         }
-        Ok(())
+        Ok(RunResult::Breakpoint) => {
+            println!("Hit breakpoint");
+        }
+        Ok(RunResult::Exit(code)) => {
+            println!("Process exited with status {}", code);
+        }
+        Ok(RunResult::Initialized) => {
+            println!("Reactor initialized, run its exports with `run FUNCTION NAME`");
+        }
+        Ok(RunResult::Initializing) => {
+            println!("Stopped at _initialize");
+        }
+        Err(msg) => {
+            eprintln!("{}", msg);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_int() {
+        let cases = vec![
+            ("42", Some(42)),
+            ("0x10", Some(16)),
+            ("-1", Some(-1)),
+            ("-0x10", Some(-16)),
+            ("-0x8000000000000000", Some(i64::MIN)),
+            ("-0x8000000000000001", None),
+            ("18446744073709551615", Some(-1)),
+            ("18446744073709551616", None),
+            ("0xg", None),
+            ("", None),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_int(text), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_parse_arg() {
+        let cases = vec![
+            ("7", Some(RunArg::Int(7))),
+            ("-0x7", Some(RunArg::Int(-7))),
+            ("1.5", Some(RunArg::Float(1.5))),
+            ("i32:0xffffffff", Some(RunArg::Typed(WasmValue::I32(-1)))),
+            (
+                "i32:-2147483648",
+                Some(RunArg::Typed(WasmValue::I32(i32::MIN))),
+            ),
+            ("i32:4294967296", None),
+            ("i32:-2147483649", None),
+            ("i64:-5", Some(RunArg::Typed(WasmValue::I64(-5)))),
+            ("f32:1.5", Some(RunArg::Typed(WasmValue::from(1.5f32)))),
+            ("f64:-2", Some(RunArg::Typed(WasmValue::from(-2f64)))),
+            ("f32:x", None),
+            ("u8:1", None),
+            ("abc", None),
+        ];
+        for (arg, expected) in cases {
+            assert_eq!(parse_arg(arg).ok(), expected, "{}", arg);
+        }
+        assert_eq!(
+            parse_arg("u8:1").unwrap_err().to_string(),
+            "unknown type u8"
+        );
+        assert_eq!(
+            parse_arg("i32:4294967296").unwrap_err().to_string(),
+            "invalid argument: i32:4294967296"
+        );
+    }
+
+    #[test]
+    fn test_format_values() {
+        assert_eq!(format_values(&[]), "[]");
+        let values = [
+            WasmValue::I32(3),
+            WasmValue::I64(-4),
+            WasmValue::from(1.5f32),
+            WasmValue::from(0.25f64),
+        ];
+        assert_eq!(format_values(&values), "[i32:3, i64:-4, f32:1.5, f64:0.25]");
     }
 }
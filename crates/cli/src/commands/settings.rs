@@ -1,6 +1,6 @@
 use super::command::{Command, CommandContext};
 use super::debugger::Debugger;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 use structopt::StructOpt;
 
@@ -14,12 +14,12 @@ impl SettingsCommand {
 
 #[derive(StructOpt)]
 enum Opts {
+    /// Set `directory.map FROM TO`, `target.env-vars KEY=VALUE...`,
+    /// `target.run-args [--] ARGS...`, `target.stop-at-initialize true|false`,
+    /// `wasi.trace true|false` or `wasi.trace-filter FUNCTIONS...`.
+    /// Put `--` before run args which start with `-`
     #[structopt(name = "set")]
-    Set {
-        key: String,
-        operand1: String,
-        operand2: String,
-    },
+    Set { key: String, operands: Vec<String> },
 }
 
 impl<D: Debugger> Command<D> for SettingsCommand {
@@ -31,16 +31,38 @@ impl<D: Debugger> Command<D> for SettingsCommand {
         "Commands for setting environment"
     }
 
-    fn run(&self, _debugger: &mut D, context: &CommandContext, args: Vec<&str>) -> Result<()> {
+    fn run(&self, debugger: &mut D, context: &CommandContext, args: Vec<&str>) -> Result<()> {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
-            Opts::Set {
-                key,
-                operand1,
-                operand2,
-            } => match key.as_str() {
-                "directory.map" => {
-                    context.sourcemap.set_directory_map(operand1, operand2);
+            Opts::Set { key, operands } => match key.as_str() {
+                "directory.map" => match operands.as_slice() {
+                    [from, to] => context.set_directory_map(from.clone(), to.clone()),
+                    _ => return Err(anyhow!("directory.map takes FROM and TO")),
+                },
+                "target.env-vars" => {
+                    let envs = operands
+                        .iter()
+                        .map(|operand| parse_env(operand))
+                        .collect::<Result<Vec<_>>>()?;
+                    debugger.set_envs(envs);
+                }
+                "target.run-args" => debugger.set_args(operands),
+                "target.stop-at-initialize" => match operands.as_slice() {
+                    [stop] => debugger.set_stop_at_initialize(stop.parse()?),
+                    _ => return Err(anyhow!("target.stop-at-initialize takes true or false")),
+                },
+                "wasi.trace" => match operands.as_slice() {
+                    [enabled] => {
+                        let mut trace = debugger.wasi_trace().clone();
+                        trace.enabled = enabled.parse()?;
+                        debugger.set_wasi_trace(trace);
+                    }
+                    _ => return Err(anyhow!("wasi.trace takes true or false")),
+                },
+                "wasi.trace-filter" => {
+                    let mut trace = debugger.wasi_trace().clone();
+                    trace.filter = operands;
+                    debugger.set_wasi_trace(trace);
                 }
                 _ => println!("'{}' is not valid key", key),
             },
@@ -48,3 +70,52 @@ impl<D: Debugger> Command<D> for SettingsCommand {
         Ok(())
     }
 }
+
+/// Parse `KEY=VALUE`
+pub fn parse_env(arg: &str) -> Result<(String, String)> {
+    let mut parts = arg.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(key), Some(value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(anyhow!("expected KEY=VALUE: {}", arg)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_env, Opts};
+    use structopt::StructOpt;
+
+    fn run_args(args: Vec<&str>) -> Option<Vec<String>> {
+        match Opts::from_iter_safe(args).ok()? {
+            Opts::Set { key, operands } if key == "target.run-args" => Some(operands),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn run_args_after_double_dash() {
+        let args = vec![
+            "settings",
+            "set",
+            "target.run-args",
+            "--",
+            "-v",
+            "--out",
+            "x",
+        ];
+        assert_eq!(
+            run_args(args),
+            Some(vec!["-v".into(), "--out".into(), "x".into()])
+        );
+        let args = vec!["settings", "set", "target.run-args", "a", "b"];
+        assert_eq!(run_args(args), Some(vec!["a".into(), "b".into()]));
+    }
+
+    #[test]
+    fn env_pairs() {
+        assert_eq!(parse_env("A=1=2").unwrap(), ("A".into(), "1=2".into()));
+        assert_eq!(parse_env("A=").unwrap(), ("A".into(), "".into()));
+        assert!(parse_env("=1").is_err());
+        assert!(parse_env("A").is_err());
+    }
+}
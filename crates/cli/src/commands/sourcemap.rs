@@ -24,17 +24,3 @@ pub trait SourceMap {
     fn find_line_info(&self, offset: usize) -> Option<LineInfo>;
     fn set_directory_map(&self, from: String, to: String);
 }
-
-pub struct EmptySourceMap {}
-
-impl EmptySourceMap {
-    pub fn new() -> Self {
-        Self {}
-    }
-}
-impl SourceMap for EmptySourceMap {
-    fn find_line_info(&self, _: usize) -> Option<LineInfo> {
-        None
-    }
-    fn set_directory_map(&self, _: String, _: String) {}
-}
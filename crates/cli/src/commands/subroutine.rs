@@ -15,19 +15,3 @@ pub trait SubroutineMap {
         name: String,
     ) -> Result<()>;
 }
-
-pub struct EmptySubroutineMap {}
-
-impl EmptySubroutineMap {
-    pub fn new() -> Self {
-        Self {}
-    }
-}
-impl SubroutineMap for EmptySubroutineMap {
-    fn variable_name_list(&self, _code_offset: usize) -> Result<Vec<Variable>> {
-        Ok(vec![])
-    }
-    fn display_variable(&self, _: usize, _: u32, _: &[u8], _: String) -> Result<()> {
-        Ok(())
-    }
-}
@@ -0,0 +1,75 @@
+use super::command::{Command, CommandContext};
+use super::debugger::Debugger;
+use super::image::list_modules;
+use crate::dwarf::load_debug_info;
+use anyhow::Result;
+use log::warn;
+use std::io::Read;
+
+use structopt::StructOpt;
+
+pub struct TargetCommand {}
+
+impl TargetCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// Commands for operating on modules of the target
+    #[structopt(name = "module")]
+    Module(ModuleOpts),
+}
+
+#[derive(StructOpt)]
+enum ModuleOpts {
+    /// Load a module and register it under NAME so that other modules can import it
+    #[structopt(name = "add")]
+    Add {
+        #[structopt(name = "NAME")]
+        name: String,
+        #[structopt(name = "FILE")]
+        file: String,
+    },
+    /// List loaded modules, like `image list`
+    #[structopt(name = "list")]
+    List {
+        /// Also show imports and exports with their types
+        #[structopt(short, long)]
+        verbose: bool,
+    },
+}
+
+impl<D: Debugger> Command<D> for TargetCommand {
+    fn name(&self) -> &'static str {
+        "target"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for operating on debugger targets."
+    }
+
+    fn run(&self, debugger: &mut D, context: &CommandContext, args: Vec<&str>) -> Result<()> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::Module(ModuleOpts::Add { name, file }) => {
+                let mut buffer = Vec::new();
+                std::fs::File::open(&file)?.read_to_end(&mut buffer)?;
+                let module_index = debugger.add_module(name.clone(), &buffer)?;
+                match load_debug_info(buffer.into()) {
+                    Ok(debug_info) => context.add_debug_info(module_index, debug_info),
+                    Err(err) => warn!("No debug info for module {}: {}", name, err),
+                }
+                Ok(())
+            }
+            Opts::Module(ModuleOpts::List { verbose }) => {
+                for line in list_modules(debugger, verbose) {
+                    println!("{}", line);
+                }
+                Ok(())
+            }
+        }
+    }
+}
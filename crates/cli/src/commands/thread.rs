@@ -1,6 +1,7 @@
 use super::command::{Command, CommandContext};
 use super::debugger::{Debugger, StepStyle};
 use super::symbol::demangle_symbol;
+use wasminspect_vm::Signal;
 
 pub struct ThreadCommand {}
 
@@ -32,7 +33,7 @@ enum Opts {
 }
 
 use super::disassemble::display_asm;
-use super::list::{display_source, next_line_info};
+use super::list::{current_debug_info, display_source, next_line_info};
 impl<D: Debugger> Command<D> for ThreadCommand {
     fn name(&self) -> &'static str {
         "thread"
@@ -52,7 +53,10 @@ impl<D: Debugger> Command<D> for ThreadCommand {
                 let current_index = if next_index == 0 { 0 } else { next_index - 1 };
                 let current_inst = insts[current_index].clone();
                 let code_offset = current_inst.offset;
-                if let Some(line_info) = context.sourcemap.find_line_info(code_offset) {
+                let line_info = current_debug_info(debugger, context)
+                    .ok()
+                    .and_then(|info| info.sourcemap.find_line_info(code_offset));
+                if let Some(line_info) = line_info {
                     println!(
                         "0x{:x} `{} at {}:{}:{}`",
                         code_offset,
@@ -79,19 +83,23 @@ impl<D: Debugger> Command<D> for ThreadCommand {
                     Opts::StepOver => StepStyle::StepInstOver,
                     _ => panic!(),
                 };
-                let initial_line_info = next_line_info(debugger, &context.sourcemap)?;
+                let initial_line_info = next_line_info(debugger, context)?;
                 while {
-                    debugger.step(style)?;
-                    let line_info = next_line_info(debugger, &context.sourcemap)?;
+                    if report_exit(debugger.step(style)?) {
+                        return Ok(());
+                    }
+                    let line_info = next_line_info(debugger, context)?;
                     initial_line_info.filepath == line_info.filepath
                         && initial_line_info.line == line_info.line
                 } {}
-                let line_info = next_line_info(debugger, &context.sourcemap)?;
+                let line_info = next_line_info(debugger, context)?;
                 display_source(line_info)?;
             }
             Opts::StepOut => {
-                debugger.step(StepStyle::StepOut)?;
-                let line_info = next_line_info(debugger, &context.sourcemap)?;
+                if report_exit(debugger.step(StepStyle::StepOut)?) {
+                    return Ok(());
+                }
+                let line_info = next_line_info(debugger, context)?;
                 display_source(line_info)?;
             }
             Opts::StepInstIn | Opts::StepInstOver => {
@@ -100,10 +108,23 @@ impl<D: Debugger> Command<D> for ThreadCommand {
                     Opts::StepInstOver => StepStyle::StepInstOver,
                     _ => panic!(),
                 };
-                debugger.step(style)?;
+                if report_exit(debugger.step(style)?) {
+                    return Ok(());
+                }
                 display_asm(debugger)?;
             }
         }
         Ok(())
     }
 }
+
+/// Print the status if the program exited by the step, which leaves no
+/// source to display
+fn report_exit(signal: Signal) -> bool {
+    if let Signal::Exit(code) = signal {
+        println!("Process exited with status {}", code);
+        true
+    } else {
+        false
+    }
+}
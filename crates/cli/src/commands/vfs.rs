@@ -0,0 +1,79 @@
+use super::command::{Command, CommandContext};
+use super::debugger::Debugger;
+use anyhow::{anyhow, Result};
+use wasminspect_wasi::WasiContext;
+
+use structopt::StructOpt;
+
+pub struct VfsCommand {}
+
+impl VfsCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// List files of the in-memory filesystem
+    #[structopt(name = "list")]
+    List {
+        /// Only list files created or written by the program
+        #[structopt(short, long)]
+        changed: bool,
+    },
+    /// Print a file of the in-memory filesystem
+    #[structopt(name = "dump")]
+    Dump {
+        #[structopt(name = "PATH")]
+        path: String,
+        /// Write the contents to this host file instead
+        #[structopt(short, long)]
+        output: Option<String>,
+    },
+}
+
+impl<D: Debugger> Command<D> for VfsCommand {
+    fn name(&self) -> &'static str {
+        "vfs"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for inspecting the in-memory filesystem."
+    }
+
+    fn run(&self, debugger: &mut D, _context: &CommandContext, args: Vec<&str>) -> Result<()> {
+        let opts = Opts::from_iter_safe(args)?;
+        let vfs = debugger
+            .store()
+            .get_embed_context::<WasiContext>()
+            .and_then(|ctx| ctx.vfs())
+            .ok_or_else(|| anyhow!("No in-memory filesystem, run with --vfs"))?;
+        match opts {
+            Opts::List { changed } => {
+                for (path, file) in vfs.files() {
+                    let state = if file.created {
+                        " (created)"
+                    } else if file.modified {
+                        " (modified)"
+                    } else if changed {
+                        continue;
+                    } else {
+                        ""
+                    };
+                    println!("{:>8} /{}{}", file.data.len(), path, state);
+                }
+            }
+            Opts::Dump { path, output } => {
+                let file = vfs
+                    .file(&path)
+                    .ok_or_else(|| anyhow!("'{}' is not a file", path))?;
+                match output {
+                    Some(output) => std::fs::write(output, &file.data)?,
+                    None => println!("{}", String::from_utf8_lossy(&file.data)),
+                }
+            }
+        }
+        Ok(())
+    }
+}
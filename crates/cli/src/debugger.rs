@@ -4,56 +4,526 @@ use log::warn;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
+use wasminspect_assemblyscript::{instantiate_assemblyscript, is_assemblyscript_module};
+use wasminspect_emscripten::{instantiate_emscripten, is_emscripten_module};
 use wasminspect_vm::{
-    CallFrame, Executor, FunctionInstance, InstIndex, Instruction, Interceptor, MemoryAddr,
-    ModuleIndex, ProgramCounter, Signal, Store, Trap, WasmValue,
+    decode_validated, decode_validated_sections, type_name, Execution, FuncAddr, FunctionInstance,
+    GlobalAddr, HostFuncBody, ImportStub, Instruction, Interceptor, MemoryAddr, Module,
+    ModuleIndex, ResourceLimits, Signal, StopReason, Store, StoreError, TableAddr, Trap,
+    ValidationError, WasmValue, WASM_PAGE_SIZE,
 };
-use wasminspect_wasi::instantiate_wasi;
-use wasmparser::ModuleReader;
+use wasminspect_wasi::{
+    host_module, instantiate_wasi_with, Snapshot, WasiConfig, WasiContext, WasiTrace,
+};
+use wasmparser::{ImportSectionEntryType, Type};
+
+#[derive(Default, Clone)]
+pub struct DebuggerOptions {
+    /// Load modules even if they fail validation
+    pub skip_validation: bool,
+    /// Validate function bodies when loading modules instead of leaving
+    /// them unchecked until they are decoded on first call
+    pub validate_bodies: bool,
+    /// Stub imports which no module provides instead of failing to load
+    pub import_stub: Option<ImportStub>,
+    pub limits: ResourceLimits,
+    /// Arguments, environment variables and directories passed to WASI
+    pub wasi: WasiConfig,
+    /// Stop at the start of `_initialize` of a reactor instead of running it
+    pub stop_at_initialize: bool,
+}
+
+/// Progress of `_initialize` of a WASI reactor on the current instances
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Reactor {
+    Uninitialized,
+    Initializing,
+    Initialized,
+}
+
+/// How the last execution on the current instances ended
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum LastExecution {
+    None,
+    Returned,
+    /// Trapped, exited or still stopped
+    Unfinished,
+}
+
+/// Whether `run` can call into the current instances, with the progress of
+/// the reactor after the last execution.
+///
+/// Exports of an initialized reactor are called on the same instances.
+/// Otherwise, an exited program may have left the store in any state, so
+/// start over from fresh instances as well as when killing a running one or
+/// after `_initialize` trapped.
+fn reuse_instances(reactor: Reactor, last: LastExecution) -> (Reactor, bool) {
+    // `_initialize` may have been finished by continuing from a breakpoint
+    let reactor = match (reactor, last) {
+        (Reactor::Initializing, LastExecution::Returned) => Reactor::Initialized,
+        _ => reactor,
+    };
+    let reuse = match last {
+        LastExecution::None => reactor != Reactor::Initializing,
+        LastExecution::Returned => reactor == Reactor::Initialized,
+        LastExecution::Unfinished => false,
+    };
+    (reactor, reuse)
+}
+
+/// A user module loaded into the debugger. Modules are instantiated again in
+/// load order when the process restarts, so they keep their indices.
+struct LoadedModule {
+    name: Option<String>,
+    module: Module,
+    index: ModuleIndex,
+}
 
 pub struct MainDebugger {
+    options: DebuggerOptions,
     store: Store,
-    executor: Option<Rc<RefCell<Executor>>>,
-    module_index: Option<ModuleIndex>,
+    execution: Option<Rc<RefCell<Execution>>>,
+    modules: Vec<LoadedModule>,
+    /// Position of the module to run in `modules`
+    main_module: Option<usize>,
 
-    function_breakpoints: HashMap<String, debugger::Breakpoint>,
+    /// Keyed by the module to stop in, or `None` for all modules, and the name
+    function_breakpoints: HashMap<(Option<String>, String), debugger::Breakpoint>,
+    memory_grow_breakpoint: Option<debugger::Breakpoint>,
+    import_mocks: Vec<debugger::ImportMock>,
+    /// WASI settings changed since the store was instantiated
+    wasi_changed: bool,
+    reactor: Reactor,
 }
 
 impl MainDebugger {
-    pub fn load_module(&mut self, module: &[u8]) -> Result<()> {
-        let mut reader = ModuleReader::new(module)?;
-        if let Err(err) = wasmparser::validate(module, None) {
-            warn!("{}", err);
+    /// Load the main module binary, whose functions are run by `run`
+    pub fn load_module(&mut self, bytes: &[u8]) -> Result<ModuleIndex> {
+        if self.main_module.is_some() {
+            return Err(anyhow!("Main module is already loaded"));
         }
-        self.module_index = Some(self.store.load_module(None, &mut reader)?);
-        Ok(())
+        let module_index = self.load_module_internal(None, bytes)?;
+        self.main_module = Some(self.modules.len() - 1);
+        Ok(module_index)
+    }
+
+    /// With `skip_validation`, validation failures are only logged so that
+    /// invalid modules can still be inspected. Function bodies are checked
+    /// only with `validate_bodies`, so that large modules load without
+    /// decoding every body up front.
+    fn load_module_internal(&mut self, name: Option<String>, bytes: &[u8]) -> Result<ModuleIndex> {
+        let validate_bodies = self.options.validate_bodies;
+        let module = if !self.options.skip_validation {
+            let decoded = if validate_bodies {
+                decode_validated(bytes)
+            } else {
+                decode_validated_sections(bytes)
+            };
+            decoded.map_err(|err| Self::describe_validation_error(bytes, err))?
+        } else {
+            let validated = if validate_bodies {
+                wasminspect_vm::validate(bytes)
+            } else {
+                wasminspect_vm::validate_sections(bytes)
+            };
+            if let Err(err) = validated {
+                warn!("{}", err);
+            }
+            Module::from_bytes(bytes)?
+        };
+        let module_index = Self::instantiate(&mut self.store, name.clone(), &module)
+            .map_err(Self::describe_instantiation_error)?;
+        self.modules.push(LoadedModule {
+            name,
+            module,
+            index: module_index,
+        });
+        for mock in self.import_mocks.clone() {
+            self.install_mock_into(self.modules.len() - 1, &mock)?;
+        }
+        Ok(module_index)
     }
-    pub fn new() -> Result<Self> {
+
+    fn main_module_index(&self) -> Option<ModuleIndex> {
+        self.main_module
+            .map(|position| self.modules[position].index)
+    }
+
+    fn module_name(&self, module_index: ModuleIndex) -> Option<&String> {
+        self.modules
+            .iter()
+            .find(|loaded| loaded.index == module_index)
+            .and_then(|loaded| loaded.name.as_ref())
+    }
+
+    /// Shared libraries are linked by the dynamic linker of the store
+    fn instantiate(
+        store: &mut Store,
+        name: Option<String>,
+        module: &Module,
+    ) -> Result<ModuleIndex> {
+        Self::load_env(store, module);
+        if module.dylink().is_some() {
+            store.load_dylink_module(name, module)
+        } else {
+            store.instantiate_module(name, module)
+        }
+    }
+
+    /// Provide the `env` imports of the toolchain which built `module`,
+    /// unless a module named `env` is already loaded
+    fn load_env(store: &mut Store, module: &Module) {
+        if store.module_by_name("env").is_some() {
+            return;
+        }
+        if is_emscripten_module(module) {
+            let (ctx, env) = instantiate_emscripten(module);
+            store.add_embed_context(Box::new(ctx));
+            store.load_host_module("env".to_string(), env);
+        } else if is_assemblyscript_module(module) {
+            store.load_host_module("env".to_string(), instantiate_assemblyscript(module));
+        }
+    }
+
+    pub fn new(options: DebuggerOptions) -> Result<Self> {
         Ok(Self {
-            store: Self::instantiate_store(),
-            executor: None,
-            module_index: None,
+            store: Self::instantiate_store(&options)?,
+            options,
+            execution: None,
+            modules: Vec::new(),
+            main_module: None,
             function_breakpoints: HashMap::new(),
+            memory_grow_breakpoint: None,
+            import_mocks: Vec::new(),
+            wasi_changed: false,
+            reactor: Reactor::Uninitialized,
         })
     }
 
-    fn instantiate_store() -> Store {
-        let (ctx, wasi_snapshot_preview) = instantiate_wasi();
-        let (_, wasi_unstable) = instantiate_wasi();
+    fn instantiate_store(options: &DebuggerOptions) -> Result<Store> {
+        let (ctx, wasi_snapshot_preview) = instantiate_wasi_with(&options.wasi)?;
         let mut store = Store::new();
+        store.set_import_stub(options.import_stub);
+        store.set_limits(options.limits.clone());
         store.add_embed_context(Box::new(ctx));
-        store.load_host_module("wasi_snapshot_preview1".to_string(), wasi_snapshot_preview);
-        store.load_host_module("wasi_unstable".to_string(), wasi_unstable);
-        store
+        store.load_host_module(
+            Snapshot::Preview1.module_name().to_string(),
+            wasi_snapshot_preview,
+        );
+        store.load_host_module(
+            Snapshot::Preview0.module_name().to_string(),
+            host_module(Snapshot::Preview0),
+        );
+        Ok(store)
+    }
+
+    fn describe_validation_error(bytes: &[u8], err: ValidationError) -> anyhow::Error {
+        let kind = if err.is_malformed() {
+            "malformed"
+        } else {
+            "invalid"
+        };
+        let mut location = format!("at offset 0x{:x}", err.offset());
+        if let Some(func_index) = err.func_index() {
+            // Names are only available if the module could be decoded
+            let name = Module::from_bytes(bytes)
+                .ok()
+                .and_then(|module| module.func_name(func_index).cloned());
+            location = match name {
+                Some(name) => format!("{} in func[{}] <{}>", location, func_index, name),
+                None => format!("{} in func[{}]", location, func_index),
+            };
+        }
+        anyhow!("{} module: {}\n  --> {}", kind, err.message(), location)
+    }
+
+    fn describe_instantiation_error(err: anyhow::Error) -> anyhow::Error {
+        match err.downcast_ref::<StoreError>() {
+            Some(StoreError::UnresolvedImports(_)) => anyhow!(
+                "{}\nhint: pass --stub-missing-imports=<trap|log> to stub them",
+                err
+            ),
+            _ => err,
+        }
+    }
+
+    fn install_mock(&mut self, mock: &debugger::ImportMock) -> Result<()> {
+        let mut installed = false;
+        for position in 0..self.modules.len() {
+            installed |= self.install_mock_into(position, mock)?;
+        }
+        if installed {
+            Ok(())
+        } else {
+            Err(anyhow!("No function import {}.{}", mock.module, mock.field))
+        }
+    }
+
+    /// Returns false if the module at `position` doesn't import the function
+    fn install_mock_into(&mut self, position: usize, mock: &debugger::ImportMock) -> Result<bool> {
+        let loaded = &self.modules[position];
+        let func_index = loaded
+            .module
+            .imports()
+            .iter()
+            .filter(|import| match import.ty {
+                ImportSectionEntryType::Function(_) => true,
+                _ => false,
+            })
+            .position(|import| import.module == mock.module && import.field == mock.field);
+        let func_index = match func_index {
+            Some(func_index) => func_index,
+            None => return Ok(false),
+        };
+        let addr = FuncAddr::new_unsafe(loaded.index, func_index);
+        let ty = match self.store.func(addr) {
+            Some((func, _)) => func.ty().clone(),
+            None => {
+                return Err(anyhow!(
+                    "Unresolved function {}.{}",
+                    mock.module,
+                    mock.field
+                ))
+            }
+        };
+        if ty.returns.len() != mock.results.len() {
+            return Err(anyhow!(
+                "{}.{} returns {} values, but {} values are given",
+                mock.module,
+                mock.field,
+                ty.returns.len(),
+                mock.results.len()
+            ));
+        }
+        let results = ty
+            .returns
+            .iter()
+            .zip(mock.results.iter())
+            .map(|(ty, value)| match (ty, value) {
+                (Type::I32, debugger::MockValue::Int(v)) => Ok(WasmValue::I32(*v as i32)),
+                (Type::I64, debugger::MockValue::Int(v)) => Ok(WasmValue::I64(*v)),
+                (Type::F32, debugger::MockValue::Int(v)) => Ok(WasmValue::from(*v as f32)),
+                (Type::F64, debugger::MockValue::Int(v)) => Ok(WasmValue::from(*v as f64)),
+                (Type::F32, debugger::MockValue::Float(v)) => Ok(WasmValue::from(*v as f32)),
+                (Type::F64, debugger::MockValue::Float(v)) => Ok(WasmValue::from(*v)),
+                (ty, _) => Err(anyhow!("Can't return a float value as {:?}", ty)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let body = HostFuncBody::new(ty, move |_, values, _, _| {
+            values.extend(results.iter().cloned());
+            Ok(())
+        });
+        self.store
+            .replace_func(addr, mock.module.clone(), mock.field.clone(), body)?;
+        Ok(true)
+    }
+
+    fn module_imports(&self, loaded: &LoadedModule) -> Vec<String> {
+        let module_index = loaded.index;
+        let (mut funcs, mut globals, mut mems, mut tables) = (0, 0, 0, 0);
+        let mut lines = Vec::new();
+        for (index, import) in loaded.module.imports().iter().enumerate() {
+            let resolved = match import.ty {
+                ImportSectionEntryType::Function(_) => {
+                    let addr = FuncAddr::new_unsafe(module_index, funcs);
+                    funcs += 1;
+                    let mocked = self
+                        .import_mocks
+                        .iter()
+                        .any(|m| m.module == import.module && m.field == import.field);
+                    let resolved = match self.store.func(addr) {
+                        Some((FunctionInstance::Host(func), _)) => {
+                            format!("host {}.{}", func.module_name(), func.field_name())
+                        }
+                        Some((FunctionInstance::Defined(func), _)) => func.name().clone(),
+                        None => "unresolved".to_string(),
+                    };
+                    if mocked {
+                        format!("{} (mocked)", resolved)
+                    } else {
+                        resolved
+                    }
+                }
+                ImportSectionEntryType::Global(_) => {
+                    let addr = GlobalAddr::new_unsafe(module_index, globals);
+                    globals += 1;
+                    format!("global = {:?}", self.store.global(addr).borrow().value())
+                }
+                ImportSectionEntryType::Memory(_) => {
+                    let addr = MemoryAddr::new_unsafe(module_index, mems);
+                    mems += 1;
+                    let pages = self.store.memory(addr).borrow().page_count();
+                    format!("memory of {} pages", pages)
+                }
+                ImportSectionEntryType::Table(_) => {
+                    let addr = TableAddr::new_unsafe(module_index, tables);
+                    tables += 1;
+                    let size = self.store.table(addr).borrow().buffer_len();
+                    format!("table of {} elements", size)
+                }
+            };
+            lines.push(format!(
+                "{:>3}: {}.{} -> {}",
+                index, import.module, import.field, resolved
+            ));
+        }
+        lines
+    }
+
+    fn signal_from(reason: StopReason) -> Signal {
+        match reason {
+            StopReason::Step | StopReason::Predicate => Signal::Next,
+            StopReason::Breakpoint => Signal::Breakpoint,
+            StopReason::Finish(_) => Signal::End,
+            StopReason::Exit(code) => Signal::Exit(code),
+        }
+    }
+
+    /// `_initialize` of the main module if it is a WASI reactor, which
+    /// exports it instead of `_start`
+    fn reactor_initializer(&self, module_index: ModuleIndex) -> Option<FuncAddr> {
+        let module = self.store.module(module_index)?.defined()?;
+        match module.exported_func("_start".to_string()) {
+            Ok(Some(_)) => None,
+            _ => module.exported_func("_initialize".to_string()).ok()?,
+        }
+    }
+
+    fn last_execution(&self) -> LastExecution {
+        match self.execution {
+            Some(ref execution) => {
+                let execution = execution.borrow();
+                if execution.is_finished() && execution.exit_code().is_none() {
+                    LastExecution::Returned
+                } else {
+                    LastExecution::Unfinished
+                }
+            }
+            None => LastExecution::None,
+        }
+    }
+
+    /// `name` exported by the main module, or its entry function
+    fn entry_func(&self, module_index: ModuleIndex, name: Option<&String>) -> Result<FuncAddr> {
+        let module = self
+            .store
+            .module(module_index)
+            .ok_or_else(|| anyhow!("Module {} is not loaded", module_index.0))?
+            .defined()
+            .unwrap();
+        let func_addr = if let Some(func_name) = name {
+            if let Some(Some(func_addr)) = module.exported_func(func_name.clone()).ok() {
+                func_addr
+            } else {
+                return Err(anyhow!("Entry function {} not found", func_name));
+            }
+        } else if let Some(start_func_addr) = module.start_func_addr() {
+            *start_func_addr
+        } else {
+            if let Some(Some(func_addr)) = module.exported_func("_start".to_string()).ok() {
+                func_addr
+            } else {
+                return Err(anyhow!("Entry function _start not found"));
+            }
+        };
+        Ok(func_addr)
+    }
+
+    /// Check `args` against the parameters of `func_addr`, which is called
+    /// `name` in errors if given
+    fn call_args(
+        &self,
+        func_addr: FuncAddr,
+        name: Option<&String>,
+        args: Vec<debugger::RunArg>,
+    ) -> Result<Vec<WasmValue>> {
+        let (func, _) = self
+            .store
+            .func(func_addr)
+            .ok_or(anyhow!("Function not found"))?;
+        let name = name.unwrap_or_else(|| func.name());
+        let params = &func.ty().params;
+        if params.len() != args.len() {
+            return Err(anyhow!(
+                "{} takes {} arguments, but {} are given",
+                name,
+                params.len(),
+                args.len()
+            ));
+        }
+        params
+            .iter()
+            .zip(args)
+            .enumerate()
+            .map(|(index, (ty, arg))| match (ty, arg) {
+                (Type::I32, debugger::RunArg::Int(v))
+                    if v >= i32::MIN as i64 && v <= u32::MAX as i64 =>
+                {
+                    Ok(WasmValue::I32(v as i32))
+                }
+                (Type::I64, debugger::RunArg::Int(v)) => Ok(WasmValue::I64(v)),
+                (Type::F32, debugger::RunArg::Int(v)) => Ok(WasmValue::from(v as f32)),
+                (Type::F64, debugger::RunArg::Int(v)) => Ok(WasmValue::from(v as f64)),
+                (Type::F32, debugger::RunArg::Float(v)) => Ok(WasmValue::from(v as f32)),
+                (Type::F64, debugger::RunArg::Float(v)) => Ok(WasmValue::from(v)),
+                (ty, debugger::RunArg::Typed(v)) if v.value_type() == *ty => Ok(v),
+                (ty, _) => Err(anyhow!(
+                    "Argument {} of {} is not a valid {}",
+                    index,
+                    name,
+                    type_name(*ty)
+                )),
+            })
+            .collect()
+    }
+
+    /// Call `func_addr` until it stops. The execution is kept even if it
+    /// returned or trapped, so that the next `run` knows how it ended.
+    fn invoke(&mut self, func_addr: FuncAddr, args: Vec<WasmValue>) -> Result<debugger::RunResult> {
+        let execution = Execution::new(func_addr, args, &self.store)?;
+        let execution = Rc::new(RefCell::new(execution));
+        self.execution = Some(execution.clone());
+        let result = execution.borrow_mut().resume(&self.store, self);
+        match result {
+            Ok(StopReason::Breakpoint) => Ok(debugger::RunResult::Breakpoint),
+            Ok(StopReason::Finish(values)) => Ok(debugger::RunResult::Finish(values)),
+            Ok(StopReason::Exit(code)) => Ok(debugger::RunResult::Exit(code)),
+            Ok(StopReason::Step) | Ok(StopReason::Predicate) => unreachable!(),
+            Err(err) => Err(anyhow!("Function exec failure {}", err)),
+        }
+    }
+
+    /// Run `_initialize` of a reactor unless it already ran on the current
+    /// instances. Returns how it stopped unless it returned.
+    fn initialize_reactor(&mut self, func_addr: FuncAddr) -> Result<Option<debugger::RunResult>> {
+        if self.reactor == Reactor::Initialized {
+            return Ok(None);
+        }
+        self.reactor = Reactor::Initializing;
+        if self.options.stop_at_initialize {
+            let execution = Execution::new(func_addr, vec![], &self.store)?;
+            self.execution = Some(Rc::new(RefCell::new(execution)));
+            return Ok(Some(debugger::RunResult::Initializing));
+        }
+        match self.invoke(func_addr, vec![])? {
+            debugger::RunResult::Finish(_) => {
+                self.reactor = Reactor::Initialized;
+                Ok(None)
+            }
+            result => Ok(Some(result)),
+        }
     }
 }
 
 impl debugger::Debugger for MainDebugger {
     fn instructions(&self) -> Result<(&[Instruction], usize)> {
-        if let Some(ref executor) = self.executor {
-            let executor = executor.borrow();
-            let insts = executor.current_func_insts(&self.store)?;
-            Ok((insts, executor.pc.inst_index().0 as usize))
+        if let Some(ref execution) = self.execution {
+            let execution = execution.borrow();
+            let insts = execution
+                .instructions(&self.store)
+                .ok_or(anyhow!("No execution context"))?;
+            let pc = execution.pc().ok_or(anyhow!("No execution context"))?;
+            Ok((insts, pc.inst_index().0 as usize))
         } else {
             Err(anyhow!("No execution context"))
         }
@@ -61,16 +531,52 @@ impl debugger::Debugger for MainDebugger {
 
     fn set_breakpoint(&mut self, breakpoint: debugger::Breakpoint) {
         match &breakpoint {
-            debugger::Breakpoint::Function { name } => {
-                self.function_breakpoints.insert(name.clone(), breakpoint);
+            debugger::Breakpoint::Function { name, module } => {
+                let key = (module.clone(), name.clone());
+                self.function_breakpoints.insert(key, breakpoint);
+            }
+            debugger::Breakpoint::MemoryGrow { .. } => {
+                self.memory_grow_breakpoint = Some(breakpoint);
             }
         }
     }
 
+    fn imports(&self) -> Result<Vec<String>> {
+        if self.modules.is_empty() {
+            return Err(anyhow!("No module loaded"));
+        }
+        let mut lines = Vec::new();
+        for loaded in &self.modules {
+            if self.modules.len() > 1 {
+                lines.push(format!("{}:", loaded.name.as_deref().unwrap_or("<main>")));
+            }
+            lines.append(&mut self.module_imports(loaded));
+        }
+        Ok(lines)
+    }
+
+    fn add_module(&mut self, name: String, bytes: &[u8]) -> Result<ModuleIndex> {
+        if self.modules.iter().any(|m| m.name.as_ref() == Some(&name)) {
+            return Err(anyhow!("Module {} is already loaded", name));
+        }
+        let module_index = self.load_module_internal(Some(name), bytes)?;
+        // `_initialize` runs again on instances including the new module
+        self.reactor = Reactor::Uninitialized;
+        Ok(module_index)
+    }
+
+    fn mock_import(&mut self, mock: debugger::ImportMock) -> Result<()> {
+        self.install_mock(&mock)?;
+        self.import_mocks
+            .retain(|m| m.module != mock.module || m.field != mock.field);
+        self.import_mocks.push(mock);
+        Ok(())
+    }
+
     fn stack_values(&self) -> Vec<String> {
-        if let Some(ref executor) = self.executor {
-            let executor = executor.borrow();
-            let values = executor.stack.peek_values();
+        if let Some(ref execution) = self.execution {
+            let execution = execution.borrow();
+            let values = execution.stack_values();
             values.iter().map(|v| format!("{:?}", v)).collect()
         } else {
             Vec::new()
@@ -80,48 +586,90 @@ impl debugger::Debugger for MainDebugger {
     fn store(&self) -> &Store {
         &self.store
     }
+    fn set_args(&mut self, args: Vec<String>) {
+        // Keep the program name
+        let program = self.options.wasi.args.first().cloned();
+        self.options.wasi.args = program.into_iter().chain(args).collect();
+        self.wasi_changed = true;
+    }
+    fn set_envs(&mut self, envs: Vec<(String, String)>) {
+        self.options.wasi.envs = envs;
+        self.wasi_changed = true;
+    }
+    fn set_stop_at_initialize(&mut self, stop: bool) {
+        self.options.stop_at_initialize = stop;
+    }
+    fn wasi_trace(&self) -> &WasiTrace {
+        &self.options.wasi.trace
+    }
+    fn set_wasi_trace(&mut self, trace: WasiTrace) {
+        // Takes effect immediately, even in the middle of a run
+        if let Some(ctx) = self.store.get_embed_context::<WasiContext>() {
+            ctx.set_trace(trace.clone());
+        }
+        self.options.wasi.trace = trace;
+    }
+    fn main_module(&self) -> Option<ModuleIndex> {
+        self.main_module_index()
+    }
+    fn module(&self, module_index: ModuleIndex) -> Option<&Module> {
+        self.modules
+            .iter()
+            .find(|loaded| loaded.index == module_index)
+            .map(|loaded| &loaded.module)
+    }
     fn locals(&self) -> Vec<WasmValue> {
-        if let Some(ref executor) = self.executor {
-            let executor = executor.borrow();
-            executor.stack.current_frame().unwrap().locals.clone()
+        if let Some(ref execution) = self.execution {
+            execution.borrow().locals().to_vec()
         } else {
             Vec::new()
         }
     }
+    fn local_name(&self, index: usize) -> Option<String> {
+        let execution = self.execution.as_ref()?.borrow();
+        let frame = execution.current_frame()?;
+        let func = self.store.func_global(frame.exec_addr).defined()?;
+        func.local_name(index as u32).cloned()
+    }
     fn current_frame(&self) -> Option<debugger::FunctionFrame> {
-        let executor = if let Some(ref executor) = self.executor {
-            executor
+        let execution = if let Some(ref execution) = self.execution {
+            execution
         } else {
             return None;
         };
-        let executor = executor.borrow();
-        let frame = executor.stack.current_frame().unwrap();
+        let execution = execution.borrow();
+        let frame = execution.current_frame()?;
         let func = self.store.func_global(frame.exec_addr);
 
-        self.module_index.map(|idx| debugger::FunctionFrame {
-            module_index: idx,
+        Some(debugger::FunctionFrame {
+            module_index: frame.module_index(),
             argument_count: func.ty().params.len(),
         })
     }
     fn frame(&self) -> Vec<String> {
-        if let Some(ref executor) = self.executor {
-            let executor = executor.borrow();
-            let frames = executor.stack.peek_frames();
+        if let Some(ref execution) = self.execution {
+            let execution = execution.borrow();
+            let frames = execution.frames();
             frames
                 .iter()
-                .map(|frame| self.store.func_global(frame.exec_addr).name().clone())
+                .map(|frame| {
+                    let name = self.store.func_global(frame.exec_addr).name();
+                    match self.module_name(frame.module_index()) {
+                        Some(module_name) => format!("{}`{}", module_name, name),
+                        None => name.clone(),
+                    }
+                })
                 .collect()
         } else {
             Vec::new()
         }
     }
     fn memory(&self) -> Result<Vec<u8>> {
-        if let Some(ref executor) = self.executor {
-            let executor = executor.borrow();
-            let frame = executor
-                .stack
+        if let Some(ref execution) = self.execution {
+            let execution = execution.borrow();
+            let frame = execution
                 .current_frame()
-                .map_err(|e| anyhow!("Failed to get current frame: {}", e))?;
+                .ok_or(anyhow!("Failed to get current frame"))?;
             let addr = MemoryAddr::new_unsafe(frame.module_index(), 0);
             Ok(self.store.memory(addr).borrow().raw_data().to_vec())
         } else {
@@ -130,141 +678,249 @@ impl debugger::Debugger for MainDebugger {
     }
 
     fn is_running(&self) -> bool {
-        self.executor.is_some()
+        match self.execution {
+            Some(ref execution) => !execution.borrow().is_finished(),
+            None => false,
+        }
     }
 
     fn step(&self, style: debugger::StepStyle) -> Result<Signal> {
-        let executor = if let Some(ref executor) = self.executor {
-            executor
+        let execution = if let Some(ref execution) = self.execution {
+            execution
         } else {
             return Err(anyhow!("No execution context"));
         };
         use debugger::StepStyle::*;
 
-        fn frame_depth(executor: &Executor) -> usize {
-            executor.stack.peek_frames().len()
-        }
-        match style {
-            StepInstIn => return Ok(executor.borrow_mut().execute_step(&self.store, self)?),
-            StepInstOver => {
-                let initial_frame_depth = frame_depth(&executor.borrow());
-                let mut last_signal = executor.borrow_mut().execute_step(&self.store, self)?;
-                while initial_frame_depth < frame_depth(&executor.borrow()) {
-                    last_signal = executor.borrow_mut().execute_step(&self.store, self)?;
-                    if let Signal::Breakpoint = last_signal {
-                        return Ok(last_signal);
-                    }
-                }
-                return Ok(last_signal);
-            }
-            StepOut => {
-                let initial_frame_depth = frame_depth(&executor.borrow());
-                let mut last_signal = executor.borrow_mut().execute_step(&self.store, self)?;
-                while initial_frame_depth <= frame_depth(&executor.borrow()) {
-                    last_signal = executor.borrow_mut().execute_step(&self.store, self)?;
-                    if let Signal::Breakpoint = last_signal {
-                        return Ok(last_signal);
-                    }
-                }
-                return Ok(last_signal);
-            }
-        }
+        let mut execution = execution.borrow_mut();
+        let reason = match style {
+            StepInstIn => execution.step(&self.store, self)?,
+            StepInstOver => execution.step_over(&self.store, self)?,
+            StepOut => execution.step_out(&self.store, self)?,
+        };
+        Ok(Self::signal_from(reason))
     }
 
     fn process(&self) -> Result<Signal> {
-        let executor = if let Some(ref executor) = self.executor {
-            executor
+        let execution = if let Some(ref execution) = self.execution {
+            execution
         } else {
             return Err(anyhow!("No execution context"));
         };
-        loop {
-            let result = executor.borrow_mut().execute_step(&self.store, self);
-            match result {
-                Ok(Signal::Next) => continue,
-                Ok(Signal::Breakpoint) | Ok(Signal::End) => return Ok(result.ok().unwrap()),
-                Err(err) => return Err(anyhow!("Function exec failure {:?}", err)),
-            }
-        }
+        let reason = execution
+            .borrow_mut()
+            .resume(&self.store, self)
+            .map_err(|err| anyhow!("Function exec failure {}", err))?;
+        Ok(Self::signal_from(reason))
     }
 
-    fn run(&mut self, name: Option<String>) -> Result<debugger::RunResult> {
-        if self.is_running() {
-            self.store = Self::instantiate_store();
+    fn run(
+        &mut self,
+        name: Option<String>,
+        args: Vec<debugger::RunArg>,
+    ) -> Result<debugger::RunResult> {
+        let (reactor, reuse) = reuse_instances(self.reactor, self.last_execution());
+        self.reactor = reactor;
+        if !reuse || self.wasi_changed {
+            self.store = Self::instantiate_store(&self.options)?;
+            self.wasi_changed = false;
+            self.reactor = Reactor::Uninitialized;
+            self.execution = None;
+            for loaded in self.modules.iter_mut() {
+                loaded.index =
+                    Self::instantiate(&mut self.store, loaded.name.clone(), &loaded.module)?;
+            }
+            for mock in self.import_mocks.clone() {
+                self.install_mock(&mock)?;
+            }
         }
-        if let Some(module_index) = self.module_index {
-            let module = self.store.module(module_index).defined().unwrap();
-            let func_addr = if let Some(func_name) = name {
-                if let Some(Some(func_addr)) = module.exported_func(func_name.clone()).ok() {
-                    func_addr
-                } else {
-                    return Err(anyhow!("Entry function {} not found", func_name));
-                }
-            } else if let Some(start_func_addr) = module.start_func_addr() {
-                *start_func_addr
-            } else {
-                if let Some(Some(func_addr)) = module.exported_func("_start".to_string()).ok() {
-                    func_addr
-                } else {
-                    return Err(anyhow!("Entry function _start not found"));
-                }
-            };
-            let func = self
-                .store
-                .func(func_addr)
-                .ok_or(anyhow!("Function not found"))?;
-            match func {
-                (FunctionInstance::Host(host), _) => {
-                    let mut results = Vec::new();
-                    match host.code().call(
-                        &vec![],
-                        &mut results,
-                        &self.store,
-                        func_addr.module_index(),
-                    ) {
-                        Ok(_) => return Ok(debugger::RunResult::Finish(results)),
-                        Err(_) => return Err(anyhow!("Failed to execute host func")),
-                    }
-                }
-                (FunctionInstance::Defined(func), exec_addr) => {
-                    let ret_types = &func.ty().returns;
-                    let frame = CallFrame::new_from_func(exec_addr, func, vec![], None);
-                    let pc = ProgramCounter::new(func.module_index(), exec_addr, InstIndex::zero());
-                    let executor = Rc::new(RefCell::new(Executor::new(frame, ret_types.len(), pc)));
-                    self.executor = Some(executor.clone());
-                    let result = self.process()?;
-                    match result {
-                        Signal::Next => unreachable!(),
-                        Signal::Breakpoint => return Ok(debugger::RunResult::Breakpoint),
-                        Signal::End => match executor.borrow_mut().pop_result(ret_types.to_vec()) {
-                            Ok(values) => {
-                                self.executor = None;
-                                return Ok(debugger::RunResult::Finish(values));
-                            }
-                            Err(err) => {
-                                self.executor = None;
-                                return Err(anyhow!("Return value failure {:?}", err));
-                            }
-                        },
-                    }
-                }
+        let module_index = self
+            .main_module_index()
+            .ok_or_else(|| anyhow!("No module loaded"))?;
+        let initializer = self.reactor_initializer(module_index);
+        // Check the function and its arguments before `_initialize` runs
+        let call = match (initializer, name.as_ref()) {
+            (Some(_), None) => None,
+            (_, name) => {
+                let func_addr = self.entry_func(module_index, name)?;
+                Some((func_addr, self.call_args(func_addr, name, args)?))
             }
-        } else {
-            Err(anyhow!("No module loaded"))
+        };
+        let initialized = self.reactor == Reactor::Initialized;
+        if let Some(initialize) = initializer {
+            if let Some(result) = self.initialize_reactor(initialize)? {
+                return Ok(result);
+            }
+        }
+        match call {
+            Some((func_addr, args)) => self.invoke(func_addr, args),
+            // A reactor is run without a function only to initialize it
+            None if initialized => Err(anyhow!("The module is a reactor, give an export to run")),
+            None => Ok(debugger::RunResult::Initialized),
         }
     }
 }
 
 impl Interceptor for MainDebugger {
-    fn invoke_func(&self, name: &String) -> Result<Signal, Trap> {
-        let key = self
-            .function_breakpoints
-            .keys()
-            .filter(|k| name.contains(k.clone()))
-            .next();
-        if let Some(_) = key {
+    fn invoke_func(&self, name: &String, module_index: ModuleIndex) -> Result<Signal, Trap> {
+        let module_name = self.module_name(module_index);
+        let hit = self.function_breakpoints.keys().any(|(module, key)| {
+            (module.is_none() || module.as_ref() == module_name) && name.contains(key)
+        });
+        if hit {
+            Ok(Signal::Breakpoint)
+        } else {
+            Ok(Signal::Next)
+        }
+    }
+
+    fn invoke_host_func(&self, module_name: &str, field_name: &str) -> Result<Signal, Trap> {
+        let stop = self
+            .import_mocks
+            .iter()
+            .any(|m| m.stop && m.module == module_name && m.field == field_name);
+        if stop {
             Ok(Signal::Breakpoint)
         } else {
             Ok(Signal::Next)
         }
     }
+
+    fn after_memory_grow(
+        &self,
+        previous_pages: usize,
+        delta: usize,
+        grown: bool,
+    ) -> Result<Signal, Trap> {
+        let threshold = match self.memory_grow_breakpoint {
+            Some(debugger::Breakpoint::MemoryGrow { threshold }) => threshold,
+            _ => return Ok(Signal::Next),
+        };
+        let bytes = previous_pages
+            .saturating_add(delta)
+            .saturating_mul(WASM_PAGE_SIZE);
+        if !grown {
+            warn!(
+                "memory.grow failed to grow {} pages by {} pages",
+                previous_pages, delta
+            );
+            Ok(Signal::Breakpoint)
+        } else if threshold
+            .map(|threshold| bytes > threshold)
+            .unwrap_or(false)
+        {
+            warn!("memory.grow grew memory to {} bytes", bytes);
+            Ok(Signal::Breakpoint)
+        } else {
+            Ok(Signal::Next)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::debugger::{Debugger, RunResult};
+    use super::{reuse_instances, DebuggerOptions, LastExecution, MainDebugger, Reactor};
+    use wasminspect_vm::WasmValue;
+
+    // (global $g (mut i32) (i32.const 0))
+    // (func $bump (result i32) (global.set $g (i32.add (global.get $g) (i32.const 1))) (global.get $g))
+    // (func (export "_start") (result i32) (call $bump))
+    // (func (export "fail") (call $bump) (unreachable))
+    #[rustfmt::skip]
+    const COMMAND_MODULE: [u8; 77] = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // (type (func (result i32))) (type (func))
+        0x01, 0x08, 0x02, 0x60, 0x00, 0x01, 0x7f, 0x60, 0x00, 0x00,
+        0x03, 0x04, 0x03, 0x00, 0x00, 0x01,
+        0x06, 0x06, 0x01, 0x7f, 0x01, 0x41, 0x00, 0x0b,
+        0x07, 0x11, 0x02, 0x06, 0x5f, 0x73, 0x74, 0x61, 0x72, 0x74, 0x00, 0x01,
+        0x04, 0x66, 0x61, 0x69, 0x6c, 0x00, 0x02,
+        0x0a, 0x18, 0x03,
+        0x0b, 0x00, 0x23, 0x00, 0x41, 0x01, 0x6a, 0x24, 0x00, 0x23, 0x00, 0x0b,
+        0x04, 0x00, 0x10, 0x00, 0x0b,
+        0x05, 0x00, 0x10, 0x00, 0x00, 0x0b,
+    ];
+
+    // The same as `COMMAND_MODULE`, with an empty `_initialize` instead of
+    // `_start` and `$bump` exported as "bump"
+    #[rustfmt::skip]
+    const REACTOR_MODULE: [u8; 93] = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        0x01, 0x08, 0x02, 0x60, 0x00, 0x01, 0x7f, 0x60, 0x00, 0x00,
+        0x03, 0x05, 0x04, 0x00, 0x01, 0x00, 0x01,
+        0x06, 0x06, 0x01, 0x7f, 0x01, 0x41, 0x00, 0x0b,
+        0x07, 0x1d, 0x03,
+        0x0b, 0x5f, 0x69, 0x6e, 0x69, 0x74, 0x69, 0x61, 0x6c, 0x69, 0x7a, 0x65, 0x00, 0x01,
+        0x04, 0x62, 0x75, 0x6d, 0x70, 0x00, 0x02,
+        0x04, 0x66, 0x61, 0x69, 0x6c, 0x00, 0x03,
+        0x0a, 0x1b, 0x04,
+        0x0b, 0x00, 0x23, 0x00, 0x41, 0x01, 0x6a, 0x24, 0x00, 0x23, 0x00, 0x0b,
+        0x02, 0x00, 0x0b,
+        0x04, 0x00, 0x10, 0x00, 0x0b,
+        0x05, 0x00, 0x10, 0x00, 0x00, 0x0b,
+    ];
+
+    /// The value of `$g` returned by `name`, or `None` if it didn't return
+    fn run_counter(debugger: &mut MainDebugger, name: Option<&str>) -> Option<i32> {
+        match debugger.run(name.map(str::to_string), vec![]) {
+            Ok(RunResult::Finish(values)) => match values.as_slice() {
+                [WasmValue::I32(count)] => Some(*count),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn rerun_on_fresh_instances() {
+        let mut debugger = MainDebugger::new(DebuggerOptions::default()).unwrap();
+        debugger.load_module(&COMMAND_MODULE).unwrap();
+        // A command module starts over after returning or trapping
+        assert_eq!(run_counter(&mut debugger, None), Some(1));
+        assert_eq!(run_counter(&mut debugger, None), Some(1));
+        assert_eq!(run_counter(&mut debugger, Some("fail")), None);
+        assert_eq!(run_counter(&mut debugger, None), Some(1));
+
+        let mut debugger = MainDebugger::new(DebuggerOptions::default()).unwrap();
+        debugger.load_module(&REACTOR_MODULE).unwrap();
+        // Exports of a reactor share the instances until one of them traps
+        assert_eq!(run_counter(&mut debugger, Some("bump")), Some(1));
+        assert_eq!(run_counter(&mut debugger, Some("bump")), Some(2));
+        assert_eq!(run_counter(&mut debugger, Some("fail")), None);
+        assert_eq!(run_counter(&mut debugger, Some("bump")), Some(1));
+    }
+
+    #[test]
+    fn reuse_decision() {
+        use LastExecution::*;
+        use Reactor::*;
+        // The state before `run`, and the state and decision it leads to
+        let cases = [
+            // Nothing ran on freshly loaded instances
+            ((Uninitialized, None), (Uninitialized, true)),
+            // `_initialize` traps, or is still stopped at a breakpoint
+            ((Initializing, Unfinished), (Initializing, false)),
+            // `_initialize` stopped at a breakpoint, then was continued
+            ((Initializing, Returned), (Initialized, true)),
+            // `_initialize` failed before it started
+            ((Initializing, None), (Initializing, false)),
+            // A reactor export returns
+            ((Initialized, Returned), (Initialized, true)),
+            // A reactor export traps or exits
+            ((Initialized, Unfinished), (Initialized, false)),
+            // A command module re-runs after returning or exiting
+            ((Uninitialized, Returned), (Uninitialized, false)),
+            ((Uninitialized, Unfinished), (Uninitialized, false)),
+        ];
+        for ((reactor, last), expected) in cases.iter() {
+            assert_eq!(
+                reuse_instances(*reactor, *last),
+                *expected,
+                "{:?} {:?}",
+                reactor,
+                last
+            );
+        }
+    }
 }
@@ -5,9 +5,9 @@ use std::collections::HashMap;
 
 use anyhow::{anyhow, Result};
 
-pub fn type_name<'input>(
+pub fn type_name(
     ty_offset: Option<usize>,
-    type_hash: &HashMap<usize, TypeInfo<Reader<'input>>>,
+    type_hash: &HashMap<usize, TypeInfo<Reader>>,
 ) -> Result<String> {
     let ty_offset = match ty_offset {
         Some(o) => o,
@@ -73,11 +73,11 @@ pub fn type_name<'input>(
     Ok(result)
 }
 
-pub fn format_object<'input>(
+pub fn format_object(
     ty_offset: usize,
     memory: &[u8],
     encoding: gimli::Encoding,
-    type_hash: &HashMap<usize, TypeInfo<Reader<'input>>>,
+    type_hash: &HashMap<usize, TypeInfo<Reader>>,
 ) -> Result<String> {
     let ty = type_hash
         .get(&ty_offset)
@@ -133,8 +133,8 @@ pub fn format_object<'input>(
             for member in &struct_type.members {
                 let offset: usize = match member.location {
                     MemberLocation::ConstOffset(offset) => offset as usize,
-                    MemberLocation::LocationDescription(expr) => {
-                        let pieces = evaluate_variable_location(encoding, 0, expr)?;
+                    MemberLocation::LocationDescription(ref expr) => {
+                        let pieces = evaluate_variable_location(encoding, 0, expr.clone())?;
                         let piece = match pieces.iter().next() {
                             Some(p) => p,
                             None => panic!(),
@@ -2,58 +2,60 @@ use anyhow::{anyhow, Result};
 use gimli::{
     AttributeValue, DebugAbbrev, DebugAddr, DebugInfo, DebugLine, DebugLineStr, DebugLoc,
     DebugLocLists, DebugRanges, DebugRngLists, DebugStr, DebugStrOffsets, DebugTypes,
-    DebuggingInformationEntry, EndianSlice, LineRow, LittleEndian, LocationLists, RangeLists, Unit,
+    DebuggingInformationEntry, EndianRcSlice, LineRow, LittleEndian, LocationLists, RangeLists,
+    Unit,
 };
 use log::trace;
 use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
 use wasmparser::{ModuleReader, SectionCode};
 
+use super::commands::command;
+
 mod format;
 mod types;
 mod utils;
 
 use utils::*;
 
-type Reader<'input> = gimli::EndianSlice<'input, LittleEndian>;
-pub type Dwarf<'input> = gimli::Dwarf<Reader<'input>>;
+/// Sections share the module binary instead of borrowing it, so that debug
+/// info can outlive the buffer the module was read into
+type Reader = EndianRcSlice<LittleEndian>;
+pub type Dwarf = gimli::Dwarf<Reader>;
 
-pub fn parse_dwarf<'a>(module: &'a [u8]) -> Result<Dwarf<'a>> {
-    const EMPTY_SECTION: &[u8] = &[];
-    let mut reader = ModuleReader::new(module)?;
+pub fn parse_dwarf(module: Rc<[u8]>) -> Result<Dwarf> {
+    let mut reader = ModuleReader::new(&module)?;
     let mut sections = HashMap::new();
     while !reader.eof() {
         let section = reader.read().expect("section");
         match section.code {
             SectionCode::Custom { name, kind: _ } => {
-                let mut reader = section.get_binary_reader();
-                let len = reader.bytes_remaining();
-                sections.insert(name, reader.read_bytes(len).expect("bytes"));
+                let range = section.get_binary_reader().range();
+                sections.insert(name.to_string(), range.start..range.end);
             }
             _ => (),
         }
     }
     let endian = LittleEndian;
-    let debug_str = DebugStr::new(sections.get(".debug_str").unwrap(), endian);
-    let debug_abbrev = DebugAbbrev::new(sections.get(".debug_abbrev").unwrap(), endian);
-    let debug_info = DebugInfo::new(sections.get(".debug_info").unwrap(), endian);
-    let debug_line = DebugLine::new(sections.get(".debug_line").unwrap(), endian);
-    let debug_addr = DebugAddr::from(EndianSlice::new(EMPTY_SECTION, endian));
-    let debug_line_str = DebugLineStr::from(EndianSlice::new(EMPTY_SECTION, endian));
-    let debug_str_sup = DebugStr::from(EndianSlice::new(EMPTY_SECTION, endian));
-    let debug_ranges = match sections.get(".debug_ranges") {
-        Some(section) => DebugRanges::new(section, endian),
-        None => DebugRanges::new(EMPTY_SECTION, endian),
-    };
-    let debug_rnglists = DebugRngLists::new(EMPTY_SECTION, endian);
+    let whole = Reader::new(module.clone(), endian);
+    let section = |name: &str| sections.get(name).map(|range| whole.range(range.clone()));
+    let empty = || whole.range(0..0);
+    let required = |name: &str| section(name).ok_or(anyhow!("{} section not found", name));
+    let debug_str = DebugStr::from(required(".debug_str")?);
+    let debug_abbrev = DebugAbbrev::from(required(".debug_abbrev")?);
+    let debug_info = DebugInfo::from(required(".debug_info")?);
+    let debug_line = DebugLine::from(required(".debug_line")?);
+    let debug_addr = DebugAddr::from(empty());
+    let debug_line_str = DebugLineStr::from(empty());
+    let debug_str_sup = DebugStr::from(empty());
+    let debug_ranges = DebugRanges::from(section(".debug_ranges").unwrap_or_else(empty));
+    let debug_rnglists = DebugRngLists::from(empty());
     let ranges = RangeLists::new(debug_ranges, debug_rnglists);
-    let debug_loc = match sections.get(".debug_loc") {
-        Some(section) => DebugLoc::new(section, endian),
-        None => DebugLoc::new(EMPTY_SECTION, endian),
-    };
-    let debug_loclists = DebugLocLists::new(EMPTY_SECTION, endian);
+    let debug_loc = DebugLoc::from(section(".debug_loc").unwrap_or_else(empty));
+    let debug_loclists = DebugLocLists::from(empty());
     let locations = LocationLists::new(debug_loc, debug_loclists);
-    let debug_str_offsets = DebugStrOffsets::from(EndianSlice::new(EMPTY_SECTION, endian));
-    let debug_types = DebugTypes::from(EndianSlice::new(EMPTY_SECTION, endian));
+    let debug_str_offsets = DebugStrOffsets::from(empty());
+    let debug_types = DebugTypes::from(empty());
 
     Ok(Dwarf {
         debug_abbrev,
@@ -70,11 +72,11 @@ pub fn parse_dwarf<'a>(module: &'a [u8]) -> Result<Dwarf<'a>> {
     })
 }
 
-pub struct DwarfDebugInfo<'a> {
+pub struct DwarfDebugInfo {
     pub sourcemap: DwarfSourceMap,
-    pub subroutine: DwarfSubroutineMap<'a>,
+    pub subroutine: DwarfSubroutineMap,
 }
-pub fn transform_dwarf<'a>(dwarf: Dwarf<'a>) -> Result<DwarfDebugInfo<'a>> {
+pub fn transform_dwarf(dwarf: Dwarf) -> Result<DwarfDebugInfo> {
     let mut headers = dwarf.units();
     let mut sourcemaps = Vec::new();
     let mut subroutines = Vec::new();
@@ -105,6 +107,16 @@ pub fn transform_dwarf<'a>(dwarf: Dwarf<'a>) -> Result<DwarfDebugInfo<'a>> {
     })
 }
 
+/// Parse DWARF sections of a module binary into debug info for commands
+pub fn load_debug_info(module: Rc<[u8]>) -> Result<command::DebugInfo> {
+    let dwarf = parse_dwarf(module)?;
+    let debug_info = transform_dwarf(dwarf)?;
+    Ok(command::DebugInfo {
+        sourcemap: Box::new(debug_info.sourcemap),
+        subroutine: Box::new(debug_info.subroutine),
+    })
+}
+
 #[derive(Clone)]
 pub struct SymbolVariable<R>
 where
@@ -123,7 +135,6 @@ enum VariableContent<R: gimli::Reader> {
 }
 
 pub struct Subroutine<R: gimli::Reader> {
-    pub name: Option<String>,
     pub pc: std::ops::Range<u64>,
     pub variables: Vec<SymbolVariable<R>>,
     pub encoding: gimli::Encoding,
@@ -149,11 +160,6 @@ pub fn transform_subprogram_rec<R: gimli::Reader>(
     let mut subroutine = None;
     match node.entry().tag() {
         gimli::DW_TAG_subprogram | gimli::DW_TAG_lexical_block => {
-            let name = match node.entry().attr_value(gimli::DW_AT_name)? {
-                Some(attr) => Some(clone_string_attribute(dwarf, unit, attr)?),
-                None => None,
-            };
-
             let low_pc_attr = node.entry().attr_value(gimli::DW_AT_low_pc)?;
             trace!("low_pc_attr: {:?}", low_pc_attr);
             let high_pc_attr = node.entry().attr_value(gimli::DW_AT_high_pc)?;
@@ -168,7 +174,6 @@ pub fn transform_subprogram_rec<R: gimli::Reader>(
                 if let Some(high_pc) = high_pc {
                     subroutine = Some(Subroutine {
                         pc: low_pc..high_pc,
-                        name,
                         encoding: unit.encoding(),
                         variables: vec![],
                     });
@@ -391,12 +396,12 @@ impl sourcemap::SourceMap for DwarfSourceMap {
 
 use super::commands::subroutine;
 use types::*;
-pub struct DwarfSubroutineMap<'input> {
-    pub subroutines: Vec<Subroutine<Reader<'input>>>,
-    type_hash: HashMap<usize, TypeInfo<Reader<'input>>>,
+pub struct DwarfSubroutineMap {
+    pub subroutines: Vec<Subroutine<Reader>>,
+    type_hash: HashMap<usize, TypeInfo<Reader>>,
 }
 
-impl<'input> subroutine::SubroutineMap for DwarfSubroutineMap<'input> {
+impl subroutine::SubroutineMap for DwarfSubroutineMap {
     fn variable_name_list(&self, code_offset: usize) -> Result<Vec<subroutine::Variable>> {
         let offset = &(code_offset as u64);
         let subroutine = match self
@@ -462,9 +467,9 @@ impl<'input> subroutine::SubroutineMap for DwarfSubroutineMap<'input> {
             }
         };
         let piece = match var.content {
-            VariableContent::Location(location) => match location {
+            VariableContent::Location(ref location) => match location {
                 AttributeValue::Exprloc(expr) => {
-                    evaluate_variable_location(subroutine.encoding, rbp, expr)?
+                    evaluate_variable_location(subroutine.encoding, rbp, expr.clone())?
                 }
                 AttributeValue::LocationListsRef(_listsref) => unimplemented!("listsref"),
                 _ => panic!(),
@@ -4,6 +4,7 @@ mod dwarf;
 mod process;
 
 use anyhow::{anyhow, Result};
+use log::warn;
 use std::env;
 use std::io::Read;
 
@@ -14,23 +15,46 @@ fn history_file_path() -> String {
     )
 }
 
-pub fn run_loop(file: Option<String>, init_source: Option<String>) -> Result<()> {
-    let mut debugger = debugger::MainDebugger::new()?;
-    let mut buffer = Vec::new();
-    let mut context = commands::command::CommandContext {
-        sourcemap: Box::new(commands::sourcemap::EmptySourceMap::new()),
-        subroutine: Box::new(commands::subroutine::EmptySubroutineMap::new()),
-    };
+pub use commands::settings::parse_env;
+pub use debugger::DebuggerOptions;
+pub use wasminspect_wasi::{OutputRedirect, WasiConfig, WasiTrace};
 
+pub fn run_loop(
+    file: Option<String>,
+    init_source: Option<String>,
+    options: DebuggerOptions,
+    modules: Vec<(String, String)>,
+) -> Result<()> {
+    let mut debugger = debugger::MainDebugger::new(options)?;
+    // Libraries are loaded first so that the main module can import from them
+    let mut buffers = Vec::new();
+    for (name, file) in modules {
+        let mut buffer = Vec::new();
+        ::std::fs::File::open(file)?.read_to_end(&mut buffer)?;
+        buffers.push((Some(name), buffer));
+    }
     if let Some(file) = file {
-        let mut f = ::std::fs::File::open(file)?;
-        f.read_to_end(&mut buffer)?;
-        debugger.load_module(&buffer)?;
-        use dwarf::{parse_dwarf, transform_dwarf};
-        let dwarf = parse_dwarf(&buffer)?;
-        let debug_info = transform_dwarf(dwarf)?;
-        context.sourcemap = Box::new(debug_info.sourcemap);
-        context.subroutine = Box::new(debug_info.subroutine);
+        let mut buffer = Vec::new();
+        ::std::fs::File::open(file)?.read_to_end(&mut buffer)?;
+        buffers.push((None, buffer));
+    }
+    let context = commands::command::CommandContext::new();
+    for (name, buffer) in buffers {
+        let module_index = match &name {
+            Some(name) => {
+                use commands::debugger::Debugger;
+                debugger.add_module(name.clone(), &buffer)?
+            }
+            None => debugger.load_module(&buffer)?,
+        };
+        match dwarf::load_debug_info(buffer.into()) {
+            Ok(debug_info) => context.add_debug_info(module_index, debug_info),
+            Err(err) => warn!(
+                "No debug info for module {}: {}",
+                name.as_deref().unwrap_or("main"),
+                err
+            ),
+        }
     }
     let mut process = process::Process::new(
         debugger,
@@ -48,6 +72,10 @@ pub fn run_loop(file: Option<String>, init_source: Option<String>) -> Result<()>
             Box::new(commands::frame::FrameCommand::new()),
             Box::new(commands::settings::SettingsCommand::new()),
             Box::new(commands::process::ProcessCommand::new()),
+            Box::new(commands::import::ImportCommand::new()),
+            Box::new(commands::target::TargetCommand::new()),
+            Box::new(commands::image::ImageCommand::new()),
+            Box::new(commands::vfs::VfsCommand::new()),
         ],
         vec![Box::new(commands::backtrace::BacktraceCommand::new())],
         &history_file_path(),
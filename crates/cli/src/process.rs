@@ -84,6 +84,9 @@ impl<D: Debugger> Process<D> {
             for (_, command) in &self.commands {
                 println!("  {} -- {}", command.name(), command.description());
             }
+            for alias in self.aliases.values() {
+                println!("  {} -- {}", alias.name(), alias.description());
+            }
         } else {
             eprintln!("'{}' is not a valid command.", cmd_name);
         }
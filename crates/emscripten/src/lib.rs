@@ -0,0 +1,433 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use wasminspect_vm::*;
+use wasminspect_wasi::WasiContext;
+use wasmparser::{FuncType, ImportSectionEntryType, Type};
+
+/// State shared by the `env` functions of Emscripten modules
+pub struct EmscriptenContext {
+    temp_ret0: Cell<i32>,
+    started: Instant,
+}
+
+impl Default for EmscriptenContext {
+    fn default() -> Self {
+        Self {
+            temp_ret0: Cell::new(0),
+            started: Instant::now(),
+        }
+    }
+}
+
+/// `ENOSYS` of the musl libc linked by Emscripten
+const ENOSYS: i64 = 38;
+
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn to_value(&self, ty: Type) -> WasmValue {
+        let (int, float) = match *self {
+            Self::Int(v) => (v, v as f64),
+            Self::Float(v) => (v as i64, v),
+        };
+        match ty {
+            Type::I32 => WasmValue::I32(int as i32),
+            Type::I64 => WasmValue::I64(int),
+            Type::F32 => WasmValue::from(float as f32),
+            _ => WasmValue::from(float),
+        }
+    }
+}
+
+/// Whether `name` is an `env` function implemented here
+fn is_supported(name: &str) -> bool {
+    match name {
+        "emscripten_memcpy_big"
+        | "_emscripten_memcpy_js"
+        | "emscripten_resize_heap"
+        | "emscripten_get_heap_size"
+        | "emscripten_notify_memory_growth"
+        | "emscripten_date_now"
+        | "emscripten_get_now"
+        | "_emscripten_get_now_is_monotonic"
+        | "emscripten_console_log"
+        | "emscripten_console_warn"
+        | "emscripten_console_error"
+        | "setTempRet0"
+        | "getTempRet0"
+        | "__assert_fail"
+        | "abortOnCannotGrowMemory"
+        | "abort"
+        | "exit" => true,
+        _ => name.starts_with("__syscall") || name.starts_with("__sys_"),
+    }
+}
+
+/// Whether `module` imports functions from `env` which only Emscripten
+/// provides. `abort` and `exit` alone are not enough to tell.
+pub fn is_emscripten_module(module: &Module) -> bool {
+    module.imports().iter().any(|import| match import.ty {
+        ImportSectionEntryType::Function(_) => {
+            import.module == "env"
+                && is_supported(&import.field)
+                && import.field != "abort"
+                && import.field != "exit"
+        }
+        _ => false,
+    })
+}
+
+/// `env` functions imported by `module`, typed as the module imports them
+/// since their signatures vary across Emscripten versions. Functions not
+/// implemented here are left to the import stub.
+pub fn instantiate_emscripten(module: &Module) -> (EmscriptenContext, HashMap<String, HostValue>) {
+    let mut funcs = HashMap::new();
+    for import in module.imports() {
+        let type_index = match import.ty {
+            ImportSectionEntryType::Function(index) => index,
+            _ => continue,
+        };
+        if import.module != "env" || !is_supported(&import.field) {
+            continue;
+        }
+        // A broken type index is reported by the import stub or validation
+        let ty: FuncType = match module.types().get(type_index as usize) {
+            Some(ty) => ty.clone(),
+            None => continue,
+        };
+        let returns = ty.returns.to_vec();
+        let name = import.field.clone();
+        let func = if name == "emscripten_resize_heap" {
+            // Growing needs the memory which other functions borrow
+            HostFuncBody::without_memory(ty, move |args, ret, ctx, store| {
+                let grown = resize_heap(store, ctx.module_index, arg(args, 0) as u64);
+                push_result(ret, &returns, Some(Number::Int(grown as i64)))
+            })
+        } else {
+            HostFuncBody::new(ty, move |args, ret, ctx, store| {
+                let result = match console_line(&name, args, ctx.mem) {
+                    Some((fd, line)) => {
+                        write_stdio(store, fd, &line);
+                        None
+                    }
+                    None => {
+                        let em_ctx = store.get_embed_context::<EmscriptenContext>().unwrap();
+                        call(&name, args, ctx.mem, em_ctx)?
+                    }
+                };
+                push_result(ret, &returns, result)
+            })
+        };
+        funcs.insert(import.field.clone(), HostValue::Func(func));
+    }
+    (EmscriptenContext::default(), funcs)
+}
+
+/// Push `result` as the declared return type, or zero if the function
+/// returns nothing meaningful
+fn push_result(
+    ret: &mut Vec<WasmValue>,
+    returns: &[Type],
+    result: Option<Number>,
+) -> Result<(), Trap> {
+    if let Some(ty) = returns.first() {
+        ret.push(match result {
+            Some(result) => result.to_value(*ty),
+            None => WasmValue::zero(*ty).map_err(Trap::Value)?,
+        });
+    }
+    Ok(())
+}
+
+/// Grow the memory of `module_index` to at least `requested` bytes within
+/// the limits of the store
+fn resize_heap(store: &Store, module_index: ModuleIndex, requested: u64) -> bool {
+    if store.memory_count(module_index) == 0 {
+        return false;
+    }
+    let mem = store.memory(MemoryAddr::new_unsafe(module_index, 0));
+    let len = mem.borrow().data_len() as u64;
+    if requested <= len {
+        return true;
+    }
+    let page = WASM_PAGE_SIZE as u64;
+    let pages = (requested - len).div_ceil(page) as usize;
+    store
+        .check_memory_growth(pages.saturating_mul(WASM_PAGE_SIZE))
+        .is_ok()
+        && mem.borrow_mut().grow(pages).is_ok()
+}
+
+/// Line written by `emscripten_console_*` and the stream it goes to, stdout
+/// for logs and stderr for warnings and errors
+fn console_line(name: &str, args: &[WasmValue], mem: &[u8]) -> Option<(u32, Vec<u8>)> {
+    let fd = match name {
+        "emscripten_console_log" => 1,
+        "emscripten_console_warn" | "emscripten_console_error" => 2,
+        _ => return None,
+    };
+    let mut line = c_string(mem, arg(args, 0)).into_bytes();
+    line.push(b'\n');
+    Some((fd, line))
+}
+
+/// Write to stdout or stderr of the program, redirected as WASI output is
+fn write_stdio(store: &Store, fd: u32, bytes: &[u8]) {
+    // Failing to show the output is not the program's problem
+    let _ = match store.get_embed_context::<WasiContext>() {
+        Some(wasi_ctx) => {
+            wasi_ctx.write_stdio(fd, bytes);
+            Ok(())
+        }
+        None if fd == 1 => std::io::stdout().write_all(bytes),
+        None => std::io::stderr().write_all(bytes),
+    };
+}
+
+fn arg(args: &[WasmValue], index: usize) -> i64 {
+    match args.get(index) {
+        Some(WasmValue::I32(v)) => *v as u32 as i64,
+        Some(WasmValue::I64(v)) => *v,
+        _ => 0,
+    }
+}
+
+/// NUL terminated string at `ptr`
+fn c_string(mem: &[u8], ptr: i64) -> String {
+    let bytes = mem.get(ptr as usize..).unwrap_or_default();
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+fn call(
+    name: &str,
+    args: &[WasmValue],
+    mem: &mut [u8],
+    ctx: &EmscriptenContext,
+) -> Result<Option<Number>, Trap> {
+    let result = match name {
+        "emscripten_memcpy_big" | "_emscripten_memcpy_js" => {
+            let (dest, src, len) = (arg(args, 0), arg(args, 1), arg(args, 2));
+            let src_end = src
+                .checked_add(len)
+                .filter(|end| *end as usize <= mem.len());
+            let dest_end = dest
+                .checked_add(len)
+                .filter(|end| *end as usize <= mem.len());
+            if src_end.is_none() || dest_end.is_none() {
+                return Err(Trap::Abort(format!(
+                    "memcpy of {} bytes from 0x{:x} to 0x{:x} is out of bounds",
+                    len, src, dest
+                )));
+            }
+            mem.copy_within(src as usize..(src + len) as usize, dest as usize);
+            Number::Int(dest)
+        }
+        "emscripten_get_heap_size" => Number::Int(mem.len() as i64),
+        "emscripten_notify_memory_growth" => return Ok(None),
+        "emscripten_date_now" => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            Number::Float(now.as_secs_f64() * 1000.0)
+        }
+        "emscripten_get_now" => Number::Float(ctx.started.elapsed().as_secs_f64() * 1000.0),
+        "_emscripten_get_now_is_monotonic" => Number::Int(1),
+        "setTempRet0" => {
+            ctx.temp_ret0.set(arg(args, 0) as i32);
+            return Ok(None);
+        }
+        "getTempRet0" => Number::Int(ctx.temp_ret0.get() as i64),
+        "__assert_fail" => {
+            return Err(Trap::Abort(format!(
+                "Assertion failed: {}, at: {}:{} {}",
+                c_string(mem, arg(args, 0)),
+                c_string(mem, arg(args, 1)),
+                arg(args, 2),
+                c_string(mem, arg(args, 3))
+            )))
+        }
+        "abortOnCannotGrowMemory" => {
+            return Err(Trap::Abort(format!(
+                "Cannot enlarge memory to {} bytes",
+                arg(args, 0)
+            )))
+        }
+        "abort" => return Err(Trap::Abort(String::new())),
+        "exit" => return Err(Trap::Exit(arg(args, 0) as i32)),
+        // System calls are reached only when WASI doesn't cover them
+        _ => Number::Int(-ENOSYS),
+    };
+    Ok(Some(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A module importing `env.field` typed as the `type_index`th type,
+    /// where the only type is `(func (param i32))`
+    fn importing(field: &str, type_index: u8) -> Module {
+        let mut bytes = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        bytes.extend_from_slice(&[0x01, 0x05, 0x01, 0x60, 0x01, 0x7f, 0x00]);
+        bytes.extend_from_slice(&[0x02, 8 + field.len() as u8, 0x01]);
+        bytes.extend_from_slice(&[0x03, b'e', b'n', b'v', field.len() as u8]);
+        bytes.extend_from_slice(field.as_bytes());
+        bytes.extend_from_slice(&[0x00, type_index]);
+        Module::from_bytes(&bytes).unwrap()
+    }
+
+    // (memory 1 3)
+    #[rustfmt::skip]
+    const MEMORY_MODULE: [u8; 14] = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        0x05, 0x04, 0x01, 0x01, 0x01, 0x03,
+    ];
+
+    fn int(result: Result<Option<Number>, Trap>) -> Option<i64> {
+        match result.unwrap() {
+            Some(Number::Int(v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn detect_emscripten_module() {
+        let cases = [
+            ("emscripten_memcpy_big", true),
+            ("__syscall_openat", true),
+            ("abort", false),
+            ("exit", false),
+            ("memcpy", false),
+        ];
+        for (field, expected) in cases.iter() {
+            assert_eq!(is_emscripten_module(&importing(field, 0)), *expected);
+        }
+    }
+
+    #[test]
+    fn skip_broken_type_index() {
+        let (_, funcs) = instantiate_emscripten(&importing("setTempRet0", 0));
+        assert!(funcs.contains_key("setTempRet0"));
+        let (_, funcs) = instantiate_emscripten(&importing("setTempRet0", 1));
+        assert!(funcs.is_empty());
+    }
+
+    #[test]
+    fn memcpy_within_bounds() {
+        let ctx = EmscriptenContext::default();
+        let mut mem = b"abcd\0\0\0\0".to_vec();
+        let args = [WasmValue::I32(4), WasmValue::I32(0), WasmValue::I32(4)];
+        assert_eq!(
+            int(call("emscripten_memcpy_big", &args, &mut mem, &ctx)),
+            Some(4)
+        );
+        assert_eq!(mem, b"abcdabcd");
+
+        for (dest, src, len) in [(4, 0, 5), (0, 5, 4), (0, -1, 2), (0, 0, -1)].iter() {
+            let args = [
+                WasmValue::I32(*dest),
+                WasmValue::I32(*src),
+                WasmValue::I32(*len),
+            ];
+            match call("_emscripten_memcpy_js", &args, &mut mem, &ctx) {
+                Err(Trap::Abort(message)) => assert!(message.ends_with("is out of bounds")),
+                _ => panic!("memcpy({}, {}, {}) should abort", dest, src, len),
+            }
+        }
+        assert_eq!(mem, b"abcdabcd");
+    }
+
+    #[test]
+    fn assert_fail_message() {
+        let ctx = EmscriptenContext::default();
+        let mut mem = b"x > 0\0main.c\0main\0".to_vec();
+        let args = [
+            WasmValue::I32(0),
+            WasmValue::I32(6),
+            WasmValue::I32(12),
+            WasmValue::I32(13),
+        ];
+        match call("__assert_fail", &args, &mut mem, &ctx) {
+            Err(Trap::Abort(message)) => {
+                assert_eq!(message, "Assertion failed: x > 0, at: main.c:12 main")
+            }
+            _ => panic!("__assert_fail should abort"),
+        }
+        // Pointers out of the memory read as empty strings
+        let args = [
+            WasmValue::I32(100),
+            WasmValue::I32(-1),
+            WasmValue::I32(0),
+            WasmValue::I32(18),
+        ];
+        match call("__assert_fail", &args, &mut mem, &ctx) {
+            Err(Trap::Abort(message)) => assert_eq!(message, "Assertion failed: , at: :0 "),
+            _ => panic!("__assert_fail should abort"),
+        }
+    }
+
+    #[test]
+    fn temp_ret0_round_trip() {
+        let ctx = EmscriptenContext::default();
+        let mut mem = vec![];
+        assert_eq!(int(call("getTempRet0", &[], &mut mem, &ctx)), Some(0));
+        let args = [WasmValue::I32(-2)];
+        assert!(call("setTempRet0", &args, &mut mem, &ctx)
+            .unwrap()
+            .is_none());
+        let result = call("getTempRet0", &[], &mut mem, &ctx).unwrap().unwrap();
+        assert_eq!(result.to_value(Type::I32), WasmValue::I32(-2));
+    }
+
+    #[test]
+    fn unimplemented_syscalls() {
+        let ctx = EmscriptenContext::default();
+        let mut mem = vec![];
+        for name in ["__syscall_openat", "__sys_ioctl"].iter() {
+            assert_eq!(int(call(name, &[], &mut mem, &ctx)), Some(-ENOSYS));
+        }
+    }
+
+    #[test]
+    fn console_lines() {
+        let mem = b"hi\0".to_vec();
+        let args = [WasmValue::I32(0)];
+        let cases = [
+            ("emscripten_console_log", Some((1, b"hi\n".to_vec()))),
+            ("emscripten_console_warn", Some((2, b"hi\n".to_vec()))),
+            ("emscripten_console_error", Some((2, b"hi\n".to_vec()))),
+            ("abort", None),
+        ];
+        for (name, expected) in cases.iter() {
+            assert_eq!(console_line(name, &args, &mem), *expected);
+        }
+    }
+
+    #[test]
+    fn resize_heap_within_limits() {
+        let page = WASM_PAGE_SIZE as u64;
+        let mut store = Store::new();
+        let module_index = store.load_module(None, &MEMORY_MODULE).unwrap();
+        let mem = store.memory(MemoryAddr::new_unsafe(module_index, 0));
+        assert!(resize_heap(&store, module_index, page));
+        assert_eq!(mem.borrow().page_count(), 1);
+        assert!(resize_heap(&store, module_index, page + 1));
+        assert_eq!(mem.borrow().page_count(), 2);
+        // Over the maximum of the memory
+        assert!(!resize_heap(&store, module_index, 3 * page + 1));
+
+        store.set_limits(ResourceLimits {
+            max_memory_bytes: Some(2 * WASM_PAGE_SIZE),
+            ..ResourceLimits::default()
+        });
+        let mem = store.memory(MemoryAddr::new_unsafe(module_index, 0));
+        assert!(!resize_heap(&store, module_index, 3 * page));
+        assert_eq!(mem.borrow().page_count(), 2);
+    }
+}
@@ -1,6 +1,5 @@
 use super::linker::{GlobalAddress, LinkableAddress};
 use std::cell::RefCell;
-use std::rc::Rc;
 
 // Internal representation of global function address to reference same function instances
 
@@ -9,13 +8,13 @@ pub type FuncAddr = LinkableAddress<FunctionInstance>;
 pub type ExecutableFuncAddr = GlobalAddress<FunctionInstance>;
 
 use super::table::TableInstance;
-pub type TableAddr = LinkableAddress<Rc<RefCell<TableInstance>>>;
-pub type ResolvedTableAddr = GlobalAddress<Rc<RefCell<TableInstance>>>;
+pub type TableAddr = LinkableAddress<RefCell<TableInstance>>;
+pub type ResolvedTableAddr = GlobalAddress<RefCell<TableInstance>>;
 
 use super::memory::MemoryInstance;
-pub type MemoryAddr = LinkableAddress<Rc<RefCell<MemoryInstance>>>;
-pub type ResolvedMemoryAddr = GlobalAddress<Rc<RefCell<MemoryInstance>>>;
+pub type MemoryAddr = LinkableAddress<RefCell<MemoryInstance>>;
+pub type ResolvedMemoryAddr = GlobalAddress<RefCell<MemoryInstance>>;
 
 use super::global::GlobalInstance;
-pub type GlobalAddr = LinkableAddress<Rc<RefCell<GlobalInstance>>>;
-pub type ResolvedGlobalAddr = GlobalAddress<Rc<RefCell<GlobalInstance>>>;
+pub type GlobalAddr = LinkableAddress<RefCell<GlobalInstance>>;
+pub type ResolvedGlobalAddr = GlobalAddress<RefCell<GlobalInstance>>;
@@ -0,0 +1,109 @@
+use super::address::*;
+use super::module::ModuleIndex;
+use anyhow::Result;
+use std::collections::HashMap;
+use wasmparser::BinaryReader;
+
+/// Memory and table requirements of a shared library, read from its
+/// `dylink` or `dylink.0` custom section
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DylinkInfo {
+    /// Size of the static data in bytes
+    pub memory_size: u32,
+    /// Alignment of the static data as a power of 2
+    pub memory_alignment: u32,
+    /// Number of table slots for functions whose address is taken
+    pub table_size: u32,
+    pub table_alignment: u32,
+    /// Libraries this one depends on
+    pub needed: Vec<String>,
+}
+
+const WASM_DYLINK_MEM_INFO: u32 = 1;
+const WASM_DYLINK_NEEDED: u32 = 2;
+
+impl DylinkInfo {
+    /// Read the legacy `dylink` section
+    pub(crate) fn read_legacy(mut reader: BinaryReader) -> Result<Self> {
+        let mut info = Self::default();
+        info.read_mem_info(&mut reader)?;
+        info.read_needed(&mut reader)?;
+        Ok(info)
+    }
+
+    /// Read the `dylink.0` section. Subsections other than memory info and
+    /// needed libraries only carry hints, so they are skipped.
+    pub(crate) fn read(mut reader: BinaryReader) -> Result<Self> {
+        let mut info = Self::default();
+        while !reader.eof() {
+            let kind = reader.read_u8()?;
+            let size = reader.read_var_u32()? as usize;
+            let offset = reader.original_position();
+            let mut payload = BinaryReader::new_with_offset(reader.read_bytes(size)?, offset);
+            match kind {
+                WASM_DYLINK_MEM_INFO => info.read_mem_info(&mut payload)?,
+                WASM_DYLINK_NEEDED => info.read_needed(&mut payload)?,
+                _ => (),
+            }
+        }
+        Ok(info)
+    }
+
+    fn read_mem_info(&mut self, reader: &mut BinaryReader) -> Result<()> {
+        self.memory_size = reader.read_var_u32()?;
+        self.memory_alignment = reader.read_var_u32()?;
+        self.table_size = reader.read_var_u32()?;
+        self.table_alignment = reader.read_var_u32()?;
+        Ok(())
+    }
+
+    fn read_needed(&mut self, reader: &mut BinaryReader) -> Result<()> {
+        let count = reader.read_var_u32()?;
+        for _ in 0..count {
+            self.needed.push(reader.read_string()?.to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Addresses below this are left unused so that null pointers don't alias
+/// static data
+pub(crate) const GLOBAL_BASE: u32 = 1024;
+pub(crate) const STACK_SIZE: u32 = 64 * 1024;
+
+/// A `GOT.mem` or `GOT.func` global shared by all libraries importing it
+pub(crate) struct GotEntry {
+    pub global: ResolvedGlobalAddr,
+    pub resolved: bool,
+}
+
+/// Memory and table regions allocated for a library
+pub(crate) struct DylinkBases {
+    pub memory_base: u32,
+    pub table_base: u32,
+}
+
+/// State shared by the libraries loaded with `Store::load_dylink_module`
+pub(crate) struct DylinkState {
+    pub memory: ResolvedMemoryAddr,
+    pub table: ResolvedTableAddr,
+    pub stack_pointer: ResolvedGlobalAddr,
+    /// End of the static data allocated so far
+    pub memory_end: u32,
+    /// Libraries in load order, which is also the symbol lookup order
+    pub libraries: Vec<ModuleIndex>,
+    pub memory_bases: HashMap<ModuleIndex, u32>,
+    pub got_mem: HashMap<String, GotEntry>,
+    pub got_func: HashMap<String, GotEntry>,
+    /// Function imports waiting for a library which exports them
+    pub pending_funcs: Vec<(FuncAddr, String)>,
+}
+
+pub(crate) fn align_to(value: u32, alignment_log2: u32) -> u32 {
+    let alignment = 1u32.checked_shl(alignment_log2).unwrap_or(0);
+    if alignment <= 1 {
+        value
+    } else {
+        (value + alignment - 1) & !(alignment - 1)
+    }
+}
@@ -0,0 +1,254 @@
+use super::address::FuncAddr;
+use super::executor::{Executor, Signal, Trap, WasmError};
+use super::func::{FunctionInstance, InstIndex};
+use super::inst::Instruction;
+use super::interceptor::Interceptor;
+use super::stack::{CallFrame, ProgramCounter};
+use super::store::Store;
+use super::value::Value;
+use wasmparser::Type;
+
+/// The reason why an `Execution` stopped and returned control to the embedder
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopReason {
+    /// The requested step has been completed
+    Step,
+    /// The interceptor requested to stop (e.g. a function breakpoint)
+    Breakpoint,
+    /// The predicate given to `continue_until` was satisfied
+    Predicate,
+    /// The entry function returned these values
+    Finish(Vec<Value>),
+    /// The program exited with the status without returning
+    Exit(i32),
+}
+
+/// A resumable invocation of a function.
+///
+/// `Execution` owns the executor state of a single invocation, so that
+/// embedders can drive it instruction by instruction and inspect frames,
+/// locals and the value stack between steps.
+pub struct Execution {
+    executor: Option<Executor>,
+    ret_types: Vec<Type>,
+    results: Option<Vec<Value>>,
+    exit_code: Option<i32>,
+}
+
+impl Execution {
+    /// Prepare an invocation of `func_addr` with `arguments`.
+    /// Host functions can't be suspended, so they are called immediately and
+    /// the first step reports their results.
+    pub fn new(
+        func_addr: FuncAddr,
+        arguments: Vec<Value>,
+        store: &Store,
+    ) -> Result<Self, WasmError> {
+        let (func, exec_addr) = store
+            .func(func_addr)
+            .ok_or(WasmError::ExecutionError(Trap::UndefinedFunc(func_addr.1)))?;
+        let ret_types = func.ty().returns.to_vec();
+        match func {
+            FunctionInstance::Host(host) => {
+                let mut results = Vec::new();
+                match host.code().call(
+                    &arguments,
+                    &mut results,
+                    store,
+                    func_addr.module_index(),
+                    None,
+                ) {
+                    Ok(_) => Ok(Self {
+                        executor: None,
+                        ret_types,
+                        results: Some(results),
+                        exit_code: None,
+                    }),
+                    Err(Trap::Exit(code)) => Err(WasmError::Exit(code)),
+                    Err(trap) => Err(WasmError::ExecutionError(trap)),
+                }
+            }
+            FunctionInstance::Defined(func) => {
+                func.instructions().map_err(WasmError::ExecutionError)?;
+                let frame = CallFrame::new_from_func(exec_addr, func, arguments, None);
+                let pc = ProgramCounter::new(func.module_index(), exec_addr, InstIndex::zero());
+                let executor = Executor::new(frame, ret_types.len(), pc);
+                Ok(Self {
+                    executor: Some(executor),
+                    ret_types,
+                    results: None,
+                    exit_code: None,
+                })
+            }
+        }
+    }
+
+    /// True when the function returned or the program exited
+    pub fn is_finished(&self) -> bool {
+        self.executor.is_none()
+    }
+
+    /// Status of the program if it exited
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// Execute a single instruction
+    pub fn step<I: Interceptor>(
+        &mut self,
+        store: &Store,
+        interceptor: &I,
+    ) -> Result<StopReason, WasmError> {
+        let executor = match self.executor {
+            Some(ref mut executor) => executor,
+            None => {
+                return Ok(match self.exit_code {
+                    Some(code) => StopReason::Exit(code),
+                    None => StopReason::Finish(self.results.clone().unwrap_or_default()),
+                })
+            }
+        };
+        match executor.execute_step(store, interceptor) {
+            Ok(Signal::Next) => Ok(StopReason::Step),
+            Ok(Signal::Breakpoint) => Ok(StopReason::Breakpoint),
+            Ok(Signal::End) => {
+                let results = executor
+                    .pop_result(self.ret_types.clone())
+                    .map_err(WasmError::ReturnValueError);
+                self.executor = None;
+                let results = results?;
+                self.results = Some(results.clone());
+                Ok(StopReason::Finish(results))
+            }
+            Ok(Signal::Exit(code)) | Err(Trap::Exit(code)) => {
+                self.executor = None;
+                self.exit_code = Some(code);
+                Ok(StopReason::Exit(code))
+            }
+            Err(err) => Err(WasmError::ExecutionError(err)),
+        }
+    }
+
+    /// Execute instructions until the current function call returns to the
+    /// current frame depth, stepping over any call
+    pub fn step_over<I: Interceptor>(
+        &mut self,
+        store: &Store,
+        interceptor: &I,
+    ) -> Result<StopReason, WasmError> {
+        let initial_depth = self.frame_depth();
+        self.step_while(store, interceptor, |depth| initial_depth < depth)
+    }
+
+    /// Execute instructions until the current function returns to its caller
+    pub fn step_out<I: Interceptor>(
+        &mut self,
+        store: &Store,
+        interceptor: &I,
+    ) -> Result<StopReason, WasmError> {
+        let initial_depth = self.frame_depth();
+        self.step_while(store, interceptor, |depth| initial_depth <= depth)
+    }
+
+    /// Continue execution until the function finishes, the interceptor
+    /// requests a stop, or `predicate` returns true after a step
+    pub fn continue_until<I, F>(
+        &mut self,
+        store: &Store,
+        interceptor: &I,
+        mut predicate: F,
+    ) -> Result<StopReason, WasmError>
+    where
+        I: Interceptor,
+        F: FnMut(&Execution, &Store) -> bool,
+    {
+        loop {
+            match self.step(store, interceptor)? {
+                StopReason::Step => {
+                    if predicate(self, store) {
+                        return Ok(StopReason::Predicate);
+                    }
+                }
+                reason => return Ok(reason),
+            }
+        }
+    }
+
+    /// Continue execution until the function finishes or the interceptor
+    /// requests a stop
+    pub fn resume<I: Interceptor>(
+        &mut self,
+        store: &Store,
+        interceptor: &I,
+    ) -> Result<StopReason, WasmError> {
+        self.continue_until(store, interceptor, |_, _| false)
+    }
+
+    fn step_while<I, F>(
+        &mut self,
+        store: &Store,
+        interceptor: &I,
+        condition: F,
+    ) -> Result<StopReason, WasmError>
+    where
+        I: Interceptor,
+        F: Fn(usize) -> bool,
+    {
+        let mut reason = self.step(store, interceptor)?;
+        while let StopReason::Step = reason {
+            if !condition(self.frame_depth()) {
+                break;
+            }
+            reason = self.step(store, interceptor)?;
+        }
+        Ok(reason)
+    }
+
+    fn frame_depth(&self) -> usize {
+        self.frames().len()
+    }
+
+    pub fn executor(&self) -> Option<&Executor> {
+        self.executor.as_ref()
+    }
+
+    /// The program counter pointing the next instruction to execute
+    pub fn pc(&self) -> Option<ProgramCounter> {
+        self.executor.as_ref().map(|executor| executor.pc)
+    }
+
+    /// Call frames from the outermost to the innermost
+    pub fn frames(&self) -> Vec<&CallFrame> {
+        match self.executor {
+            Some(ref executor) => executor.stack.peek_frames(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn current_frame(&self) -> Option<&CallFrame> {
+        self.executor
+            .as_ref()
+            .and_then(|executor| executor.stack.current_frame().ok())
+    }
+
+    pub fn locals(&self) -> &[Value] {
+        match self.current_frame() {
+            Some(frame) => &frame.locals,
+            None => &[],
+        }
+    }
+
+    pub fn stack_values(&self) -> Vec<&Value> {
+        match self.executor {
+            Some(ref executor) => executor.stack.peek_values(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Instructions of the currently executing function
+    pub fn instructions<'a>(&self, store: &'a Store) -> Option<&'a [Instruction]> {
+        self.executor
+            .as_ref()
+            .and_then(|executor| executor.current_func_insts(store).ok())
+    }
+}
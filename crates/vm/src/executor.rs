@@ -1,4 +1,5 @@
 use super::address::{FuncAddr, GlobalAddr, MemoryAddr, TableAddr};
+use super::execution::{Execution, StopReason};
 use super::func::*;
 use super::inst::{Instruction, InstructionKind};
 use super::interceptor::{Interceptor, NopInterceptor};
@@ -14,6 +15,7 @@ use super::value::{
     ExtendInto, FromLittleEndian, IntoLittleEndian, NativeValue, Value, F32, F64, I32, I64, U32,
     U64,
 };
+use super::WASM_PAGE_SIZE;
 use wasmparser::{FuncType, Type, TypeOrFuncType};
 
 use std::ops::*;
@@ -37,6 +39,19 @@ pub enum Trap {
     },
     UnexpectedStackValueType(/* expected: */ Type, /* actual: */ Type),
     UndefinedFunc(usize),
+    ModuleNotLoaded(ModuleIndex),
+    InvalidFunctionBody {
+        func_name: String,
+        message: String,
+    },
+    UnresolvedImport {
+        module: String,
+        field: String,
+    },
+    /// The program requested to exit with the status, e.g. by WASI `proc_exit`
+    Exit(i32),
+    /// The program aborted with the message, e.g. by a failed assertion
+    Abort(String),
 }
 
 impl std::error::Error for Trap {}
@@ -54,7 +69,17 @@ impl std::fmt::Display for Trap {
                 expected, actual, name
             ),
             Self::UndefinedFunc(addr) => write!(f, "uninitialized func at {:?}", addr),
+            Self::ModuleNotLoaded(index) => write!(f, "module {} is not loaded", index.0),
             Self::Unreachable => write!(f, "unreachable"),
+            Self::InvalidFunctionBody { func_name, message } => {
+                write!(f, "failed to decode body of '{}': {}", func_name, message)
+            }
+            Self::UnresolvedImport { module, field } => {
+                write!(f, "call to unresolved import \"{}.{}\"", module, field)
+            }
+            Self::Exit(code) => write!(f, "exited with status {}", code),
+            Self::Abort(message) if message.is_empty() => write!(f, "aborted"),
+            Self::Abort(message) => write!(f, "aborted: {}", message),
             _ => write!(f, "{:?}", self),
         }
     }
@@ -64,6 +89,8 @@ pub enum Signal {
     Next,
     Breakpoint,
     End,
+    /// The program exited with the status
+    Exit(i32),
 }
 
 pub type ExecResult<T> = std::result::Result<T, Trap>;
@@ -104,7 +131,7 @@ impl Executor {
 
     pub fn current_func_insts<'a>(&self, store: &'a Store) -> ExecResult<&'a [Instruction]> {
         let func = store.func_global(self.pc.exec_addr());
-        Ok(&func.defined().unwrap().instructions())
+        func.defined().unwrap().instructions()
     }
 
     pub fn execute_step<I: Interceptor>(
@@ -114,8 +141,8 @@ impl Executor {
     ) -> ExecResult<Signal> {
         let func = store.func_global(self.pc.exec_addr()).defined().unwrap();
         let module_index = func.module_index().clone();
-        let inst = func.inst(self.pc.inst_index());
-        return self.execute_inst(&inst, module_index, store, interceptor);
+        let inst = &func.instructions()?[self.pc.inst_index().0 as usize];
+        return self.execute_inst(inst, module_index, store, interceptor);
     }
 
     fn execute_inst<I: Interceptor>(
@@ -255,7 +282,10 @@ impl Executor {
             } => {
                 let frame = self.stack.current_frame().map_err(Trap::Stack)?;
                 let addr = TableAddr::new_unsafe(frame.module_index(), 0);
-                let module = store.module(frame.module_index()).defined().unwrap();
+                let module = store
+                    .module(frame.module_index())
+                    .and_then(ModuleInstance::defined)
+                    .ok_or(Trap::ModuleNotLoaded(frame.module_index()))?;
                 let ty = module.get_type(index as usize);
                 let buf_index: i32 = self.pop_as()?;
                 let table = store.table(addr);
@@ -392,18 +422,19 @@ impl Executor {
             }
             InstructionKind::MemoryGrow { reserved: _ } => {
                 let grow_page: i32 = self.pop_as()?;
+                let grow_page = grow_page as u32 as usize;
                 let mem = self.memory(store)?;
                 let size = mem.borrow().page_count();
-                match mem.borrow_mut().grow(grow_page as usize) {
-                    Ok(_) => {
-                        self.stack.push_value(Value::I32(size as i32));
-                    }
-                    Err(err) => {
-                        println!("[Debug] Failed to grow memory {:?}", err);
-                        self.stack.push_value(Value::I32(-1));
-                    }
+                // A byte count which overflows can never fit in the limit
+                let grow_bytes = grow_page.saturating_mul(WASM_PAGE_SIZE);
+                let grown = store.check_memory_growth(grow_bytes).is_ok()
+                    && mem.borrow_mut().grow(grow_page).is_ok();
+                if grown {
+                    self.stack.push_value(Value::I32(size as i32));
+                } else {
+                    self.stack.push_value(Value::I32(-1));
                 }
-                Ok(Signal::Next)
+                interceptor.after_memory_grow(size, grow_page, grown)
             }
 
             InstructionKind::I32Const { value } => {
@@ -720,22 +751,31 @@ impl Executor {
         let arity = func.ty().returns.len();
         match func {
             FunctionInstance::Defined(func) => {
+                // Decode the body before entering so that decoding failure is
+                // reported at the call site
+                func.instructions()?;
                 let pc = ProgramCounter::new(func.module_index(), exec_addr, InstIndex::zero());
                 let frame = CallFrame::new_from_func(exec_addr, &func, args, Some(self.pc));
                 self.stack.set_frame(frame).map_err(Trap::Stack)?;
                 self.stack.push_label(Label::Return(arity));
                 self.pc = pc;
-                interceptor.invoke_func(func.name())
+                interceptor.invoke_func(func.name(), func.module_index())
             }
             FunctionInstance::Host(func) => {
                 let mut result = Vec::new();
+                // `pc` has already moved past the call instruction
+                let caller = ProgramCounter::new(
+                    self.pc.module_index(),
+                    self.pc.exec_addr(),
+                    InstIndex(self.pc.inst_index().0.saturating_sub(1)),
+                );
                 func.code()
-                    .call(&args, &mut result, store, addr.module_index())?;
+                    .call(&args, &mut result, store, addr.module_index(), Some(caller))?;
                 assert_eq!(result.len(), arity);
                 for v in result {
                     self.stack.push_value(v);
                 }
-                Ok(Signal::Next)
+                interceptor.invoke_host_func(func.module_name(), func.field_name())
             }
         }
     }
@@ -769,7 +809,7 @@ impl Executor {
         Ok(Signal::Next)
     }
 
-    fn memory(&self, store: &Store) -> ExecResult<std::rc::Rc<std::cell::RefCell<MemoryInstance>>> {
+    fn memory<'a>(&self, store: &'a Store) -> ExecResult<&'a std::cell::RefCell<MemoryInstance>> {
         let frame = self.stack.current_frame().map_err(Trap::Stack)?;
         let mem_addr = MemoryAddr::new_unsafe(frame.module_index(), 0);
         Ok(store.memory(mem_addr))
@@ -855,29 +895,149 @@ impl Executor {
     }
 }
 
-use anyhow;
-use wasmparser::InitExpr;
+/// An error evaluating the constant expression of a global initializer or
+/// a segment offset
+#[derive(Debug)]
+pub enum ConstExprError {
+    Empty,
+    NonConstantInstruction(InstructionKind),
+    TypeMismatch {
+        expected: Type,
+        actual: Type,
+    },
+    /// The expression leaves this many values instead of exactly one
+    StackMismatch(usize),
+    UnknownGlobal(u32),
+    MutableGlobal(u32),
+    /// `ref.func` and `ref.null` evaluate to references, which `Value` can't
+    /// hold yet, so funcref and externref globals fail to instantiate
+    ReferenceValue,
+}
+
+impl std::error::Error for ConstExprError {}
+
+impl std::fmt::Display for ConstExprError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty constant expression"),
+            Self::NonConstantInstruction(kind) => {
+                write!(f, "constant expression required: {:?}", kind)
+            }
+            Self::TypeMismatch { expected, actual } => write!(
+                f,
+                "type mismatch in constant expression, expected {:?} but got {:?}",
+                expected, actual
+            ),
+            Self::StackMismatch(count) => write!(
+                f,
+                "type mismatch: constant expression leaves {} values",
+                count
+            ),
+            Self::UnknownGlobal(index) => write!(f, "unknown global {}", index),
+            Self::MutableGlobal(index) => write!(
+                f,
+                "constant expression required: global {} is mutable",
+                index
+            ),
+            Self::ReferenceValue => write!(f, "reference values are not supported in globals"),
+        }
+    }
+}
+
+/// Result of a constant expression
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConstValue {
+    Num(Value),
+    /// A function reference by index in the module, or null
+    FuncRef(Option<u32>),
+}
+
+/// Evaluate a constant expression, including the arithmetic instructions
+/// of the extended-const proposal. `global` looks up the value of
+/// `global.get` operands.
+pub fn eval_const_expr_with<G>(
+    init_expr: &[Instruction],
+    global: G,
+) -> Result<ConstValue, ConstExprError>
+where
+    G: Fn(u32) -> Result<Value, ConstExprError>,
+{
+    fn pop_num(stack: &mut Vec<ConstValue>, expected: Type) -> Result<Value, ConstExprError> {
+        match stack.pop() {
+            Some(ConstValue::Num(value)) if value.value_type() == expected => Ok(value),
+            Some(ConstValue::Num(value)) => Err(ConstExprError::TypeMismatch {
+                expected,
+                actual: value.value_type(),
+            }),
+            Some(ConstValue::FuncRef(_)) => Err(ConstExprError::TypeMismatch {
+                expected,
+                actual: Type::AnyFunc,
+            }),
+            None => Err(ConstExprError::StackMismatch(0)),
+        }
+    }
+    macro_rules! binop {
+        ($stack:ident, $ty:ident, $op:ident) => {{
+            let rhs = pop_num(&mut $stack, Type::$ty)?;
+            let lhs = pop_num(&mut $stack, Type::$ty)?;
+            match (lhs, rhs) {
+                (Value::$ty(lhs), Value::$ty(rhs)) => Value::$ty(lhs.$op(rhs)),
+                _ => unreachable!(),
+            }
+        }};
+    }
+
+    if init_expr.is_empty() {
+        return Err(ConstExprError::Empty);
+    }
+    let mut stack = Vec::new();
+    for inst in init_expr {
+        let value = match inst.kind {
+            InstructionKind::I32Const { value } => ConstValue::Num(Value::I32(value)),
+            InstructionKind::I64Const { value } => ConstValue::Num(Value::I64(value)),
+            InstructionKind::F32Const { value } => ConstValue::Num(Value::F32(value.bits())),
+            InstructionKind::F64Const { value } => ConstValue::Num(Value::F64(value.bits())),
+            InstructionKind::GlobalGet { global_index } => ConstValue::Num(global(global_index)?),
+            InstructionKind::RefNull => ConstValue::FuncRef(None),
+            InstructionKind::RefFunc { function_index } => {
+                ConstValue::FuncRef(Some(function_index))
+            }
+            InstructionKind::I32Add => ConstValue::Num(binop!(stack, I32, wrapping_add)),
+            InstructionKind::I32Sub => ConstValue::Num(binop!(stack, I32, wrapping_sub)),
+            InstructionKind::I32Mul => ConstValue::Num(binop!(stack, I32, wrapping_mul)),
+            InstructionKind::I64Add => ConstValue::Num(binop!(stack, I64, wrapping_add)),
+            InstructionKind::I64Sub => ConstValue::Num(binop!(stack, I64, wrapping_sub)),
+            InstructionKind::I64Mul => ConstValue::Num(binop!(stack, I64, wrapping_mul)),
+            InstructionKind::End => break,
+            ref kind => return Err(ConstExprError::NonConstantInstruction(kind.clone())),
+        };
+        stack.push(value);
+    }
+    match stack.len() {
+        1 => Ok(stack.pop().unwrap()),
+        count => Err(ConstExprError::StackMismatch(count)),
+    }
+}
+
+/// Evaluate a constant expression of a module instantiated in `store`.
+/// Expressions resulting in a reference fail with
+/// `ConstExprError::ReferenceValue`.
 pub fn eval_const_expr(
-    init_expr: &InitExpr,
+    init_expr: &[Instruction],
     store: &Store,
     module_index: ModuleIndex,
-) -> anyhow::Result<Value> {
-    use super::inst::transform_inst;
-    let mut reader = init_expr.get_operators_reader();
-    let base_offset = reader.original_position();
-    let inst = transform_inst(&mut reader, base_offset)?;
-    let val = match inst.kind {
-        InstructionKind::I32Const { value } => Value::I32(value),
-        InstructionKind::I64Const { value } => Value::I64(value),
-        InstructionKind::F32Const { value } => Value::F32(value.bits()),
-        InstructionKind::F64Const { value } => Value::F64(value.bits()),
-        InstructionKind::GlobalGet { global_index } => {
-            let addr = GlobalAddr::new_unsafe(module_index, global_index as usize);
-            store.global(addr).borrow().value()
-        }
-        _ => panic!("Unsupported init_expr {:?}", inst.kind),
+) -> Result<Value, ConstExprError> {
+    let global = |global_index| {
+        let addr = GlobalAddr::new_unsafe(module_index, global_index as usize);
+        store
+            .try_global(addr)
+            .map(|global| global.borrow().value())
+            .ok_or(ConstExprError::UnknownGlobal(global_index))
     };
-    Ok(val)
+    match eval_const_expr_with(init_expr, global)? {
+        ConstValue::Num(value) => Ok(value),
+        ConstValue::FuncRef(_) => Err(ConstExprError::ReferenceValue),
+    }
 }
 
 #[derive(Debug)]
@@ -885,9 +1045,12 @@ pub enum WasmError {
     ExecutionError(Trap),
     EntryFunctionNotFound(String),
     ReturnValueError(ReturnValError),
-    HostExecutionError,
+    /// The program exited with the status before returning
+    Exit(i32),
 }
 
+impl std::error::Error for WasmError {}
+
 impl std::fmt::Display for WasmError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -898,7 +1061,7 @@ impl std::fmt::Display for WasmError {
             WasmError::ReturnValueError(err) => {
                 write!(f, "Failed to get returned value: {:?}", err)
             }
-            WasmError::HostExecutionError => write!(f, "Failed to execute host func"),
+            WasmError::Exit(code) => write!(f, "Process exited with status {}", code),
         }
     }
 }
@@ -908,41 +1071,13 @@ pub fn simple_invoke_func(
     arguments: Vec<Value>,
     store: &mut Store,
 ) -> Result<Vec<Value>, WasmError> {
-    match store
-        .func(func_addr)
-        .ok_or(WasmError::ExecutionError(Trap::UndefinedFunc(func_addr.1)))?
-    {
-        (FunctionInstance::Host(host), _) => {
-            let mut results = Vec::new();
-            match host
-                .code()
-                .call(&arguments, &mut results, store, func_addr.module_index())
-            {
-                Ok(_) => Ok(results),
-                Err(_) => Err(WasmError::HostExecutionError),
-            }
-        }
-        (FunctionInstance::Defined(func), exec_addr) => {
-            let (frame, ret_types) = {
-                let ret_types = &func.ty().returns;
-                let frame = CallFrame::new_from_func(exec_addr, func, arguments, None);
-                (frame, ret_types)
-            };
-            let pc = ProgramCounter::new(func.module_index(), exec_addr, InstIndex::zero());
-            let interceptor = NopInterceptor::new();
-            let mut executor = Executor::new(frame, ret_types.len(), pc);
-            loop {
-                let result = executor.execute_step(store, &interceptor);
-                match result {
-                    Ok(Signal::Next) => continue,
-                    Ok(Signal::Breakpoint) => continue,
-                    Ok(Signal::End) => match executor.pop_result(ret_types.to_vec()) {
-                        Ok(values) => return Ok(values),
-                        Err(err) => return Err(WasmError::ReturnValueError(err)),
-                    },
-                    Err(err) => return Err(WasmError::ExecutionError(err)),
-                }
-            }
+    let interceptor = NopInterceptor::new();
+    let mut execution = Execution::new(func_addr, arguments, store)?;
+    loop {
+        match execution.resume(store, &interceptor)? {
+            StopReason::Finish(values) => return Ok(values),
+            StopReason::Exit(code) => return Err(WasmError::Exit(code)),
+            _ => continue,
         }
     }
 }
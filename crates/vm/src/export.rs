@@ -1,5 +1,5 @@
 use super::address::*;
-use super::module::ModuleIndex;
+use super::module::{ExportEntry, ModuleIndex};
 
 pub struct ExportInstance {
     name: String,
@@ -15,10 +15,10 @@ impl ExportInstance {
         &self.value
     }
 
-    pub fn new_from_entry(entry: wasmparser::Export, module_index: ModuleIndex) -> Self {
+    pub fn new_from_entry(entry: &ExportEntry, module_index: ModuleIndex) -> Self {
         use wasmparser::ExternalKind;
         Self {
-            name: entry.field.to_string(),
+            name: entry.field.clone(),
             value: match entry.kind {
                 ExternalKind::Function => {
                     let addr = FuncAddr::new_unsafe(module_index, entry.index as usize);
@@ -1,9 +1,13 @@
+use super::executor::Trap;
 use super::host::HostFuncBody;
 use super::inst::*;
 use super::module::*;
-use super::value::Value;
+use super::value::{self, Value};
 use anyhow::Result;
+use std::collections::HashMap;
 use std::iter;
+use std::ops::Range;
+use std::sync::{Arc, OnceLock};
 use wasmparser::{FuncType, FunctionBody, Type};
 
 #[derive(Clone, Copy, Debug)]
@@ -43,22 +47,28 @@ impl FunctionInstance {
     }
 }
 
-pub struct DefinedFunctionInstance {
-    name: String,
-    ty: FuncType,
-    module_index: ModuleIndex,
+/// Body of a defined function shared between instances
+///
+/// Only the locals are read at load time. The body stays as a range of the
+/// code section until first use, when `Instruction`s are built and cached,
+/// so loading a module with many functions stays cheap. Malformed bodies
+/// are reported by validation, or when they are called without it.
+pub struct FunctionCode {
     locals: Vec<Type>,
-    instructions: Vec<Instruction>,
-    // cache
-    pub cached_local_inits: Vec<Value>,
+    section: Arc<[u8]>,
+    section_offset: usize,
+    body: Range<usize>,
+    base_offset: usize,
+    instructions: OnceLock<Result<Vec<Instruction>, String>>,
 }
 
-impl DefinedFunctionInstance {
+impl FunctionCode {
+    /// `section` holds the bytes of the code section starting at
+    /// `section_offset` in the module, and `body` is one of its entries
     pub fn new(
-        name: String,
-        ty: FuncType,
-        module_index: ModuleIndex,
-        body: FunctionBody,
+        body: &FunctionBody,
+        section: Arc<[u8]>,
+        section_offset: usize,
         base_offset: usize,
     ) -> Result<Self> {
         let mut locals = Vec::new();
@@ -68,37 +78,94 @@ impl DefinedFunctionInstance {
             let elements = iter::repeat(value_type).take(count as usize);
             locals.append(&mut elements.collect());
         }
+        let range = body.get_binary_reader().range();
+        Ok(Self {
+            locals,
+            section,
+            section_offset,
+            body: range.start - section_offset..range.end - section_offset,
+            base_offset,
+            instructions: OnceLock::new(),
+        })
+    }
+
+    fn decode(&self) -> Result<Vec<Instruction>> {
+        let body = FunctionBody::new(
+            self.section_offset + self.body.start,
+            &self.section[self.body.clone()],
+        );
         let mut reader = body.get_operators_reader()?;
         let mut instructions = Vec::new();
         while !reader.eof() {
-            let inst = transform_inst(&mut reader, base_offset)?;
+            let inst = transform_inst(&mut reader, self.base_offset)?;
             instructions.push(inst);
         }
+        Ok(instructions)
+    }
+
+    pub fn locals(&self) -> &[Type] {
+        &self.locals
+    }
+
+    /// Decoded instructions. Decoding happens only once.
+    pub fn instructions(&self) -> Result<&[Instruction], &str> {
+        match self
+            .instructions
+            .get_or_init(|| self.decode().map_err(|e| e.to_string()))
+        {
+            Ok(insts) => Ok(insts),
+            Err(err) => Err(err),
+        }
+    }
 
+    pub fn is_decoded(&self) -> bool {
+        self.instructions.get().is_some()
+    }
+}
+
+pub struct DefinedFunctionInstance {
+    name: String,
+    ty: FuncType,
+    module_index: ModuleIndex,
+    code: Arc<FunctionCode>,
+    local_names: HashMap<u32, String>,
+    // cache
+    pub cached_local_inits: Vec<Value>,
+}
+
+impl DefinedFunctionInstance {
+    pub fn new(
+        name: String,
+        ty: FuncType,
+        module_index: ModuleIndex,
+        code: Arc<FunctionCode>,
+    ) -> Result<Self, value::Error> {
         let mut local_tys = ty.params.to_vec();
-        local_tys.append(&mut locals.to_vec());
+        local_tys.append(&mut code.locals().to_vec());
         let mut cached_local_inits = Vec::new();
         for ty in local_tys {
-            let v = match ty {
-                Type::I32 => Value::I32(0),
-                Type::I64 => Value::I64(0),
-                Type::F32 => Value::F32(0),
-                Type::F64 => Value::F64(0),
-                _ => unimplemented!(),
-            };
-            cached_local_inits.push(v);
+            cached_local_inits.push(Value::zero(ty)?);
         }
 
         Ok(Self {
             name,
             ty,
             module_index,
-            locals,
-            instructions,
+            code,
+            local_names: HashMap::new(),
             cached_local_inits,
         })
     }
 
+    pub(crate) fn set_local_names(&mut self, names: HashMap<u32, String>) {
+        self.local_names = names;
+    }
+
+    /// Name of a local including parameters, from the name section
+    pub fn local_name(&self, index: u32) -> Option<&String> {
+        self.local_names.get(&index)
+    }
+
     pub fn name(&self) -> &String {
         &self.name
     }
@@ -111,16 +178,21 @@ impl DefinedFunctionInstance {
         self.module_index
     }
 
-    pub fn instructions(&self) -> &[Instruction] {
-        &self.instructions
+    pub fn instructions(&self) -> Result<&[Instruction], Trap> {
+        self.code
+            .instructions()
+            .map_err(|err| Trap::InvalidFunctionBody {
+                func_name: self.name.clone(),
+                message: err.to_string(),
+            })
     }
 
-    pub fn locals(&self) -> &[Type] {
-        &self.locals
+    pub fn code(&self) -> &FunctionCode {
+        &self.code
     }
 
-    pub fn inst(&self, index: InstIndex) -> &Instruction {
-        &self.instructions[index.0 as usize]
+    pub fn locals(&self) -> &[Type] {
+        self.code.locals()
     }
 }
 
@@ -1,28 +1,32 @@
 use super::value::Value;
 
-use std::cell::RefCell;
-use std::rc::Rc;
-
 use super::address::MemoryAddr;
 use super::executor::Trap;
 use super::global::GlobalInstance;
 use super::memory::MemoryInstance;
 use super::module::ModuleIndex;
+use super::stack::ProgramCounter;
 use super::store::Store;
 use super::table::TableInstance;
+use log::warn;
 use wasmparser::FuncType;
 
-type Ref<T> = Rc<RefCell<T>>;
-
 pub struct HostContext<'a> {
+    /// Memory of the calling module, empty for functions made with
+    /// `HostFuncBody::without_memory`
     pub mem: &'a mut [u8],
+    /// The module which called the function
+    pub module_index: ModuleIndex,
+    /// The call instruction which called the function, or `None` if the
+    /// embedder called it directly
+    pub caller: Option<ProgramCounter>,
 }
 
 pub enum HostValue {
     Func(HostFuncBody),
-    Global(Rc<RefCell<GlobalInstance>>),
-    Mem(Ref<MemoryInstance>),
-    Table(Ref<TableInstance>),
+    Global(GlobalInstance),
+    Mem(MemoryInstance),
+    Table(TableInstance),
 }
 
 impl HostValue {
@@ -36,20 +40,38 @@ impl HostValue {
     }
 }
 
+/// Host functions must be `Send` to keep `Store` movable between threads
 pub struct HostFuncBody {
     ty: FuncType,
-    code: Box<dyn Fn(&[Value], &mut Vec<Value>, &mut HostContext, &Store) -> Result<(), Trap>>,
+    code:
+        Box<dyn Fn(&[Value], &mut Vec<Value>, &mut HostContext, &Store) -> Result<(), Trap> + Send>,
+    /// Whether the memory is borrowed for `HostContext::mem` during the call
+    borrows_memory: bool,
 }
 
 impl HostFuncBody {
     pub fn new<F>(ty: FuncType, code: F) -> Self
     where
         F: Fn(&[Value], &mut Vec<Value>, &mut HostContext, &Store) -> Result<(), Trap>,
-        F: 'static,
+        F: Send + 'static,
     {
         Self {
             ty,
             code: Box::new(code),
+            borrows_memory: true,
+        }
+    }
+
+    /// A function which leaves the memory in the store, so that it can
+    /// grow the memory through `Store::memory`
+    pub fn without_memory<F>(ty: FuncType, code: F) -> Self
+    where
+        F: Fn(&[Value], &mut Vec<Value>, &mut HostContext, &Store) -> Result<(), Trap>,
+        F: Send + 'static,
+    {
+        Self {
+            borrows_memory: false,
+            ..Self::new(ty, code)
         }
     }
 
@@ -59,16 +81,25 @@ impl HostFuncBody {
         results: &mut Vec<Value>,
         store: &Store,
         module_index: ModuleIndex,
+        caller: Option<ProgramCounter>,
     ) -> Result<(), Trap> {
-        if store.memory_count(module_index) > 0 {
+        if self.borrows_memory && store.memory_count(module_index) > 0 {
             let mem_addr = MemoryAddr::new_unsafe(module_index, 0);
             let mem = store.memory(mem_addr);
             let mem = &mut mem.borrow_mut();
             let raw_mem = mem.raw_data_mut();
-            let mut ctx = HostContext { mem: raw_mem };
+            let mut ctx = HostContext {
+                mem: raw_mem,
+                module_index,
+                caller,
+            };
             (self.code)(param, results, &mut ctx, store)
         } else {
-            let mut ctx = HostContext { mem: &mut vec![] };
+            let mut ctx = HostContext {
+                mem: &mut [],
+                module_index,
+                caller,
+            };
             (self.code)(param, results, &mut ctx, store)
         }
     }
@@ -76,4 +107,42 @@ impl HostFuncBody {
     pub fn ty(&self) -> &FuncType {
         &self.ty
     }
+
+    /// A function standing in for the unresolved import `module.field`
+    pub fn stub(ty: FuncType, module: String, field: String, kind: ImportStub) -> Self {
+        let returns = ty.returns.to_vec();
+        Self::new(ty, move |args, results, _, _| match kind {
+            ImportStub::Trap => Err(Trap::UnresolvedImport {
+                module: module.clone(),
+                field: field.clone(),
+            }),
+            ImportStub::Log => {
+                warn!("stub of {}.{} called with {:?}", module, field, args);
+                for ty in &returns {
+                    results.push(Value::zero(*ty).map_err(Trap::Value)?);
+                }
+                Ok(())
+            }
+        })
+    }
+}
+
+/// How to fill imports which can't be resolved while instantiating a module
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportStub {
+    /// Calling the import traps. Other kinds of imports are zero-initialized.
+    Trap,
+    /// Calling the import is logged as a warning and returns zero values
+    Log,
+}
+
+impl std::str::FromStr for ImportStub {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trap" => Ok(Self::Trap),
+            "log" => Ok(Self::Log),
+            _ => Err(format!("unknown import stub kind '{}'", s)),
+        }
+    }
 }
@@ -1,6 +1,6 @@
-use super::executor::{simple_invoke_func, WasmError};
+use super::executor::{simple_invoke_func, Trap, WasmError};
 use super::host::HostValue;
-use super::module::ModuleIndex;
+use super::module::{Module, ModuleIndex};
 use super::store::Store;
 use super::value::Value;
 use std::collections::HashMap;
@@ -8,6 +8,7 @@ use std::collections::HashMap;
 use anyhow::Result;
 use std::io::Read;
 
+/// Like `Store`, `WasmInstance` is `Send` but not `Sync`
 pub struct WasmInstance {
     pub store: Store,
 }
@@ -21,17 +22,34 @@ impl WasmInstance {
         let mut f = ::std::fs::File::open(module_filename)?;
         let mut buffer = Vec::new();
         f.read_to_end(&mut buffer)?;
-        let reader = wasmparser::ModuleReader::new(&buffer)?;
-        self.load_module_from_module(name, reader)
+        self.load_module_from_bytes(name, &buffer)
     }
 
+    pub fn load_module_from_bytes(
+        &mut self,
+        name: Option<String>,
+        bytes: &[u8],
+    ) -> Result<ModuleIndex> {
+        self.store.load_module(name, bytes)
+    }
+
+    /// Decode and instantiate a module without validation
     pub fn load_module_from_module(
         &mut self,
         name: Option<String>,
         reader: wasmparser::ModuleReader,
     ) -> Result<ModuleIndex> {
         let mut reader = reader;
-        self.store.load_module(name, &mut reader)
+        let module = Module::from_reader(&mut reader)?;
+        self.store.instantiate_module(name, &module)
+    }
+
+    pub fn instantiate_module(
+        &mut self,
+        name: Option<String>,
+        module: &Module,
+    ) -> Result<ModuleIndex> {
+        self.store.instantiate_module(name, module)
     }
 
     pub fn load_host_module(&mut self, name: String, module: HashMap<String, HostValue>) {
@@ -42,7 +60,7 @@ impl WasmInstance {
         self.store.register_name(name, module_index)
     }
 
-    pub fn add_embed_context<T: std::any::Any>(&mut self, ctx: T) {
+    pub fn add_embed_context<T: std::any::Any + Send>(&mut self, ctx: T) {
         self.store.add_embed_context(Box::new(ctx))
     }
 }
@@ -60,13 +78,23 @@ impl WasmInstance {
             .map(|g| g.borrow().value())
     }
 
+    /// Run `func_name` or the entry function of the module. If the program
+    /// exits instead of returning, even with status 0, the status is returned
+    /// as `WasmError::Exit`.
     pub fn run(
         &mut self,
         module_index: ModuleIndex,
         func_name: Option<String>,
         arguments: Vec<Value>,
     ) -> Result<Vec<Value>, WasmError> {
-        let module = self.store.module(module_index).defined().unwrap();
+        let module = self
+            .store
+            .module(module_index)
+            .ok_or(WasmError::ExecutionError(Trap::ModuleNotLoaded(
+                module_index,
+            )))?
+            .defined()
+            .unwrap();
         let func_addr = if let Some(func_name) = func_name {
             if let Some(Some(func_addr)) = module.exported_func(func_name.clone()).ok() {
                 func_addr
@@ -1,7 +1,25 @@
 use crate::executor::{ExecResult, Signal};
+use crate::module::ModuleIndex;
 
 pub trait Interceptor {
-    fn invoke_func(&self, name: &String) -> ExecResult<Signal>;
+    /// Called after entering a function defined in `module_index`
+    fn invoke_func(&self, name: &String, module_index: ModuleIndex) -> ExecResult<Signal>;
+
+    /// Called after a host function returns, with its results on the stack
+    fn invoke_host_func(&self, _module_name: &str, _field_name: &str) -> ExecResult<Signal> {
+        Ok(Signal::Next)
+    }
+
+    /// Called after `memory.grow` tried to grow a memory of `previous_pages`
+    /// pages by `delta` pages. `grown` is false if growing failed.
+    fn after_memory_grow(
+        &self,
+        _previous_pages: usize,
+        _delta: usize,
+        _grown: bool,
+    ) -> ExecResult<Signal> {
+        Ok(Signal::Next)
+    }
 }
 
 pub struct NopInterceptor {}
@@ -11,7 +29,7 @@ impl NopInterceptor {
     }
 }
 impl Interceptor for NopInterceptor {
-    fn invoke_func(&self, _name: &String) -> ExecResult<Signal> {
+    fn invoke_func(&self, _name: &String, _module_index: ModuleIndex) -> ExecResult<Signal> {
         Ok(Signal::Next)
     }
 }
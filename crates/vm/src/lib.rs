@@ -1,4 +1,6 @@
 mod address;
+mod dylink;
+mod execution;
 mod executor;
 mod export;
 mod func;
@@ -7,28 +9,41 @@ mod host;
 mod inst;
 mod instance;
 mod interceptor;
+mod limits;
 mod linker;
 mod memory;
+mod mmap;
 mod module;
+mod names;
+mod reflection;
 mod stack;
 mod store;
 mod table;
+mod validation;
 mod value;
 
 pub use self::address::*;
+pub use self::dylink::DylinkInfo;
+pub use self::execution::{Execution, StopReason};
 pub use self::executor::{simple_invoke_func, Executor, Signal};
-pub use self::executor::{Trap, WasmError};
+pub use self::executor::{ConstExprError, Trap, WasmError};
 pub use self::func::{FunctionInstance, InstIndex};
 pub use self::global::GlobalInstance as HostGlobal;
-pub use self::host::{HostContext, HostFuncBody, HostValue};
+pub use self::host::{HostContext, HostFuncBody, HostValue, ImportStub};
 pub use self::inst::{Instruction, InstructionKind};
 pub use self::instance::WasmInstance;
 pub use self::interceptor::{Interceptor, NopInterceptor};
+pub use self::limits::{LimitError, ResourceLimits};
 pub use self::memory::MemoryInstance as HostMemory;
-pub use self::module::ModuleIndex;
+pub use self::module::{Module, ModuleIndex};
+pub use self::names::NameSection;
+pub use self::reflection::{type_name, ExportType, ExternType, ImportType, ModuleInfo};
 pub use self::stack::{CallFrame, ProgramCounter};
-pub use self::store::Store;
+pub use self::store::{Store, StoreError, StoreUsage, UnresolvedImport};
 pub use self::table::TableInstance as HostTable;
+pub use self::validation::{
+    decode_validated, decode_validated_sections, validate, validate_sections, ValidationError,
+};
 pub use self::value::Value as WasmValue;
 
 pub const WASM_PAGE_SIZE: usize = 0x10000;
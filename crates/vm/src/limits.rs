@@ -0,0 +1,73 @@
+/// Limits on the resources a `Store` may allocate, to reproduce how
+/// runtimes with constrained memory behave. Unlimited by default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceLimits {
+    /// Total size of all memories in bytes
+    pub max_memory_bytes: Option<usize>,
+    /// Number of elements of each table. Unlike `max_memory_bytes`, this is
+    /// not a total over all tables.
+    pub max_table_elements: Option<usize>,
+    /// Number of instantiated modules. Host modules are not counted.
+    pub max_instances: Option<usize>,
+}
+
+#[derive(Debug)]
+pub enum LimitError {
+    MemoryBytes { requested: usize, limit: usize },
+    TableElements { requested: usize, limit: usize },
+    Instances { limit: usize },
+}
+
+impl std::error::Error for LimitError {}
+
+impl std::fmt::Display for LimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MemoryBytes { requested, limit } => write!(
+                f,
+                "memory limit exceeded, requested {} bytes in total but the limit is {}",
+                requested, limit
+            ),
+            Self::TableElements { requested, limit } => write!(
+                f,
+                "table limit exceeded, requested {} elements but the limit is {}",
+                requested, limit
+            ),
+            Self::Instances { limit } => {
+                write!(f, "instance limit exceeded, the limit is {}", limit)
+            }
+        }
+    }
+}
+
+impl ResourceLimits {
+    /// Check that memories can use `total` bytes
+    pub fn check_memory_bytes(&self, total: usize) -> Result<(), LimitError> {
+        match self.max_memory_bytes {
+            Some(limit) if total > limit => Err(LimitError::MemoryBytes {
+                requested: total,
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that a table can have `elements` elements
+    pub fn check_table_elements(&self, elements: usize) -> Result<(), LimitError> {
+        match self.max_table_elements {
+            Some(limit) if elements > limit => Err(LimitError::TableElements {
+                requested: elements,
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that one more module can be instantiated when `instances` are
+    pub fn check_new_instance(&self, instances: usize) -> Result<(), LimitError> {
+        match self.max_instances {
+            Some(limit) if instances >= limit => Err(LimitError::Instances { limit }),
+            _ => Ok(()),
+        }
+    }
+}
@@ -1,9 +1,11 @@
 use super::module::ModuleIndex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 
+// Addresses are plain indices, so they are always `Send` and `Sync`
+// regardless of the item type
 #[derive(PartialEq, Eq, Hash)]
-pub struct GlobalAddress<T>(usize, std::marker::PhantomData<T>);
+pub struct GlobalAddress<T>(usize, std::marker::PhantomData<fn() -> T>);
 
 impl<T> Clone for GlobalAddress<T> {
     fn clone(&self) -> Self {
@@ -20,7 +22,11 @@ impl<T> fmt::Debug for GlobalAddress<T> {
 }
 
 #[derive(PartialEq, Eq, Hash)]
-pub struct LinkableAddress<T>(ModuleIndex, pub(crate) usize, std::marker::PhantomData<T>);
+pub struct LinkableAddress<T>(
+    ModuleIndex,
+    pub(crate) usize,
+    std::marker::PhantomData<fn() -> T>,
+);
 
 impl<T> LinkableAddress<T> {
     pub fn new_unsafe(module: ModuleIndex, index: usize) -> Self {
@@ -30,6 +36,11 @@ impl<T> LinkableAddress<T> {
     pub fn module_index(&self) -> ModuleIndex {
         self.0
     }
+
+    /// Index in the index space of the module
+    pub fn index(&self) -> usize {
+        self.1
+    }
 }
 
 impl<T> Clone for LinkableAddress<T> {
@@ -47,7 +58,9 @@ impl<T> fmt::Debug for LinkableAddress<T> {
 }
 
 pub struct LinkableCollection<T> {
-    items: Vec<T>,
+    /// Slots of collected items are `None` until they are reused
+    items: Vec<Option<T>>,
+    free_slots: Vec<usize>,
     item_addrs_by_module: HashMap<ModuleIndex, Vec<usize>>,
 }
 
@@ -55,6 +68,7 @@ impl<T> LinkableCollection<T> {
     pub fn new() -> Self {
         Self {
             items: Vec::new(),
+            free_slots: Vec::new(),
             item_addrs_by_module: HashMap::new(),
         }
     }
@@ -77,25 +91,46 @@ impl<T> LinkableCollection<T> {
         LinkableAddress::new_unsafe(dist, index)
     }
 
+    /// Make `address` refer to `source` instead of the item it was linked to
+    pub fn relink(&mut self, source: GlobalAddress<T>, address: LinkableAddress<T>) -> Option<()> {
+        let raw_address = self
+            .item_addrs_by_module
+            .get_mut(&address.0)?
+            .get_mut(address.1)?;
+        *raw_address = source.0;
+        Some(())
+    }
+
     pub fn get_global(&self, address: GlobalAddress<T>) -> &T {
-        // Never panic because GlobalAddress is always valid
-        self.items.get(address.0).unwrap()
+        // Never panic because GlobalAddress is valid until it's collected
+        self.items.get(address.0).and_then(Option::as_ref).unwrap()
     }
 
     pub fn get(&self, address: LinkableAddress<T>) -> Option<(&T, GlobalAddress<T>)> {
         let addr = self.resolve(address)?;
-        Some((self.items.get(addr.0)?, addr))
+        Some((self.items.get(addr.0)?.as_ref()?, addr))
+    }
+
+    fn push_item(&mut self, item: T) -> usize {
+        match self.free_slots.pop() {
+            Some(index) => {
+                self.items[index] = Some(item);
+                index
+            }
+            None => {
+                self.items.push(Some(item));
+                self.items.len() - 1
+            }
+        }
     }
 
     pub fn push_global(&mut self, item: T) -> GlobalAddress<T> {
-        let index = self.items.len();
-        self.items.push(item);
+        let index = self.push_item(item);
         GlobalAddress(index, std::marker::PhantomData)
     }
 
     pub fn push(&mut self, module_index: ModuleIndex, item: T) -> LinkableAddress<T> {
-        let globa_index = self.items.len();
-        self.items.push(item);
+        let globa_index = self.push_item(item);
         let addrs = self
             .item_addrs_by_module
             .entry(module_index)
@@ -105,11 +140,45 @@ impl<T> LinkableCollection<T> {
         LinkableAddress::new_unsafe(module_index, index)
     }
 
+    /// Unlink all items from a module. Items are kept until `collect`.
     pub fn remove_module(&mut self, index: &ModuleIndex) {
-        // TODO: GC unlinked items
         self.item_addrs_by_module.remove(index);
     }
 
+    /// Modules which have items linked
+    pub fn modules(&self) -> impl Iterator<Item = ModuleIndex> + '_ {
+        self.item_addrs_by_module.keys().cloned()
+    }
+
+    /// Drop items which are neither linked to any module nor in `roots`,
+    /// and return how many were dropped. Their slots are reused by later
+    /// pushes, so addresses of dropped items must not be used anymore.
+    pub fn collect(&mut self, roots: impl Iterator<Item = GlobalAddress<T>>) -> usize {
+        let mut live: HashSet<usize> = roots.map(|addr| addr.0).collect();
+        for addrs in self.item_addrs_by_module.values() {
+            live.extend(addrs.iter().cloned());
+        }
+        let mut count = 0;
+        for (index, item) in self.items.iter_mut().enumerate() {
+            if item.is_some() && !live.contains(&index) {
+                *item = None;
+                self.free_slots.push(index);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// All items including ones not linked to any module
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter().flatten()
+    }
+
+    /// Number of items which are not collected yet
+    pub fn count(&self) -> usize {
+        self.items.len() - self.free_slots.len()
+    }
+
     pub fn items(&self, module_index: ModuleIndex) -> Option<Vec<GlobalAddress<T>>> {
         let item_addrs = self.item_addrs_by_module.get(&module_index)?;
         Some(
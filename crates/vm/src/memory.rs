@@ -1,8 +1,12 @@
+use super::mmap::Mmap;
 use super::value::FromLittleEndian;
 use super::WASM_PAGE_SIZE;
+use wasmparser::{MemoryType, ResizableLimits};
+
+const MAX_PAGES: usize = 65536;
 
 pub struct MemoryInstance {
-    data: Vec<u8>,
+    data: Mmap,
     pub max: Option<usize>,
     pub initial: usize,
 }
@@ -11,6 +15,7 @@ pub struct MemoryInstance {
 pub enum Error {
     GrowOverMaximumSize(usize),
     GrowOverMaximumPageSize(usize),
+    FailedToAllocate(std::io::Error),
     AccessOutOfBounds(
         /* try to access */ Option<usize>,
         /* memory size */ usize,
@@ -30,6 +35,7 @@ impl std::fmt::Display for Error {
                 "out of bounds memory access, try to access over size of usize but size of memory is {}",
                 size
             ),
+            Self::FailedToAllocate(err) => write!(f, "failed to allocate memory: {}", err),
             _ => write!(f, "{:?}", self),
         }
     }
@@ -38,14 +44,17 @@ impl std::fmt::Display for Error {
 type Result<T> = std::result::Result<T, Error>;
 
 impl MemoryInstance {
-    pub fn new(initial: usize, maximum: Option<usize>) -> Self {
-        Self {
-            data: std::iter::repeat(0)
-                .take(initial * WASM_PAGE_SIZE)
-                .collect(),
+    pub fn new(initial: usize, maximum: Option<usize>) -> Result<Self> {
+        // Reserve enough to grow up to the maximum without moving memory
+        let max_pages = std::cmp::min(maximum.unwrap_or(MAX_PAGES), MAX_PAGES);
+        let reserved = max_pages.checked_mul(WASM_PAGE_SIZE).unwrap_or(0);
+        let data =
+            Mmap::new(initial * WASM_PAGE_SIZE, reserved).map_err(Error::FailedToAllocate)?;
+        Ok(Self {
+            data,
             initial,
             max: maximum,
-        }
+        })
     }
 
     pub fn validate_region(&self, offset: usize, size: usize) -> Result<()> {
@@ -61,28 +70,37 @@ impl MemoryInstance {
 
     pub fn store(&mut self, offset: usize, data: &[u8]) -> Result<()> {
         self.validate_region(offset, data.len())?;
-        for (index, byte) in data.into_iter().enumerate() {
-            self.data[offset + index] = *byte;
-        }
+        self.data.as_mut_slice()[offset..offset + data.len()].copy_from_slice(data);
         Ok(())
     }
     pub fn data_len(&self) -> usize {
-        self.data.len()
+        self.data.as_slice().len()
     }
 
     pub fn load_as<T: FromLittleEndian>(&self, offset: usize) -> Result<T> {
         self.validate_region(offset, std::mem::size_of::<T>())?;
-        let buf = &self.data[offset..offset + std::mem::size_of::<T>()];
+        let buf = &self.data.as_slice()[offset..offset + std::mem::size_of::<T>()];
         Ok(T::from_le(buf))
     }
 
+    /// Type with the current size as the minimum
+    pub fn ty(&self) -> MemoryType {
+        MemoryType {
+            limits: ResizableLimits {
+                initial: self.page_count() as u32,
+                maximum: self.max.map(|max| max as u32),
+            },
+            shared: false,
+        }
+    }
+
     pub fn page_count(&self) -> usize {
         self.data_len() / WASM_PAGE_SIZE
     }
 
     pub fn grow(&mut self, n: usize) -> Result<()> {
         let len = self.page_count() + n;
-        if len > 65536 {
+        if len > MAX_PAGES {
             return Err(Error::GrowOverMaximumPageSize(len));
         }
 
@@ -91,15 +109,15 @@ impl MemoryInstance {
                 return Err(Error::GrowOverMaximumSize(max));
             }
         }
-        let mut extra: Vec<u8> = std::iter::repeat(0).take(n * WASM_PAGE_SIZE).collect();
-        self.data.append(&mut extra);
-        return Ok(());
+        self.data
+            .grow(len * WASM_PAGE_SIZE)
+            .map_err(Error::FailedToAllocate)
     }
     pub fn raw_data_mut(&mut self) -> &mut [u8] {
-        &mut self.data
+        self.data.as_mut_slice()
     }
 
     pub fn raw_data(&self) -> &[u8] {
-        &self.data
+        self.data.as_slice()
     }
 }
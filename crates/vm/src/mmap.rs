@@ -0,0 +1,138 @@
+/// Zero-initialized, contiguous storage for a linear memory.
+///
+/// Address space for the maximum size is reserved up front and only the
+/// accessible part is made readable and writable. The OS commits pages when
+/// they are first touched, so neither allocating nor growing the storage
+/// zeroes or copies memory.
+#[cfg(unix)]
+pub(crate) struct Mmap {
+    ptr: *mut u8,
+    /// Accessible bytes from `ptr`
+    len: usize,
+    /// Reserved bytes from `ptr`
+    reserved: usize,
+}
+
+// The mapping is owned exclusively, like the buffer of a `Vec`
+#[cfg(unix)]
+unsafe impl Send for Mmap {}
+
+#[cfg(unix)]
+impl Mmap {
+    /// Make `len` bytes accessible, reserving `reserved` bytes if possible
+    pub fn new(len: usize, reserved: usize) -> std::io::Result<Self> {
+        let mut mmap =
+            Self::reserve(std::cmp::max(len, reserved)).or_else(|_| Self::reserve(len))?;
+        mmap.grow(len)?;
+        Ok(mmap)
+    }
+
+    fn reserve(reserved: usize) -> std::io::Result<Self> {
+        if reserved == 0 {
+            return Ok(Self {
+                ptr: std::ptr::NonNull::dangling().as_ptr(),
+                len: 0,
+                reserved: 0,
+            });
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                reserved,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANON,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr: ptr as *mut u8,
+            len: 0,
+            reserved,
+        })
+    }
+
+    /// Make the first `len` bytes accessible. `len` must be a multiple of
+    /// the OS page size.
+    pub fn grow(&mut self, len: usize) -> std::io::Result<()> {
+        if len <= self.len {
+            return Ok(());
+        }
+        if len > self.reserved {
+            // Out of reserved address space, so move to a larger mapping
+            let mut mmap = Self::reserve(std::cmp::max(len, self.reserved.saturating_mul(2)))?;
+            mmap.grow(len)?;
+            mmap.as_mut_slice()[..self.len].copy_from_slice(self.as_slice());
+            *self = mmap;
+            return Ok(());
+        }
+        let result = unsafe {
+            libc::mprotect(
+                self.ptr.add(self.len) as *mut libc::c_void,
+                len - self.len,
+                libc::PROT_READ | libc::PROT_WRITE,
+            )
+        };
+        if result != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        self.len = len;
+        Ok(())
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.reserved > 0 {
+            unsafe {
+                libc::munmap(self.ptr as *mut libc::c_void, self.reserved);
+            }
+        }
+    }
+}
+
+/// Heap storage for platforms without `mmap`. Allocation failures are
+/// reported instead of aborting, at the cost of zeroing new bytes eagerly.
+#[cfg(not(unix))]
+pub(crate) struct Mmap {
+    data: Vec<u8>,
+}
+
+#[cfg(not(unix))]
+impl Mmap {
+    pub fn new(len: usize, _reserved: usize) -> std::io::Result<Self> {
+        let mut mmap = Self { data: Vec::new() };
+        mmap.grow(len)?;
+        Ok(mmap)
+    }
+
+    pub fn grow(&mut self, len: usize) -> std::io::Result<()> {
+        if len > self.data.len() {
+            self.data
+                .try_reserve_exact(len - self.data.len())
+                .map_err(|_| std::io::Error::from(std::io::ErrorKind::OutOfMemory))?;
+            self.data.resize(len, 0);
+        }
+        Ok(())
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
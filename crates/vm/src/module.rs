@@ -1,12 +1,345 @@
 use super::address::*;
+use super::dylink::DylinkInfo;
 use super::export::{ExportInstance, ExternalValue};
+use super::func::FunctionCode;
+use super::inst::{transform_inst, Instruction};
+use super::names::NameSection;
+use super::reflection::ImportType;
 
+use anyhow::Result;
 use std::collections::HashMap;
 use std::hash::Hash;
+use std::sync::Arc;
+use wasmparser::{
+    DataKind, ElementItem, ElementKind, ExternalKind, FuncType, GlobalType, ImportSectionEntryType,
+    InitExpr, MemoryType, ModuleReader, SectionCode, TableType,
+};
 
 #[derive(Copy, Clone, Hash, PartialEq, Eq, Debug)]
 pub struct ModuleIndex(pub u32);
 
+/// A decoded module independent of any `Store`
+///
+/// Decoding a binary once and instantiating the `Module` many times avoids
+/// reparsing the same bytes for every instantiation. `Module` is immutable
+/// after decoding, so it's both `Send` and `Sync`.
+pub struct Module {
+    types: Vec<FuncType>,
+    imports: Vec<ImportEntry>,
+    exports: Vec<ExportEntry>,
+    funcs: Vec<FunctionEntry>,
+    tables: Vec<TableType>,
+    mems: Vec<MemoryType>,
+    globals: Vec<GlobalEntry>,
+    elem_segments: Vec<ElementSegment>,
+    data_segments: Vec<DataSegment>,
+    start_func: Option<u32>,
+    names: NameSection,
+    dylink: Option<DylinkInfo>,
+}
+
+pub struct ImportEntry {
+    pub module: String,
+    pub field: String,
+    pub ty: ImportSectionEntryType,
+}
+
+pub struct ExportEntry {
+    pub field: String,
+    pub kind: ExternalKind,
+    pub index: u32,
+}
+
+pub struct FunctionEntry {
+    pub type_index: u32,
+    pub code: Arc<FunctionCode>,
+}
+
+pub struct GlobalEntry {
+    pub ty: GlobalType,
+    pub init_expr: Vec<Instruction>,
+}
+
+/// Mode of element and data segments.
+/// `index` is a table index for element segments and a memory index for
+/// data segments.
+pub enum SegmentKind {
+    Passive,
+    Active {
+        index: u32,
+        offset: Vec<Instruction>,
+    },
+    Declared,
+}
+
+pub struct ElementSegment {
+    pub kind: SegmentKind,
+    pub items: Vec<Option<u32>>,
+}
+
+pub struct DataSegment {
+    pub kind: SegmentKind,
+    pub data: Vec<u8>,
+}
+
+pub(crate) fn decode_init_expr(init_expr: &InitExpr) -> Result<Vec<Instruction>> {
+    let mut reader = init_expr.get_operators_reader();
+    let base_offset = reader.original_position();
+    let mut insts = Vec::new();
+    while !reader.eof() {
+        insts.push(transform_inst(&mut reader, base_offset)?);
+    }
+    Ok(insts)
+}
+
+impl Module {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = ModuleReader::new(bytes)?;
+        Self::from_reader(&mut reader)
+    }
+
+    pub fn from_reader(reader: &mut ModuleReader) -> Result<Self> {
+        let mut types = Vec::new();
+        let mut elem_segments = Vec::new();
+        let mut data_segments = Vec::new();
+        let mut func_sigs = Vec::new();
+        let mut imports = Vec::new();
+        let mut exports = Vec::new();
+        let mut funcs = Vec::new();
+        let mut tables = Vec::new();
+        let mut globals = Vec::new();
+        let mut mems = Vec::new();
+        let mut names = NameSection::default();
+
+        let mut start_func = None;
+        let mut dylink = None;
+
+        while !reader.eof() {
+            let offset = reader.current_position();
+            let section = reader.read()?;
+            match section.code {
+                SectionCode::Type => {
+                    let section = section.get_type_section_reader()?;
+                    types.reserve_exact(section.get_count() as usize);
+                    for entry in section {
+                        types.push(entry?);
+                    }
+                }
+                SectionCode::Element => {
+                    let section = section.get_element_section_reader()?;
+                    elem_segments.reserve_exact(section.get_count() as usize);
+                    for entry in section {
+                        let entry = entry?;
+                        let kind = match entry.kind {
+                            ElementKind::Passive => SegmentKind::Passive,
+                            ElementKind::Declared => SegmentKind::Declared,
+                            ElementKind::Active {
+                                table_index,
+                                init_expr,
+                            } => SegmentKind::Active {
+                                index: table_index,
+                                offset: decode_init_expr(&init_expr)?,
+                            },
+                        };
+                        let items = entry
+                            .items
+                            .get_items_reader()?
+                            .into_iter()
+                            .map(|item| match item? {
+                                ElementItem::Func(index) => Ok(Some(index)),
+                                ElementItem::Null => Ok(None),
+                            })
+                            .collect::<Result<Vec<Option<u32>>>>()?;
+                        elem_segments.push(ElementSegment { kind, items });
+                    }
+                }
+                SectionCode::Data => {
+                    let section = section.get_data_section_reader()?;
+                    data_segments.reserve_exact(section.get_count() as usize);
+                    for entry in section {
+                        let entry = entry?;
+                        let kind = match entry.kind {
+                            DataKind::Passive => SegmentKind::Passive,
+                            DataKind::Active {
+                                memory_index,
+                                init_expr,
+                            } => SegmentKind::Active {
+                                index: memory_index,
+                                offset: decode_init_expr(&init_expr)?,
+                            },
+                        };
+                        data_segments.push(DataSegment {
+                            kind,
+                            data: entry.data.to_vec(),
+                        });
+                    }
+                }
+                SectionCode::Import => {
+                    let section = section.get_import_section_reader()?;
+                    imports.reserve_exact(section.get_count() as usize);
+                    for entry in section {
+                        let entry = entry?;
+                        imports.push(ImportEntry {
+                            module: entry.module.to_string(),
+                            field: entry.field.to_string(),
+                            ty: entry.ty,
+                        });
+                    }
+                }
+                SectionCode::Export => {
+                    let section = section.get_export_section_reader()?;
+                    exports.reserve_exact(section.get_count() as usize);
+                    for entry in section {
+                        let entry = entry?;
+                        exports.push(ExportEntry {
+                            field: entry.field.to_string(),
+                            kind: entry.kind,
+                            index: entry.index,
+                        });
+                    }
+                }
+                SectionCode::Function => {
+                    let section = section.get_function_section_reader()?;
+                    func_sigs.reserve_exact(section.get_count() as usize);
+                    for entry in section {
+                        func_sigs.push(entry?);
+                    }
+                }
+                SectionCode::Code => {
+                    let mut section_reader = section.get_binary_reader();
+                    let section_offset = section_reader.original_position();
+                    let bytes: Arc<[u8]> = section_reader
+                        .read_bytes(section_reader.bytes_remaining())?
+                        .into();
+                    let section = section.get_code_section_reader()?;
+                    funcs.reserve_exact(section.get_count() as usize);
+                    for (entry, type_index) in section.into_iter().zip(func_sigs.iter()) {
+                        let code =
+                            FunctionCode::new(&entry?, bytes.clone(), section_offset, offset)?;
+                        funcs.push(FunctionEntry {
+                            type_index: *type_index,
+                            code: Arc::new(code),
+                        });
+                    }
+                }
+                SectionCode::Table => {
+                    let section = section.get_table_section_reader()?;
+                    tables.reserve_exact(section.get_count() as usize);
+                    for entry in section {
+                        tables.push(entry?);
+                    }
+                }
+                SectionCode::Memory => {
+                    let section = section.get_memory_section_reader()?;
+                    mems.reserve_exact(section.get_count() as usize);
+                    for entry in section {
+                        mems.push(entry?);
+                    }
+                }
+                SectionCode::Global => {
+                    let section = section.get_global_section_reader()?;
+                    globals.reserve_exact(section.get_count() as usize);
+                    for entry in section {
+                        let entry = entry?;
+                        globals.push(GlobalEntry {
+                            ty: entry.ty,
+                            init_expr: decode_init_expr(&entry.init_expr)?,
+                        });
+                    }
+                }
+                SectionCode::Start => {
+                    start_func = Some(section.get_start_section_content()?);
+                }
+                SectionCode::Custom { name, kind } => {
+                    use wasmparser::CustomSectionKind;
+                    match (kind, name) {
+                        (CustomSectionKind::Name, _) => {
+                            names = NameSection::read(section.get_binary_reader())?;
+                        }
+                        (_, "dylink.0") => {
+                            dylink = Some(DylinkInfo::read(section.get_binary_reader())?);
+                        }
+                        (_, "dylink") => {
+                            dylink = Some(DylinkInfo::read_legacy(section.get_binary_reader())?);
+                        }
+                        _ => (),
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Ok(Self {
+            types,
+            imports,
+            exports,
+            funcs,
+            tables,
+            mems,
+            globals,
+            elem_segments,
+            data_segments,
+            start_func,
+            names,
+            dylink,
+        })
+    }
+
+    pub fn types(&self) -> &[FuncType] {
+        &self.types
+    }
+
+    pub fn imports(&self) -> &[ImportEntry] {
+        &self.imports
+    }
+
+    pub fn exports(&self) -> &[ExportEntry] {
+        &self.exports
+    }
+
+    pub fn funcs(&self) -> &[FunctionEntry] {
+        &self.funcs
+    }
+
+    pub fn tables(&self) -> &[TableType] {
+        &self.tables
+    }
+
+    pub fn mems(&self) -> &[MemoryType] {
+        &self.mems
+    }
+
+    pub fn globals(&self) -> &[GlobalEntry] {
+        &self.globals
+    }
+
+    pub fn elem_segments(&self) -> &[ElementSegment] {
+        &self.elem_segments
+    }
+
+    pub fn data_segments(&self) -> &[DataSegment] {
+        &self.data_segments
+    }
+
+    pub fn start_func(&self) -> Option<u32> {
+        self.start_func
+    }
+
+    /// Dynamic linking info if this is a shared library
+    pub fn dylink(&self) -> Option<&DylinkInfo> {
+        self.dylink.as_ref()
+    }
+
+    pub fn func_name(&self, index: u32) -> Option<&String> {
+        self.names.funcs.get(&index)
+    }
+
+    /// Names from the name section
+    pub fn names(&self) -> &NameSection {
+        &self.names
+    }
+}
+
 pub enum ModuleInstance {
     Defined(DefinedModuleInstance),
     Host(HostModuleInstance),
@@ -23,6 +356,7 @@ impl ModuleInstance {
 
 pub struct DefinedModuleInstance {
     types: Vec<wasmparser::FuncType>,
+    imports: Vec<ImportType>,
     pub exports: Vec<ExportInstance>,
     start_func: Option<FuncAddr>,
 }
@@ -50,14 +384,16 @@ impl DefinedModuleInstance {
     pub fn new_from_module(
         module_index: ModuleIndex,
         types: Vec<wasmparser::FuncType>,
-        exports: Vec<wasmparser::Export>,
+        imports: Vec<ImportType>,
+        exports: &[ExportEntry],
         start_func: Option<FuncAddr>,
     ) -> Self {
         Self {
             types,
+            imports,
             exports: exports
                 .iter()
-                .map(|e| ExportInstance::new_from_entry(e.clone(), module_index))
+                .map(|e| ExportInstance::new_from_entry(e, module_index))
                 .collect(),
             start_func: start_func,
         }
@@ -130,6 +466,10 @@ impl DefinedModuleInstance {
     pub fn get_type(&self, index: usize) -> &wasmparser::FuncType {
         &self.types[index]
     }
+
+    pub fn imports(&self) -> &[ImportType] {
+        &self.imports
+    }
 }
 
 pub struct HostModuleInstance {
@@ -178,6 +518,14 @@ impl HostModuleInstance {
         Self { values }
     }
 
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &HostExport)> {
+        self.values.iter()
+    }
+
     pub fn global_by_name(&self, name: String) -> HostModuleResult<Option<&ResolvedGlobalAddr>> {
         match &self.values.get(&name) {
             Some(HostExport::Global(global)) => Ok(Some(global)),
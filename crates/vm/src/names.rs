@@ -0,0 +1,92 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use wasmparser::BinaryReader;
+
+/// Names from the `name` custom section, including the subsections added by
+/// the extended name section proposal
+#[derive(Debug, Clone, Default)]
+pub struct NameSection {
+    pub module: Option<String>,
+    pub funcs: HashMap<u32, String>,
+    /// Local names by function index
+    pub locals: HashMap<u32, HashMap<u32, String>>,
+    /// Label names by function index
+    pub labels: HashMap<u32, HashMap<u32, String>>,
+    pub types: HashMap<u32, String>,
+    pub tables: HashMap<u32, String>,
+    pub memories: HashMap<u32, String>,
+    pub globals: HashMap<u32, String>,
+    pub elem_segments: HashMap<u32, String>,
+    pub data_segments: HashMap<u32, String>,
+}
+
+const NAME_MODULE: u32 = 0;
+const NAME_FUNCTION: u32 = 1;
+const NAME_LOCAL: u32 = 2;
+const NAME_LABEL: u32 = 3;
+const NAME_TYPE: u32 = 4;
+const NAME_TABLE: u32 = 5;
+const NAME_MEMORY: u32 = 6;
+const NAME_GLOBAL: u32 = 7;
+const NAME_ELEM: u32 = 8;
+const NAME_DATA: u32 = 9;
+
+impl NameSection {
+    /// Read the payload of the `name` section. Unknown subsections are
+    /// skipped.
+    pub(crate) fn read(mut reader: BinaryReader) -> Result<Self> {
+        let mut names = Self::default();
+        while !reader.eof() {
+            let kind = reader.read_u8()?;
+            let size = reader.read_var_u32()? as usize;
+            let offset = reader.original_position();
+            let mut payload = BinaryReader::new_with_offset(reader.read_bytes(size)?, offset);
+            match kind {
+                NAME_MODULE => names.module = Some(payload.read_string()?.to_string()),
+                NAME_FUNCTION => names.funcs = read_name_map(&mut payload)?,
+                NAME_LOCAL => names.locals = read_indirect_name_map(&mut payload)?,
+                NAME_LABEL => names.labels = read_indirect_name_map(&mut payload)?,
+                NAME_TYPE => names.types = read_name_map(&mut payload)?,
+                NAME_TABLE => names.tables = read_name_map(&mut payload)?,
+                NAME_MEMORY => names.memories = read_name_map(&mut payload)?,
+                NAME_GLOBAL => names.globals = read_name_map(&mut payload)?,
+                NAME_ELEM => names.elem_segments = read_name_map(&mut payload)?,
+                NAME_DATA => names.data_segments = read_name_map(&mut payload)?,
+                _ => (),
+            }
+        }
+        Ok(names)
+    }
+
+    pub fn local_name(&self, func_index: u32, local_index: u32) -> Option<&String> {
+        self.locals.get(&func_index)?.get(&local_index)
+    }
+
+    /// Find a global by its name
+    pub fn global_index(&self, name: &str) -> Option<u32> {
+        self.globals
+            .iter()
+            .find(|(_, global)| global.as_str() == name)
+            .map(|(index, _)| *index)
+    }
+}
+
+fn read_name_map(reader: &mut BinaryReader) -> Result<HashMap<u32, String>> {
+    let count = reader.read_var_u32()?;
+    let mut names = HashMap::new();
+    for _ in 0..count {
+        let index = reader.read_var_u32()?;
+        names.insert(index, reader.read_string()?.to_string());
+    }
+    Ok(names)
+}
+
+fn read_indirect_name_map(reader: &mut BinaryReader) -> Result<HashMap<u32, HashMap<u32, String>>> {
+    let count = reader.read_var_u32()?;
+    let mut names = HashMap::new();
+    for _ in 0..count {
+        let index = reader.read_var_u32()?;
+        names.insert(index, read_name_map(reader)?);
+    }
+    Ok(names)
+}
@@ -0,0 +1,98 @@
+use super::module::ModuleIndex;
+use wasmparser::{FuncType, GlobalType, MemoryType, ResizableLimits, TableType, Type};
+
+/// Type of an import or an export
+#[derive(Debug, Clone)]
+pub enum ExternType {
+    Func(FuncType),
+    Table(TableType),
+    Memory(MemoryType),
+    Global(GlobalType),
+}
+
+impl std::fmt::Display for ExternType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Func(ty) => {
+                write!(f, "func (")?;
+                write_types(f, &ty.params)?;
+                write!(f, ") -> (")?;
+                write_types(f, &ty.returns)?;
+                write!(f, ")")
+            }
+            Self::Table(ty) => {
+                write!(f, "table ")?;
+                write_limits(f, &ty.limits)?;
+                write!(f, " {}", type_name(ty.element_type))
+            }
+            Self::Memory(ty) => {
+                write!(f, "memory ")?;
+                write_limits(f, &ty.limits)
+            }
+            Self::Global(ty) if ty.mutable => {
+                write!(f, "global mut {}", type_name(ty.content_type))
+            }
+            Self::Global(ty) => write!(f, "global {}", type_name(ty.content_type)),
+        }
+    }
+}
+
+/// Name of a value type in the text format
+pub fn type_name(ty: Type) -> &'static str {
+    match ty {
+        Type::I32 => "i32",
+        Type::I64 => "i64",
+        Type::F32 => "f32",
+        Type::F64 => "f64",
+        Type::V128 => "v128",
+        Type::AnyFunc => "funcref",
+        Type::AnyRef => "anyref",
+        Type::NullRef => "nullref",
+        Type::Func => "func",
+        Type::EmptyBlockType => "",
+    }
+}
+
+fn write_types(f: &mut std::fmt::Formatter<'_>, types: &[Type]) -> std::fmt::Result {
+    for (index, ty) in types.iter().enumerate() {
+        if index > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", type_name(*ty))?;
+    }
+    Ok(())
+}
+
+fn write_limits(f: &mut std::fmt::Formatter<'_>, limits: &ResizableLimits) -> std::fmt::Result {
+    match limits.maximum {
+        Some(maximum) => write!(f, "{}..{}", limits.initial, maximum),
+        None => write!(f, "{}..", limits.initial),
+    }
+}
+
+/// An import of a module, returned by `Store::imports`
+#[derive(Debug, Clone)]
+pub struct ImportType {
+    pub module: String,
+    pub field: String,
+    pub ty: ExternType,
+}
+
+/// An export of a module, returned by `Store::exports`
+#[derive(Debug, Clone)]
+pub struct ExportType {
+    pub name: String,
+    pub ty: ExternType,
+    /// Index of the exported item in the index space of its kind. Items
+    /// exported by host modules have no index.
+    pub index: Option<usize>,
+}
+
+/// A module loaded in a `Store`, returned by `Store::modules`
+#[derive(Debug, Clone)]
+pub struct ModuleInfo {
+    pub index: ModuleIndex,
+    /// Name the module is registered with, if any
+    pub name: Option<String>,
+    pub is_host: bool,
+}
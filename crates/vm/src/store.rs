@@ -1,34 +1,54 @@
 use super::address::*;
-use super::executor::eval_const_expr;
+use super::dylink::{self, DylinkBases, DylinkInfo, DylinkState, GotEntry};
+use super::executor::{eval_const_expr, simple_invoke_func, ConstExprError, WasmError};
+use super::export::ExternalValue;
 use super::func::{eq_func_type, DefinedFunctionInstance, FunctionInstance, HostFunctionInstance};
 use super::global::GlobalInstance;
-use super::host::HostValue;
+use super::host::{HostFuncBody, HostValue, ImportStub};
+use super::inst::Instruction;
+use super::limits::{LimitError, ResourceLimits};
 use super::linker::LinkableCollection;
 use super::memory::{self, MemoryInstance};
 use super::module::{
-    self, DefinedModuleInstance, HostExport, HostModuleInstance, ModuleIndex, ModuleInstance,
+    self, DataSegment, DefinedModuleInstance, ElementSegment, GlobalEntry, HostExport,
+    HostModuleInstance, ImportEntry, Module, ModuleIndex, ModuleInstance, SegmentKind,
 };
+use super::reflection::{ExportType, ExternType, ImportType, ModuleInfo};
 use super::table::{self, TableInstance};
+use super::validation;
 use super::value::Value;
+use super::WASM_PAGE_SIZE;
 use anyhow::Result;
 use std::cell::RefCell;
-use std::collections::HashMap;
-use std::rc::Rc;
-use wasmparser::{
-    Data, DataKind, Element, ElementItem, ElementKind, FuncType, FunctionBody, Global, GlobalType,
-    Import, MemoryType, ModuleReader, SectionCode, TableType, Type,
-};
+use std::collections::{HashMap, HashSet};
+use wasmparser::{FuncType, GlobalType, MemoryType, TableType, Type};
 
 /// Store
+///
+/// `Store` is `Send` but not `Sync`. It can be moved to a worker thread as a
+/// whole, but can't be shared between threads. Memories, tables and globals
+/// are guarded by `RefCell` rather than locks, so single-threaded execution
+/// doesn't pay for synchronization.
 pub struct Store {
     funcs: LinkableCollection<FunctionInstance>,
-    tables: LinkableCollection<Rc<RefCell<TableInstance>>>,
-    mems: LinkableCollection<Rc<RefCell<MemoryInstance>>>,
-    globals: LinkableCollection<Rc<RefCell<GlobalInstance>>>,
-    modules: Vec<ModuleInstance>,
+    tables: LinkableCollection<RefCell<TableInstance>>,
+    mems: LinkableCollection<RefCell<MemoryInstance>>,
+    globals: LinkableCollection<RefCell<GlobalInstance>>,
+    /// Indexed by `ModuleIndex`. Collected modules are `None` until their
+    /// slots are reused.
+    modules: Vec<Option<ModuleInstance>>,
+    /// Unloaded modules whose instances are kept because their functions
+    /// are still reachable from loaded modules
+    unloaded_modules: HashSet<ModuleIndex>,
+    free_module_indices: Vec<ModuleIndex>,
     module_index_by_name: HashMap<String, ModuleIndex>,
 
-    embedded_contexts: HashMap<std::any::TypeId, Box<dyn std::any::Any>>,
+    embedded_contexts: HashMap<std::any::TypeId, Box<dyn std::any::Any + Send>>,
+
+    validation: bool,
+    import_stub: Option<ImportStub>,
+    dylink: Option<DylinkState>,
+    limits: ResourceLimits,
 }
 
 impl Store {
@@ -39,11 +59,79 @@ impl Store {
             mems: LinkableCollection::new(),
             globals: LinkableCollection::new(),
             modules: Vec::new(),
+            unloaded_modules: HashSet::new(),
+            free_module_indices: Vec::new(),
             module_index_by_name: HashMap::new(),
             embedded_contexts: HashMap::new(),
+            validation: true,
+            import_stub: None,
+            dylink: None,
+            limits: ResourceLimits::default(),
         }
     }
 
+    /// Enable or disable validation in `load_module`. Enabled by default.
+    /// Debuggers may want to disable it to inspect modules that the
+    /// validator rejects.
+    pub fn set_validation(&mut self, enabled: bool) {
+        self.validation = enabled;
+    }
+
+    /// Fill unresolved imports with stubs instead of failing to instantiate.
+    /// Disabled by default.
+    pub fn set_import_stub(&mut self, stub: Option<ImportStub>) {
+        self.import_stub = stub;
+    }
+
+    /// Limit memories, tables and instances allocated from now on.
+    /// Resources allocated before are kept even if they exceed the limits.
+    pub fn set_limits(&mut self, limits: ResourceLimits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> &ResourceLimits {
+        &self.limits
+    }
+
+    /// Total size of all memories in bytes
+    pub fn memory_bytes(&self) -> usize {
+        self.mems.iter().map(|mem| mem.borrow().data_len()).sum()
+    }
+
+    /// Check that memories can grow by `additional` bytes within the limits.
+    /// Byte counts computed with `saturating_mul` exceed any limit on overflow.
+    pub fn check_memory_growth(&self, additional: usize) -> Result<(), LimitError> {
+        self.limits
+            .check_memory_bytes(self.memory_bytes().saturating_add(additional))
+    }
+
+    /// Replace the function which `addr` refers to with a host function.
+    /// Other modules linked to the original function are not affected.
+    pub fn replace_func(
+        &mut self,
+        addr: FuncAddr,
+        module_name: String,
+        field_name: String,
+        body: HostFuncBody,
+    ) -> Result<()> {
+        let (func, _) = self.funcs.get(addr).ok_or(StoreError::UndefinedFunction(
+            module_name.clone(),
+            field_name.clone(),
+        ))?;
+        if !eq_func_type(func.ty(), body.ty()) {
+            return Err(StoreError::IncompatibleImportFuncType(
+                field_name,
+                func.ty().clone(),
+                body.ty().clone(),
+            )
+            .into());
+        }
+        let instance = HostFunctionInstance::new(body.ty().clone(), module_name, field_name, body);
+        let exec_addr = self.funcs.push_global(FunctionInstance::Host(instance));
+        self.funcs.relink(exec_addr, addr);
+        Ok(())
+    }
+
     pub fn func_global(&self, addr: ExecutableFuncAddr) -> &FunctionInstance {
         self.funcs.get_global(addr)
     }
@@ -52,52 +140,238 @@ impl Store {
         self.funcs.get(addr)
     }
 
-    pub fn global(&self, addr: GlobalAddr) -> Rc<RefCell<GlobalInstance>> {
-        self.globals.get(addr).unwrap().0.clone()
+    pub fn global(&self, addr: GlobalAddr) -> &RefCell<GlobalInstance> {
+        self.globals.get(addr).unwrap().0
+    }
+
+    pub fn try_global(&self, addr: GlobalAddr) -> Option<&RefCell<GlobalInstance>> {
+        self.globals.get(addr).map(|(global, _)| global)
     }
 
     pub fn scan_global_by_name(
         &self,
         module_index: ModuleIndex,
         field: &str,
-    ) -> Option<Rc<RefCell<GlobalInstance>>> {
-        let module = self.module(module_index).defined().unwrap();
+    ) -> Option<&RefCell<GlobalInstance>> {
+        let module = self.module(module_index)?.defined()?;
         let global_addr = module.exported_global(field.to_string()).ok().unwrap();
         global_addr.map(|addr| self.global(addr))
     }
 
-    pub fn table(&self, addr: TableAddr) -> Rc<RefCell<TableInstance>> {
-        self.tables.get(addr).unwrap().0.clone()
+    pub fn table(&self, addr: TableAddr) -> &RefCell<TableInstance> {
+        self.tables.get(addr).unwrap().0
     }
 
-    pub fn memory(&self, addr: MemoryAddr) -> Rc<RefCell<MemoryInstance>> {
-        self.mems.get(addr).unwrap().0.clone()
+    pub fn memory(&self, addr: MemoryAddr) -> &RefCell<MemoryInstance> {
+        self.mems.get(addr).unwrap().0
     }
 
     pub fn memory_count(&self, addr: ModuleIndex) -> usize {
         self.mems.items(addr).map(|c| c.len()).unwrap_or(0)
     }
 
-    pub fn module(&self, module_index: ModuleIndex) -> &ModuleInstance {
-        &self.modules[module_index.0 as usize]
+    /// Instance of a module. Instances of unloaded modules are available
+    /// while their functions are reachable from loaded modules.
+    pub fn module(&self, module_index: ModuleIndex) -> Option<&ModuleInstance> {
+        self.modules.get(module_index.0 as usize)?.as_ref()
+    }
+
+    pub fn is_loaded(&self, module_index: ModuleIndex) -> bool {
+        self.module(module_index).is_some() && !self.unloaded_modules.contains(&module_index)
+    }
+
+    pub fn module_by_name(&self, name: &str) -> Option<&ModuleInstance> {
+        self.module_index_by_name
+            .get(name)
+            .and_then(|index| self.module(*index))
+    }
+
+    /// Index for the next module, reusing a slot of a collected module
+    fn next_module_index(&self) -> ModuleIndex {
+        self.free_module_indices
+            .last()
+            .cloned()
+            .unwrap_or(ModuleIndex(self.modules.len() as u32))
     }
 
-    pub fn module_by_name(&self, name: String) -> &ModuleInstance {
-        if let Some(index) = self.module_index_by_name.get(&name) {
-            self.module(*index)
+    fn insert_module(&mut self, module_index: ModuleIndex, instance: ModuleInstance) {
+        if self.free_module_indices.last() == Some(&module_index) {
+            self.free_module_indices.pop();
+        }
+        let index = module_index.0 as usize;
+        if index < self.modules.len() {
+            self.modules[index] = Some(instance);
         } else {
-            panic!("Module {} was not loaded", name);
+            self.modules.push(Some(instance));
         }
     }
 
     pub fn register_name(&mut self, name: String, module_index: ModuleIndex) {
         self.module_index_by_name.insert(name, module_index);
     }
+
+    /// Unload a module and drop the instances which are no longer reachable.
+    ///
+    /// Functions, memories, tables and globals which other modules imported
+    /// from the module stay alive as long as those modules are loaded, and
+    /// so does the module instance which their functions refer to.
+    /// The index of the module is reused once the instance is dropped.
+    /// The module must not be executing.
+    pub fn unload_module(&mut self, module_index: ModuleIndex) -> Result<(), StoreError> {
+        if !self.is_loaded(module_index) {
+            return Err(StoreError::ModuleNotLoaded(module_index));
+        }
+        self.unloaded_modules.insert(module_index);
+        self.module_index_by_name
+            .retain(|_, index| *index != module_index);
+        if let Some(ref mut state) = self.dylink {
+            state.libraries.retain(|index| *index != module_index);
+            state.memory_bases.remove(&module_index);
+        }
+        self.collect_garbage();
+        Ok(())
+    }
+
+    /// Drop instances which are not reachable from any loaded module
+    fn collect_garbage(&mut self) {
+        // Functions of unloaded modules refer to their own items and types
+        // through the module, so the links and the instance of such modules
+        // are kept while any of their functions is reachable.
+        let mut live = HashSet::new();
+        let mut queue: Vec<ModuleIndex> = (0..self.modules.len())
+            .map(|index| ModuleIndex(index as u32))
+            .filter(|index| self.is_loaded(*index))
+            .collect();
+        let mut host_tables = Vec::new();
+        for module in self.modules.iter().flatten() {
+            if let ModuleInstance::Host(host) = module {
+                for (_, value) in host.entries() {
+                    if let HostExport::Table(addr) = value {
+                        host_tables.push(*addr);
+                    }
+                }
+            }
+        }
+        if let Some(ref state) = self.dylink {
+            host_tables.push(state.table);
+            queue.extend(
+                state
+                    .pending_funcs
+                    .iter()
+                    .map(|(addr, _)| addr.module_index()),
+            );
+        }
+        for addr in host_tables {
+            let table = self.tables.get_global(addr).borrow();
+            queue.extend(
+                table
+                    .contents()
+                    .iter()
+                    .flatten()
+                    .map(|addr| addr.module_index()),
+            );
+        }
+        while let Some(module_index) = queue.pop() {
+            if !live.insert(module_index) {
+                continue;
+            }
+            for addr in self.funcs.items(module_index).unwrap_or_default() {
+                if let FunctionInstance::Defined(func) = self.funcs.get_global(addr) {
+                    queue.push(func.module_index());
+                }
+            }
+            for addr in self.tables.items(module_index).unwrap_or_default() {
+                let table = self.tables.get_global(addr).borrow();
+                queue.extend(
+                    table
+                        .contents()
+                        .iter()
+                        .flatten()
+                        .map(|addr| addr.module_index()),
+                );
+            }
+        }
+
+        let dead: Vec<ModuleIndex> = self
+            .funcs
+            .modules()
+            .chain(self.tables.modules())
+            .chain(self.mems.modules())
+            .chain(self.globals.modules())
+            .filter(|index| !live.contains(index))
+            .collect();
+        for module_index in dead {
+            self.funcs.remove_module(&module_index);
+            self.tables.remove_module(&module_index);
+            self.mems.remove_module(&module_index);
+            self.globals.remove_module(&module_index);
+        }
+        for (index, module) in self.modules.iter_mut().enumerate() {
+            let module_index = ModuleIndex(index as u32);
+            if module.is_some() && !live.contains(&module_index) {
+                *module = None;
+                self.unloaded_modules.remove(&module_index);
+                self.free_module_indices.push(module_index);
+            }
+        }
+
+        let mut funcs = Vec::new();
+        let mut tables = Vec::new();
+        let mut mems = Vec::new();
+        let mut globals = Vec::new();
+        for module in self.modules.iter().flatten() {
+            if let ModuleInstance::Host(host) = module {
+                for (_, value) in host.entries() {
+                    match value {
+                        HostExport::Func(addr) => funcs.push(*addr),
+                        HostExport::Table(addr) => tables.push(*addr),
+                        HostExport::Mem(addr) => mems.push(*addr),
+                        HostExport::Global(addr) => globals.push(*addr),
+                    }
+                }
+            }
+        }
+        if let Some(ref state) = self.dylink {
+            tables.push(state.table);
+            mems.push(state.memory);
+            globals.push(state.stack_pointer);
+            for entry in state.got_mem.values().chain(state.got_func.values()) {
+                globals.push(entry.global);
+            }
+        }
+        self.funcs.collect(funcs.into_iter());
+        self.tables.collect(tables.into_iter());
+        self.mems.collect(mems.into_iter());
+        self.globals.collect(globals.into_iter());
+    }
+
+    /// Number of instances alive in this store
+    pub fn usage(&self) -> StoreUsage {
+        StoreUsage {
+            modules: self.modules.iter().flatten().count(),
+            funcs: self.funcs.count(),
+            tables: self.tables.count(),
+            mems: self.mems.count(),
+            globals: self.globals.count(),
+            memory_bytes: self.memory_bytes(),
+        }
+    }
+}
+
+/// Number of instances alive in a `Store`, returned by `Store::usage`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoreUsage {
+    pub modules: usize,
+    pub funcs: usize,
+    pub tables: usize,
+    pub mems: usize,
+    pub globals: usize,
+    pub memory_bytes: usize,
 }
 
 impl Store {
     pub fn load_host_module(&mut self, name: String, module: HashMap<String, HostValue>) {
-        let module_index = ModuleIndex(self.modules.len() as u32);
+        let module_index = self.next_module_index();
         let mut values = HashMap::new();
         for (field, entry) in module {
             match entry {
@@ -108,25 +382,25 @@ impl Store {
                     values.insert(field, HostExport::Func(addr));
                 }
                 HostValue::Global(g) => {
-                    let addr = self.globals.push_global(g);
+                    let addr = self.globals.push_global(RefCell::new(g));
                     values.insert(field, HostExport::Global(addr));
                 }
                 HostValue::Table(t) => {
-                    let addr = self.tables.push_global(t);
+                    let addr = self.tables.push_global(RefCell::new(t));
                     values.insert(field, HostExport::Table(addr));
                 }
                 HostValue::Mem(m) => {
-                    let addr = self.mems.push_global(m);
+                    let addr = self.mems.push_global(RefCell::new(m));
                     values.insert(field, HostExport::Mem(addr));
                 }
             }
         }
         let instance = HostModuleInstance::new(values);
-        self.modules.push(ModuleInstance::Host(instance));
+        self.insert_module(module_index, ModuleInstance::Host(instance));
         self.module_index_by_name.insert(name, module_index);
     }
 
-    pub fn add_embed_context<T: std::any::Any>(&mut self, ctx: Box<T>) {
+    pub fn add_embed_context<T: std::any::Any + Send>(&mut self, ctx: Box<T>) {
         let type_id = std::any::TypeId::of::<T>();
         self.embedded_contexts.insert(type_id, ctx);
     }
@@ -139,6 +413,203 @@ impl Store {
     }
 }
 
+/// Reflection of the store contents
+impl Store {
+    /// Loaded modules in load order, including host modules
+    pub fn modules(&self) -> impl Iterator<Item = ModuleInfo> + '_ {
+        self.modules
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, module)| {
+                let module = module.as_ref()?;
+                let index = ModuleIndex(index as u32);
+                let name = self
+                    .module_index_by_name
+                    .iter()
+                    .filter(|(_, i)| **i == index)
+                    .map(|(name, _)| name)
+                    .min()
+                    .cloned();
+                Some(ModuleInfo {
+                    index,
+                    name,
+                    is_host: module.defined().is_none(),
+                })
+            })
+    }
+
+    pub fn imports(&self, module_index: ModuleIndex) -> Vec<ImportType> {
+        match self.module(module_index) {
+            Some(ModuleInstance::Defined(defined)) => defined.imports().to_vec(),
+            Some(ModuleInstance::Host(_)) | None => Vec::new(),
+        }
+    }
+
+    /// Exports with the types of the exported items. Exports of host
+    /// modules are sorted by name.
+    pub fn exports(&self, module_index: ModuleIndex) -> Vec<ExportType> {
+        match self.module(module_index) {
+            Some(ModuleInstance::Defined(defined)) => defined
+                .exports
+                .iter()
+                .filter_map(|export| {
+                    let (ty, index) = match export.value() {
+                        ExternalValue::Func(addr) => (
+                            ExternType::Func(self.func(*addr)?.0.ty().clone()),
+                            addr.index(),
+                        ),
+                        ExternalValue::Global(addr) => (
+                            ExternType::Global(*self.globals.get(*addr)?.0.borrow().ty()),
+                            addr.index(),
+                        ),
+                        ExternalValue::Memory(addr) => (
+                            ExternType::Memory(self.mems.get(*addr)?.0.borrow().ty()),
+                            addr.index(),
+                        ),
+                        ExternalValue::Table(addr) => (
+                            ExternType::Table(self.tables.get(*addr)?.0.borrow().ty()),
+                            addr.index(),
+                        ),
+                    };
+                    Some(ExportType {
+                        name: export.name().clone(),
+                        ty,
+                        index: Some(index),
+                    })
+                })
+                .collect(),
+            Some(ModuleInstance::Host(host)) => {
+                let mut exports: Vec<ExportType> = host
+                    .entries()
+                    .map(|(name, value)| {
+                        let ty = match value {
+                            HostExport::Func(addr) => {
+                                ExternType::Func(self.funcs.get_global(*addr).ty().clone())
+                            }
+                            HostExport::Global(addr) => {
+                                ExternType::Global(*self.globals.get_global(*addr).borrow().ty())
+                            }
+                            HostExport::Mem(addr) => {
+                                ExternType::Memory(self.mems.get_global(*addr).borrow().ty())
+                            }
+                            HostExport::Table(addr) => {
+                                ExternType::Table(self.tables.get_global(*addr).borrow().ty())
+                            }
+                        };
+                        ExportType {
+                            name: name.clone(),
+                            ty,
+                            index: None,
+                        }
+                    })
+                    .collect();
+                exports.sort_by(|a, b| a.name.cmp(&b.name));
+                exports
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Functions in the function index space of a module, including imports
+    pub fn funcs(
+        &self,
+        module_index: ModuleIndex,
+    ) -> impl Iterator<Item = (FuncAddr, &FunctionInstance)> {
+        let addrs = self.funcs.items(module_index).unwrap_or_default();
+        addrs.into_iter().enumerate().map(move |(index, addr)| {
+            (
+                FuncAddr::new_unsafe(module_index, index),
+                self.funcs.get_global(addr),
+            )
+        })
+    }
+
+    pub fn memories(
+        &self,
+        module_index: ModuleIndex,
+    ) -> impl Iterator<Item = (MemoryAddr, &RefCell<MemoryInstance>)> {
+        let addrs = self.mems.items(module_index).unwrap_or_default();
+        addrs.into_iter().enumerate().map(move |(index, addr)| {
+            (
+                MemoryAddr::new_unsafe(module_index, index),
+                self.mems.get_global(addr),
+            )
+        })
+    }
+
+    pub fn globals(
+        &self,
+        module_index: ModuleIndex,
+    ) -> impl Iterator<Item = (GlobalAddr, &RefCell<GlobalInstance>)> {
+        let addrs = self.globals.items(module_index).unwrap_or_default();
+        addrs.into_iter().enumerate().map(move |(index, addr)| {
+            (
+                GlobalAddr::new_unsafe(module_index, index),
+                self.globals.get_global(addr),
+            )
+        })
+    }
+
+    pub fn tables(
+        &self,
+        module_index: ModuleIndex,
+    ) -> impl Iterator<Item = (TableAddr, &RefCell<TableInstance>)> {
+        let addrs = self.tables.items(module_index).unwrap_or_default();
+        addrs.into_iter().enumerate().map(move |(index, addr)| {
+            (
+                TableAddr::new_unsafe(module_index, index),
+                self.tables.get_global(addr),
+            )
+        })
+    }
+
+    fn import_type(import: &ImportEntry, types: &[FuncType]) -> Result<ExternType, StoreError> {
+        use wasmparser::ImportSectionEntryType::*;
+        Ok(match import.ty {
+            Function(type_index) => ExternType::Func(
+                types
+                    .get(type_index as usize)
+                    .ok_or(StoreError::UnknownType(type_index))?
+                    .clone(),
+            ),
+            Table(ty) => ExternType::Table(ty),
+            Memory(ty) => ExternType::Memory(ty),
+            Global(ty) => ExternType::Global(ty),
+        })
+    }
+}
+
+/// An import which no loaded module provides
+#[derive(Debug, Clone)]
+pub struct UnresolvedImport {
+    pub module: String,
+    pub field: String,
+    pub kind: &'static str,
+}
+
+impl UnresolvedImport {
+    fn new(import: &ImportEntry) -> Self {
+        use wasmparser::ImportSectionEntryType::*;
+        let kind = match import.ty {
+            Function(_) => "function",
+            Memory(_) => "memory",
+            Table(_) => "table",
+            Global(_) => "global",
+        };
+        Self {
+            module: import.module.clone(),
+            field: import.field.clone(),
+            kind,
+        }
+    }
+}
+
+impl std::fmt::Display for UnresolvedImport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} \"{}\" in \"{}\"", self.kind, self.field, self.module)
+    }
+}
+
 #[derive(Debug)]
 pub enum StoreError {
     InvalidElementSegments(table::Error),
@@ -146,6 +617,8 @@ pub enum StoreError {
     InvalidHostImport(module::HostModuleError),
     InvalidImport(module::DefinedModuleError),
     UnknownType(/* type index: */ u32),
+    ModuleNotLoaded(ModuleIndex),
+    UnresolvedImports(Vec<UnresolvedImport>),
     UndefinedFunction(/* module: */ String, /* name: */ String),
     UndefinedMemory(String, String),
     UndefinedTable(String, String),
@@ -155,6 +628,10 @@ pub enum StoreError {
     IncompatibleImportGlobalMutability,
     IncompatibleImportTableType,
     IncompatibleImportMemoryType,
+    NotSharedLibrary,
+    FailedToAllocateMemory(memory::Error),
+    FailedToAllocateLibrary(String),
+    FailedToInitializeLibrary(/* function: */ String, WasmError),
 }
 impl std::error::Error for StoreError {}
 
@@ -168,6 +645,14 @@ impl std::fmt::Display for StoreError {
             Self::InvalidHostImport(err) => write!(f, "invalid host import: {}", err),
             Self::InvalidImport(err) => write!(f, "invalid import: {}", err),
             Self::UnknownType(idx) => write!(f, "Unknown type index used: {:?}", idx),
+            Self::ModuleNotLoaded(idx) => write!(f, "module {} is not loaded", idx.0),
+            Self::UnresolvedImports(imports) => {
+                write!(f, "unknown import: {} unresolved import(s)", imports.len())?;
+                for import in imports {
+                    write!(f, "\n  {}", import)?;
+                }
+                Ok(())
+            }
             Self::UndefinedFunction(module, name) => write!(
                 f,
                 "unknown import: Undefined function \"{}\" in \"{}\"",
@@ -201,199 +686,100 @@ impl std::fmt::Display for StoreError {
             Self::IncompatibleImportGlobalMutability => write!(f, "incompatible import type"),
             Self::IncompatibleImportTableType => write!(f, "incompatible import type"),
             Self::IncompatibleImportMemoryType => write!(f, "incompatible import type"),
+            Self::NotSharedLibrary => write!(f, "module has no dylink section"),
+            Self::FailedToAllocateMemory(err) => write!(f, "{}", err),
+            Self::FailedToAllocateLibrary(reason) => {
+                write!(f, "failed to allocate shared library: {}", reason)
+            }
+            Self::FailedToInitializeLibrary(func, err) => {
+                write!(f, "failed to run {} of shared library: {}", func, err)
+            }
         }
     }
 }
 
-fn read_name_section(reader: wasmparser::NameSectionReader) -> Result<HashMap<u32, String>> {
-    // let mut module_name = None;
-    let mut func_names = HashMap::new();
-    // let mut locals_names = HashMap::new();
-    for i in reader.into_iter() {
-        match i? {
-            wasmparser::Name::Module(_) => {
-                // module_name = Some(String::from(m.get_name()?));
-            }
-            wasmparser::Name::Function(f) => {
-                let mut reader = f.get_map()?;
-                while let Ok(naming) = reader.read() {
-                    func_names.insert(naming.index, String::from(naming.name));
-                }
-            }
-            wasmparser::Name::Local(_) => {
-                // let mut reader = l.get_function_local_reader()?;
-                // while let Ok(f) = reader.read() {
-                //     let mut names = HashMap::new();
-                //     let mut reader = f.get_map()?;
-                //     while let Ok(naming) = reader.read() {
-                //         names.insert(naming.index, String::from(naming.name));
-                //     }
-                //     locals_names.insert(f.func_index, names);
-                // }
-            }
-        }
-    }
-    // let result = NameSection {
-    //     module_name,
-    //     func_names,
-    //     locals_names,
-    // };
-    Ok(func_names)
-}
-
 impl Store {
-    fn load_module_internal(
+    fn instantiate_module_internal(
         &mut self,
         name: Option<String>,
-        reader: &mut ModuleReader,
+        module: &Module,
         module_index: ModuleIndex,
+        dylink: Option<&DylinkBases>,
     ) -> Result<ModuleIndex> {
-        let mut types = Vec::new();
-        let mut elem_segs = Vec::new();
-        let mut data_segs = Vec::new();
-        let mut func_sigs = Vec::new();
-        let mut imports = Vec::new();
-        let mut exports = Vec::new();
-        let mut bodies = Vec::new();
-        let mut tables = Vec::new();
-        let mut globals = Vec::new();
-        let mut mems = Vec::new();
-        let mut func_names = HashMap::new();
-
-        let mut start_func = None;
-
-        let mut code_section_base_offset = None;
-
-        while !reader.eof() {
-            let offset = reader.current_position();
-            let section = reader.read()?;
-            match section.code {
-                SectionCode::Type => {
-                    let section = section.get_type_section_reader()?;
-                    types.reserve_exact(section.get_count() as usize);
-                    for entry in section {
-                        types.push(entry?);
-                    }
-                }
-                SectionCode::Element => {
-                    let section = section.get_element_section_reader()?;
-                    elem_segs.reserve_exact(section.get_count() as usize);
-                    for entry in section {
-                        elem_segs.push(entry?);
-                    }
-                }
-                SectionCode::Data => {
-                    let section = section.get_data_section_reader()?;
-                    data_segs.reserve_exact(section.get_count() as usize);
-                    for entry in section {
-                        data_segs.push(entry?);
-                    }
-                }
-                SectionCode::Import => {
-                    let section = section.get_import_section_reader()?;
-                    imports.reserve_exact(section.get_count() as usize);
-                    for entry in section {
-                        imports.push(entry?);
-                    }
-                }
-                SectionCode::Export => {
-                    let section = section.get_export_section_reader()?;
-                    exports.reserve_exact(section.get_count() as usize);
-                    for entry in section {
-                        exports.push(entry?);
-                    }
-                }
-                SectionCode::Function => {
-                    let section = section.get_function_section_reader()?;
-                    func_sigs.reserve_exact(section.get_count() as usize);
-                    for entry in section {
-                        func_sigs.push(entry?);
-                    }
-                }
-                SectionCode::Code => {
-                    let section = section.get_code_section_reader()?;
-                    code_section_base_offset = Some(offset);
-                    bodies.reserve_exact(section.get_count() as usize);
-                    for entry in section {
-                        bodies.push(entry?);
-                    }
-                }
-                SectionCode::Table => {
-                    let section = section.get_table_section_reader()?;
-                    tables.reserve_exact(section.get_count() as usize);
-                    for entry in section {
-                        tables.push(entry?);
-                    }
-                }
-                SectionCode::Memory => {
-                    let section = section.get_memory_section_reader()?;
-                    mems.reserve_exact(section.get_count() as usize);
-                    for entry in section {
-                        mems.push(entry?);
-                    }
-                }
-                SectionCode::Global => {
-                    let section = section.get_global_section_reader()?;
-                    globals.reserve_exact(section.get_count() as usize);
-                    for entry in section {
-                        globals.push(entry?);
-                    }
-                }
-                SectionCode::Start => {
-                    start_func = Some(FuncAddr::new_unsafe(
-                        module_index,
-                        section.get_start_section_content()? as usize,
-                    ));
-                }
-                SectionCode::Custom { name: _, kind } => {
-                    use wasmparser::CustomSectionKind;
-                    match kind {
-                        CustomSectionKind::Name => {
-                            let section = section.get_name_section_reader()?;
-                            func_names = read_name_section(section)?;
-                        }
-                        _ => (),
-                    }
-                }
-                _ => (),
-            }
-        }
+        let instances = self
+            .modules
+            .iter()
+            .flatten()
+            .filter(|module| module.defined().is_some())
+            .count();
+        self.limits.check_new_instance(instances)?;
+        let types = module.types();
+        self.load_imports(module.imports(), module_index, types, dylink)?;
+        self.load_globals(module.globals(), module_index)?;
+        self.load_functions(module_index, module, types)?;
+        self.load_tables(module.tables(), module_index, module.elem_segments())?;
+        self.load_mems(module.mems(), module_index, module.data_segments())?;
 
-        self.load_imports(imports, module_index, &types)?;
-        self.load_globals(globals, module_index)?;
-        if let Some(base_offset) = code_section_base_offset {
-            self.load_functions(
-                module_index,
-                func_sigs,
-                bodies,
-                func_names,
-                &types,
-                base_offset,
-            )?;
-        }
-        self.load_tables(tables, module_index, elem_segs)?;
-        self.load_mems(mems, module_index, data_segs)?;
-
-        let types = types.iter().map(|ty| ty.clone()).collect();
-
-        let instance =
-            DefinedModuleInstance::new_from_module(module_index, types, exports, start_func);
-        self.modules.push(ModuleInstance::Defined(instance));
+        let start_func = module
+            .start_func()
+            .map(|index| FuncAddr::new_unsafe(module_index, index as usize));
+        let imports = module
+            .imports()
+            .iter()
+            .map(|import| {
+                Ok(ImportType {
+                    module: import.module.clone(),
+                    field: import.field.clone(),
+                    ty: Self::import_type(import, types)?,
+                })
+            })
+            .collect::<Result<Vec<_>, StoreError>>()?;
+        let instance = DefinedModuleInstance::new_from_module(
+            module_index,
+            types.to_vec(),
+            imports,
+            module.exports(),
+            start_func,
+        );
+        self.insert_module(module_index, ModuleInstance::Defined(instance));
         if let Some(name) = name {
             self.module_index_by_name.insert(name, module_index);
         }
 
         Ok(module_index)
     }
-    pub fn load_module(
+
+    /// Decode, validate and instantiate a module binary.
+    /// Validation failures are reported as `ValidationError`.
+    pub fn load_module(&mut self, name: Option<String>, bytes: &[u8]) -> Result<ModuleIndex> {
+        let module = if self.validation {
+            validation::decode_validated(bytes)?
+        } else {
+            Module::from_bytes(bytes)?
+        };
+        self.instantiate_module(name, &module)
+    }
+
+    /// Instantiate a decoded module into this store.
+    /// The same `Module` can be instantiated many times.
+    pub fn instantiate_module(
+        &mut self,
+        name: Option<String>,
+        module: &Module,
+    ) -> Result<ModuleIndex> {
+        self.instantiate_module_with(name, module, None)
+    }
+
+    fn instantiate_module_with(
         &mut self,
         name: Option<String>,
-        reader: &mut ModuleReader,
+        module: &Module,
+        dylink: Option<&DylinkBases>,
     ) -> Result<ModuleIndex> {
-        let module_index = ModuleIndex(self.modules.len() as u32);
+        let module_index = self.next_module_index();
 
         let result: Result<ModuleIndex> =
-            self.load_module_internal(name.clone(), reader, module_index);
+            self.instantiate_module_internal(name.clone(), module, module_index, dylink);
         match result {
             Ok(ok) => Ok(ok),
             Err(err) => {
@@ -402,26 +788,63 @@ impl Store {
                 self.tables.remove_module(&module_index);
                 self.mems.remove_module(&module_index);
                 self.globals.remove_module(&module_index);
-                let module_index = module_index.0 as usize;
-                if module_index < self.modules.len() {
-                    self.modules.remove(module_index);
+                if let Some(slot) = self.modules.get_mut(module_index.0 as usize) {
+                    if slot.take().is_some() {
+                        self.free_module_indices.push(module_index);
+                    }
                 }
                 if let Some(ref name) = name.clone() {
                     self.module_index_by_name.remove(name);
                 }
+                self.collect_garbage();
                 Err(err)
             }
         }
     }
 
+    fn is_resolvable(&self, import: &ImportEntry) -> bool {
+        match self.module_by_name(&import.module) {
+            Some(ModuleInstance::Defined(defined)) => {
+                defined.exported_by_name(import.field.clone()).is_some()
+            }
+            Some(ModuleInstance::Host(host)) => host.contains(&import.field),
+            None => false,
+        }
+    }
+
     fn load_imports(
         &mut self,
-        imports: Vec<Import>,
+        imports: &[ImportEntry],
         module_index: ModuleIndex,
         types: &[FuncType],
+        dylink: Option<&DylinkBases>,
     ) -> Result<()> {
+        let unresolved = imports
+            .iter()
+            .filter(|import| !self.is_resolvable(import))
+            .filter(|import| dylink.is_none() || !Self::is_dylink_import(import))
+            .collect::<Vec<_>>();
+        let stub = match (self.import_stub, unresolved.is_empty()) {
+            (_, true) => None,
+            (Some(stub), false) => Some(stub),
+            (None, false) => {
+                let unresolved = unresolved.into_iter().map(UnresolvedImport::new).collect();
+                return Err(StoreError::UnresolvedImports(unresolved).into());
+            }
+        };
         for import in imports {
             use wasmparser::ImportSectionEntryType::*;
+            if let Some(bases) = dylink {
+                if self.load_dylink_import(module_index, import, types, bases)? {
+                    continue;
+                }
+            }
+            if let Some(stub) = stub {
+                if !self.is_resolvable(import) {
+                    self.load_import_stub(module_index, import, types, stub)?;
+                    continue;
+                }
+            }
             match import.ty {
                 Function(type_index) => {
                     self.load_import_function(module_index, import, type_index as usize, &types)?;
@@ -440,10 +863,58 @@ impl Store {
         Ok(())
     }
 
+    fn load_import_stub(
+        &mut self,
+        module_index: ModuleIndex,
+        import: &ImportEntry,
+        types: &[FuncType],
+        stub: ImportStub,
+    ) -> Result<()> {
+        use wasmparser::ImportSectionEntryType::*;
+        match import.ty {
+            Function(type_index) => {
+                let func_ty = types
+                    .get(type_index as usize)
+                    .ok_or(StoreError::UnknownType(type_index))?
+                    .clone();
+                let (module, field) = (import.module.clone(), import.field.clone());
+                let body = HostFuncBody::stub(func_ty.clone(), module.clone(), field.clone(), stub);
+                let instance = HostFunctionInstance::new(func_ty, module, field, body);
+                let addr = self.funcs.push_global(FunctionInstance::Host(instance));
+                self.funcs.link(addr, module_index);
+            }
+            Memory(memory_ty) => {
+                let limits = memory_ty.limits;
+                let memory = MemoryInstance::new(
+                    limits.initial as usize,
+                    limits.maximum.map(|max| max as usize),
+                )
+                .map_err(StoreError::FailedToAllocateMemory)?;
+                let addr = self.mems.push_global(RefCell::new(memory));
+                self.mems.link(addr, module_index);
+            }
+            Table(table_ty) => {
+                let limits = table_ty.limits;
+                let table = TableInstance::new(
+                    limits.initial as usize,
+                    limits.maximum.map(|max| max as usize),
+                );
+                let addr = self.tables.push_global(RefCell::new(table));
+                self.tables.link(addr, module_index);
+            }
+            Global(global_ty) => {
+                let global = GlobalInstance::new(Value::zero(global_ty.content_type)?, global_ty);
+                let addr = self.globals.push_global(RefCell::new(global));
+                self.globals.link(addr, module_index);
+            }
+        }
+        Ok(())
+    }
+
     fn load_import_function(
         &mut self,
         module_index: ModuleIndex,
-        import: Import,
+        import: &ImportEntry,
         type_index: usize,
         types: &[FuncType],
     ) -> Result<()> {
@@ -451,14 +922,13 @@ impl Store {
             .get(type_index)
             .ok_or(StoreError::UnknownType(type_index as u32))?
             .clone();
-        let name = import.field.to_string();
-        let module = self.module_by_name(import.module.to_string());
-        let err = || {
-            StoreError::UndefinedFunction(
-                import.module.clone().to_string(),
-                import.field.clone().to_string(),
-            )
-        };
+        let name = import.field.clone();
+        let module = self
+            .module_by_name(&import.module)
+            .ok_or(StoreError::UnresolvedImports(vec![UnresolvedImport::new(
+                import,
+            )]))?;
+        let err = || StoreError::UndefinedFunction(import.module.clone(), import.field.clone());
         let exec_addr = match module {
             ModuleInstance::Defined(defined) => {
                 let func_addr = defined
@@ -468,7 +938,7 @@ impl Store {
                 self.funcs.resolve(func_addr).ok_or_else(err)?.clone()
             }
             ModuleInstance::Host(host) => *host
-                .func_by_name(import.field.to_string())
+                .func_by_name(import.field.clone())
                 .map_err(StoreError::InvalidHostImport)?
                 .ok_or_else(err)?,
         };
@@ -476,7 +946,7 @@ impl Store {
         // Validation
         if !eq_func_type(actual_func_ty, &func_ty) {
             Err(StoreError::IncompatibleImportFuncType(
-                import.field.to_string(),
+                import.field.clone(),
                 func_ty,
                 actual_func_ty.clone(),
             ))?;
@@ -488,17 +958,16 @@ impl Store {
     fn load_import_memory(
         &mut self,
         module_index: ModuleIndex,
-        import: Import,
+        import: &ImportEntry,
         memory_ty: MemoryType,
     ) -> Result<()> {
-        let err = || {
-            StoreError::UndefinedMemory(
-                import.module.clone().to_string(),
-                import.field.clone().to_string(),
-            )
-        };
-        let name = import.field.to_string();
-        let module = self.module_by_name(import.module.to_string());
+        let err = || StoreError::UndefinedMemory(import.module.clone(), import.field.clone());
+        let name = import.field.clone();
+        let module = self
+            .module_by_name(&import.module)
+            .ok_or(StoreError::UnresolvedImports(vec![UnresolvedImport::new(
+                import,
+            )]))?;
         let resolved_addr = match module {
             ModuleInstance::Defined(defined) => {
                 let addr = defined
@@ -517,7 +986,7 @@ impl Store {
         // Validation
         {
             let memory = self.mems.get_global(resolved_addr);
-            if memory.borrow().initial < memory_ty.limits.initial as usize {
+            if memory.borrow().page_count() < memory_ty.limits.initial as usize {
                 Err(StoreError::IncompatibleImportMemoryType)?;
             }
             match (memory.borrow().max, memory_ty.limits.maximum) {
@@ -537,17 +1006,16 @@ impl Store {
     fn load_import_table(
         &mut self,
         module_index: ModuleIndex,
-        import: Import,
+        import: &ImportEntry,
         table_ty: TableType,
     ) -> Result<()> {
-        let name = import.field.to_string();
-        let module = self.module_by_name(import.module.to_string());
-        let err = || {
-            StoreError::UndefinedTable(
-                import.module.clone().to_string(),
-                import.field.clone().to_string(),
-            )
-        };
+        let name = import.field.clone();
+        let module = self
+            .module_by_name(&import.module)
+            .ok_or(StoreError::UnresolvedImports(vec![UnresolvedImport::new(
+                import,
+            )]))?;
+        let err = || StoreError::UndefinedTable(import.module.clone(), import.field.clone());
         let resolved_addr = match module {
             ModuleInstance::Defined(defined) => {
                 let addr = defined
@@ -565,10 +1033,10 @@ impl Store {
         let found = self.tables.get_global(resolved_addr);
         // Validation
         {
-            if found.borrow().initial < table_ty.limits.initial as usize {
+            if found.borrow().buffer_len() < table_ty.limits.initial as usize {
                 Err(StoreError::IncompatibleImportTableType)?;
             }
-            match (found.clone().borrow().max, table_ty.limits.maximum) {
+            match (found.borrow().max, table_ty.limits.maximum) {
                 (Some(found), Some(expected)) => {
                     if found > expected as usize {
                         Err(StoreError::IncompatibleImportTableType)?;
@@ -586,17 +1054,16 @@ impl Store {
     fn load_import_global(
         &mut self,
         module_index: ModuleIndex,
-        import: Import,
+        import: &ImportEntry,
         global_ty: GlobalType,
     ) -> Result<()> {
-        let name = import.field.to_string();
-        let module = self.module_by_name(import.module.to_string());
-        let err = || {
-            StoreError::UndefinedGlobal(
-                import.module.clone().to_string(),
-                import.field.clone().to_string(),
-            )
-        };
+        let name = import.field.clone();
+        let module = self
+            .module_by_name(&import.module)
+            .ok_or(StoreError::UnresolvedImports(vec![UnresolvedImport::new(
+                import,
+            )]))?;
+        let err = || StoreError::UndefinedGlobal(import.module.clone(), import.field.clone());
         let resolved_addr = match module {
             ModuleInstance::Defined(defined) => {
                 let addr = defined
@@ -633,26 +1100,26 @@ impl Store {
     fn load_functions(
         &mut self,
         module_index: ModuleIndex,
-        func_sigs: Vec<u32>,
-        bodies: Vec<FunctionBody>,
-        names: HashMap<u32, String>,
+        module: &Module,
         types: &[FuncType],
-        base_offset: usize,
     ) -> Result<Vec<FuncAddr>> {
         let mut func_addrs = Vec::new();
         let imported_funcs = self.funcs.items(module_index);
         let mut index = imported_funcs.map(|items| items.len() as u32).unwrap_or(0);
-        for (func_sig, body) in func_sigs.into_iter().zip(bodies) {
+        for entry in module.funcs() {
             let func_type = types
-                .get(func_sig as usize)
-                .ok_or(StoreError::UnknownType(func_sig))?
+                .get(entry.type_index as usize)
+                .ok_or(StoreError::UnknownType(entry.type_index))?
                 .clone();
-            let name = names.get(&index).map(|n| n.clone()).unwrap_or(format!(
+            let name = module.func_name(index).cloned().unwrap_or(format!(
                 "<module #{} defined func #{}>",
                 module_index.0, index
             ));
-            let defined =
-                DefinedFunctionInstance::new(name, func_type, module_index, body, base_offset)?;
+            let mut defined =
+                DefinedFunctionInstance::new(name, func_type, module_index, entry.code.clone())?;
+            if let Some(names) = module.names().locals.get(&index) {
+                defined.set_local_names(names.clone());
+            }
             let instance = FunctionInstance::Defined(defined);
             let func_addr = self.funcs.push(module_index, instance);
             func_addrs.push(func_addr);
@@ -661,21 +1128,38 @@ impl Store {
         Ok(func_addrs)
     }
 
-    fn load_globals(&mut self, globals: Vec<Global>, module_index: ModuleIndex) -> Result<()> {
+    fn load_globals(&mut self, globals: &[GlobalEntry], module_index: ModuleIndex) -> Result<()> {
         for entry in globals {
             let value = eval_const_expr(&entry.init_expr, &self, module_index)?;
+            if value.value_type() != entry.ty.content_type {
+                Err(ConstExprError::TypeMismatch {
+                    expected: entry.ty.content_type,
+                    actual: value.value_type(),
+                })?;
+            }
             let instance = GlobalInstance::new(value, entry.ty.clone());
-            self.globals
-                .push(module_index, Rc::new(RefCell::new(instance)));
+            self.globals.push(module_index, RefCell::new(instance));
         }
         Ok(())
     }
 
+    /// Evaluate the offset of an active segment
+    fn eval_offset(&self, offset: &[Instruction], module_index: ModuleIndex) -> Result<u32> {
+        match eval_const_expr(offset, self, module_index)? {
+            Value::I32(offset) => Ok(offset as u32),
+            other => Err(ConstExprError::TypeMismatch {
+                expected: Type::I32,
+                actual: other.value_type(),
+            }
+            .into()),
+        }
+    }
+
     fn load_tables(
         &mut self,
-        tables: Vec<TableType>,
+        tables: &[TableType],
         module_index: ModuleIndex,
-        element_segments: Vec<Element>,
+        element_segments: &[ElementSegment],
     ) -> Result<Vec<TableAddr>> {
         let mut table_addrs = Vec::new();
         if tables.is_empty() && self.tables.is_empty(module_index) {
@@ -684,13 +1168,13 @@ impl Store {
         for entry in tables.iter() {
             match entry.element_type {
                 Type::AnyFunc => {
+                    self.limits
+                        .check_table_elements(entry.limits.initial as usize)?;
                     let instance = TableInstance::new(
                         entry.limits.initial as usize,
                         entry.limits.maximum.map(|mx| mx as usize),
                     );
-                    let addr = self
-                        .tables
-                        .push(module_index, Rc::new(RefCell::new(instance)));
+                    let addr = self.tables.push(module_index, RefCell::new(instance));
                     table_addrs.push(addr);
                 }
                 _ => (),
@@ -699,36 +1183,30 @@ impl Store {
         let tables = self.tables.items(module_index).unwrap();
         for seg in element_segments {
             match seg.kind {
-                ElementKind::Active {
-                    table_index,
-                    init_expr,
+                SegmentKind::Active {
+                    index: table_index,
+                    ref offset,
                 } => {
                     let table_addr = match tables.get(table_index as usize) {
                         Some(addr) => addr,
                         None => continue,
                     };
-                    let offset = match eval_const_expr(&init_expr, self, module_index)? {
-                        Value::I32(v) => v,
-                        _ => panic!(),
-                    };
+                    let offset = self.eval_offset(offset, module_index)?;
                     let data = seg
                         .items
-                        .get_items_reader()?
-                        .into_iter()
-                        .map(|item| match item? {
-                            ElementItem::Func(index) => {
-                                Ok(Some(FuncAddr::new_unsafe(module_index, index as usize)))
-                            }
-                            ElementItem::Null => Ok(None),
+                        .iter()
+                        .map(|index| {
+                            index.map(|index| FuncAddr::new_unsafe(module_index, index as usize))
                         })
-                        .collect::<Result<Vec<Option<FuncAddr>>>>()?;
+                        .collect();
                     let table = self.tables.get_global(*table_addr);
                     table
                         .borrow_mut()
                         .initialize(offset as usize, data)
                         .map_err(StoreError::InvalidElementSegments)?;
                 }
-                _ => unimplemented!(),
+                // Passive and declared segments are used only by instructions
+                _ => (),
             }
         }
         Ok(table_addrs)
@@ -736,22 +1214,23 @@ impl Store {
 
     fn load_mems(
         &mut self,
-        mems: Vec<MemoryType>,
+        mems: &[MemoryType],
         module_index: ModuleIndex,
-        data_segments: Vec<Data>,
+        data_segments: &[DataSegment],
     ) -> Result<Vec<MemoryAddr>> {
         let mut mem_addrs = Vec::new();
         if mems.is_empty() && self.mems.is_empty(module_index) {
             return Ok(mem_addrs);
         }
         for entry in mems.iter() {
+            let initial_bytes = (entry.limits.initial as usize).saturating_mul(WASM_PAGE_SIZE);
+            self.check_memory_growth(initial_bytes)?;
             let instance = MemoryInstance::new(
                 entry.limits.initial as usize,
                 entry.limits.maximum.map(|mx| mx as usize),
-            );
-            let addr = self
-                .mems
-                .push(module_index, Rc::new(RefCell::new(instance)));
+            )
+            .map_err(StoreError::FailedToAllocateMemory)?;
+            let addr = self.mems.push(module_index, RefCell::new(instance));
             mem_addrs.push(addr);
         }
 
@@ -759,23 +1238,20 @@ impl Store {
         let mems = self.mems.items(module_index).unwrap();
         for seg in data_segments {
             match seg.kind {
-                DataKind::Active {
-                    memory_index,
-                    init_expr,
+                SegmentKind::Active {
+                    index: memory_index,
+                    ref offset,
                 } => {
                     let mem_addr = match mems.get(memory_index as usize) {
                         Some(addr) => addr,
                         None => continue,
                     };
-                    let offset = match eval_const_expr(&init_expr, self, module_index)? {
-                        Value::I32(v) => v,
-                        _ => panic!(),
-                    };
+                    let offset = self.eval_offset(offset, module_index)?;
                     let mem = self.mems.get_global(*mem_addr);
                     mem.borrow()
                         .validate_region(offset as usize, seg.data.len())
                         .map_err(StoreError::InvalidDataSegments)?;
-                    offsets_and_value.push((mem, offset, seg.data));
+                    offsets_and_value.push((mem, offset, &seg.data));
                 }
                 _ => (),
             }
@@ -790,6 +1266,357 @@ impl Store {
     }
 }
 
+impl Store {
+    /// Instantiate a shared library following the dynamic linking convention
+    /// of the wasm tool-conventions.
+    ///
+    /// Libraries share one memory, table and stack, and each gets its own
+    /// region of the memory and table. `GOT.mem` and `GOT.func` imports are
+    /// resolved against the libraries loaded so far, and function imports
+    /// from `env` which no library exports yet are bound when one does.
+    /// Relocations and constructors run before this returns, except that
+    /// commands call their constructors from `_start` by themselves.
+    pub fn load_dylink_module(
+        &mut self,
+        name: Option<String>,
+        module: &Module,
+    ) -> Result<ModuleIndex> {
+        let info = module.dylink().ok_or(StoreError::NotSharedLibrary)?;
+        if self.dylink.is_none() {
+            self.dylink = Some(self.init_dylink(module)?);
+        }
+        let bases = self.allocate_dylink_regions(module, info)?;
+        let module_index = self.instantiate_module_with(name, module, Some(&bases))?;
+        {
+            let state = self.dylink.as_mut().unwrap();
+            state.libraries.push(module_index);
+            state.memory_bases.insert(module_index, bases.memory_base);
+        }
+        self.resolve_dylink_symbols()?;
+        self.run_dylink_initializers(module_index)?;
+        Ok(module_index)
+    }
+
+    /// Create the memory, table and stack shared by all libraries, with
+    /// the limits which the first library imports them with
+    fn init_dylink(&mut self, module: &Module) -> Result<DylinkState> {
+        use wasmparser::ImportSectionEntryType::*;
+        let (mut memory_max, mut table_max) = (None, None);
+        for import in module.imports().iter().filter(|i| i.module == "env") {
+            match (import.field.as_str(), import.ty) {
+                ("memory", Memory(ty)) => memory_max = ty.limits.maximum,
+                ("__indirect_function_table", Table(ty)) => table_max = ty.limits.maximum,
+                _ => (),
+            }
+        }
+        let memory = MemoryInstance::new(0, memory_max.map(|max| max as usize))
+            .map_err(StoreError::FailedToAllocateMemory)?;
+        // Keep the slot 0 empty so that calling a null function pointer traps
+        let table = TableInstance::new(1, table_max.map(|max| max as usize));
+        let stack_top = dylink::GLOBAL_BASE + dylink::STACK_SIZE;
+        let stack_pointer = GlobalInstance::new(
+            Value::I32(stack_top as i32),
+            GlobalType {
+                content_type: Type::I32,
+                mutable: true,
+            },
+        );
+        Ok(DylinkState {
+            memory: self.mems.push_global(RefCell::new(memory)),
+            table: self.tables.push_global(RefCell::new(table)),
+            stack_pointer: self.globals.push_global(RefCell::new(stack_pointer)),
+            memory_end: stack_top,
+            libraries: Vec::new(),
+            memory_bases: HashMap::new(),
+            got_mem: HashMap::new(),
+            got_func: HashMap::new(),
+            pending_funcs: Vec::new(),
+        })
+    }
+
+    fn allocate_dylink_regions(
+        &mut self,
+        module: &Module,
+        info: &DylinkInfo,
+    ) -> Result<DylinkBases> {
+        use wasmparser::ImportSectionEntryType::*;
+        let (mut memory_min, mut table_min) = (0, 0);
+        for import in module.imports().iter().filter(|i| i.module == "env") {
+            match (import.field.as_str(), import.ty) {
+                ("memory", Memory(ty)) => memory_min = ty.limits.initial as usize,
+                ("__indirect_function_table", Table(ty)) => table_min = ty.limits.initial as usize,
+                _ => (),
+            }
+        }
+        let state = self.dylink.as_ref().unwrap();
+        let memory_base = dylink::align_to(state.memory_end, info.memory_alignment);
+        let memory_end = memory_base.checked_add(info.memory_size).ok_or(
+            StoreError::FailedToAllocateLibrary("memory region exceeds 4GiB".to_string()),
+        )?;
+        let memory = self.mems.get_global(state.memory);
+        let pages = (memory_end as usize).div_ceil(WASM_PAGE_SIZE);
+        let pages = std::cmp::max(pages, memory_min);
+        let grow_pages = pages.saturating_sub(memory.borrow().page_count());
+        let grow_bytes = grow_pages.saturating_mul(WASM_PAGE_SIZE);
+        self.check_memory_growth(grow_bytes)?;
+        memory
+            .borrow_mut()
+            .grow(grow_pages)
+            .map_err(|err| StoreError::FailedToAllocateLibrary(format!("{:?}", err)))?;
+
+        let mut table = self.tables.get_global(state.table).borrow_mut();
+        let table_base = dylink::align_to(table.buffer_len() as u32, info.table_alignment);
+        let table_len = std::cmp::max((table_base + info.table_size) as usize, table_min);
+        if table_len > table.buffer_len() {
+            self.limits.check_table_elements(table_len)?;
+            let delta = table_len - table.buffer_len();
+            table
+                .grow(delta)
+                .map_err(|err| StoreError::FailedToAllocateLibrary(err.to_string()))?;
+        }
+        self.dylink.as_mut().unwrap().memory_end = memory_end;
+        Ok(DylinkBases {
+            memory_base,
+            table_base,
+        })
+    }
+
+    /// Whether the dynamic linker provides `import` instead of a module
+    /// registered under the import module name
+    fn is_dylink_import(import: &ImportEntry) -> bool {
+        match import.module.as_str() {
+            "env" => match import.ty {
+                wasmparser::ImportSectionEntryType::Function(_) => true,
+                _ => match import.field.as_str() {
+                    "memory"
+                    | "__indirect_function_table"
+                    | "__stack_pointer"
+                    | "__memory_base"
+                    | "__table_base" => true,
+                    _ => false,
+                },
+            },
+            "GOT.mem" | "GOT.func" => true,
+            _ => false,
+        }
+    }
+
+    /// Link an import of a shared library provided by the dynamic linker.
+    /// Returns false if the import should be resolved as usual.
+    fn load_dylink_import(
+        &mut self,
+        module_index: ModuleIndex,
+        import: &ImportEntry,
+        types: &[FuncType],
+        bases: &DylinkBases,
+    ) -> Result<bool> {
+        use wasmparser::ImportSectionEntryType::*;
+        if !Self::is_dylink_import(import) {
+            return Ok(false);
+        }
+        let state = self.dylink.as_mut().unwrap();
+        match (import.module.as_str(), import.field.as_str(), import.ty) {
+            ("env", "memory", Memory(_)) => {
+                self.mems.link(state.memory, module_index);
+            }
+            ("env", "__indirect_function_table", Table(_)) => {
+                self.tables.link(state.table, module_index);
+            }
+            ("env", "__stack_pointer", Global(_)) => {
+                self.globals.link(state.stack_pointer, module_index);
+            }
+            ("env", "__memory_base", Global(ty)) | ("env", "__table_base", Global(ty)) => {
+                let base = if import.field == "__memory_base" {
+                    bases.memory_base
+                } else {
+                    bases.table_base
+                };
+                let global = GlobalInstance::new(Value::I32(base as i32), ty);
+                let addr = self.globals.push_global(RefCell::new(global));
+                self.globals.link(addr, module_index);
+            }
+            ("GOT.mem", symbol, Global(ty)) | ("GOT.func", symbol, Global(ty)) => {
+                let placeholder = Value::zero(ty.content_type)?;
+                let got = if import.module == "GOT.mem" {
+                    &mut state.got_mem
+                } else {
+                    &mut state.got_func
+                };
+                let globals = &mut self.globals;
+                let entry = got.entry(symbol.to_string()).or_insert_with(|| {
+                    let global = GlobalInstance::new(placeholder, ty);
+                    GotEntry {
+                        global: globals.push_global(RefCell::new(global)),
+                        resolved: false,
+                    }
+                });
+                self.globals.link(entry.global, module_index);
+            }
+            ("env", symbol, Function(type_index)) => {
+                if self.is_resolvable(import) {
+                    return Ok(false);
+                }
+                let func_ty = types
+                    .get(type_index as usize)
+                    .ok_or(StoreError::UnknownType(type_index))?
+                    .clone();
+                match self.find_dylink_func(symbol) {
+                    Some(func_addr) => {
+                        let exec_addr = self.resolve_dylink_func(func_addr, symbol, &func_ty)?;
+                        self.funcs.link(exec_addr, module_index);
+                    }
+                    None => {
+                        // Calls trap until a library exporting the symbol is loaded
+                        let (module, field) = (import.module.clone(), import.field.clone());
+                        let body = HostFuncBody::stub(
+                            func_ty.clone(),
+                            module.clone(),
+                            field.clone(),
+                            ImportStub::Trap,
+                        );
+                        let instance = HostFunctionInstance::new(func_ty, module, field, body);
+                        let exec_addr = self.funcs.push_global(FunctionInstance::Host(instance));
+                        let addr = self.funcs.link(exec_addr, module_index);
+                        let state = self.dylink.as_mut().unwrap();
+                        state.pending_funcs.push((addr, symbol.to_string()));
+                    }
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Find a function exported by a loaded library
+    fn find_dylink_func(&self, symbol: &str) -> Option<FuncAddr> {
+        let state = self.dylink.as_ref()?;
+        state.libraries.iter().find_map(|index| {
+            let module = self.module(*index)?.defined()?;
+            module.exported_func(symbol.to_string()).ok()?
+        })
+    }
+
+    /// Find the address of a data symbol exported by a loaded library.
+    /// Libraries export data symbols relative to their `__memory_base`.
+    fn find_dylink_data(&self, symbol: &str) -> Option<u32> {
+        let state = self.dylink.as_ref()?;
+        state.libraries.iter().find_map(|index| {
+            let module = self.module(*index)?.defined()?;
+            let addr = module.exported_global(symbol.to_string()).ok()??;
+            let offset = match self.global(addr).borrow().value() {
+                Value::I32(offset) => offset as u32,
+                _ => return None,
+            };
+            Some(state.memory_bases[index].wrapping_add(offset))
+        })
+    }
+
+    fn resolve_dylink_func(
+        &self,
+        func_addr: FuncAddr,
+        symbol: &str,
+        func_ty: &FuncType,
+    ) -> Result<ExecutableFuncAddr> {
+        let err = || StoreError::UndefinedFunction("env".to_string(), symbol.to_string());
+        let exec_addr = self.funcs.resolve(func_addr).ok_or_else(err)?;
+        let actual_func_ty = self.funcs.get_global(exec_addr).ty();
+        if !eq_func_type(actual_func_ty, func_ty) {
+            Err(StoreError::IncompatibleImportFuncType(
+                symbol.to_string(),
+                func_ty.clone(),
+                actual_func_ty.clone(),
+            ))?;
+        }
+        Ok(exec_addr)
+    }
+
+    /// Fill GOT entries and bind pending function imports with the symbols
+    /// exported by the libraries loaded so far
+    fn resolve_dylink_symbols(&mut self) -> Result<()> {
+        let state = self.dylink.as_ref().unwrap();
+        let got_mem = state
+            .got_mem
+            .iter()
+            .filter(|(_, entry)| !entry.resolved)
+            .filter_map(|(symbol, entry)| {
+                let address = self.find_dylink_data(symbol)?;
+                Some((symbol.clone(), entry.global, address))
+            })
+            .collect::<Vec<_>>();
+        let got_func = state
+            .got_func
+            .iter()
+            .filter(|(_, entry)| !entry.resolved)
+            .filter_map(|(symbol, entry)| {
+                let func_addr = self.find_dylink_func(symbol)?;
+                Some((symbol.clone(), entry.global, func_addr))
+            })
+            .collect::<Vec<_>>();
+        let pending_funcs = state
+            .pending_funcs
+            .iter()
+            .filter_map(|(addr, symbol)| {
+                let func_addr = self.find_dylink_func(symbol)?;
+                Some((*addr, symbol.clone(), func_addr))
+            })
+            .collect::<Vec<_>>();
+
+        for (symbol, global, address) in got_mem {
+            let mut global = self.globals.get_global(global).borrow_mut();
+            global.set_value(Value::I32(address as i32));
+            let state = self.dylink.as_mut().unwrap();
+            state.got_mem.get_mut(&symbol).unwrap().resolved = true;
+        }
+        for (symbol, global, func_addr) in got_func {
+            // Functions get a table slot when their address is first taken
+            let state = self.dylink.as_mut().unwrap();
+            let mut table = self.tables.get_global(state.table).borrow_mut();
+            let index = table.buffer_len();
+            self.limits.check_table_elements(index + 1)?;
+            table
+                .grow(1)
+                .and_then(|_| table.initialize(index, vec![Some(func_addr)]))
+                .map_err(|err| StoreError::FailedToAllocateLibrary(err.to_string()))?;
+            let mut global = self.globals.get_global(global).borrow_mut();
+            global.set_value(Value::I32(index as i32));
+            state.got_func.get_mut(&symbol).unwrap().resolved = true;
+        }
+        for (addr, symbol, func_addr) in pending_funcs {
+            let func_ty = self.funcs.get(addr).unwrap().0.ty().clone();
+            let exec_addr = self.resolve_dylink_func(func_addr, &symbol, &func_ty)?;
+            self.funcs.relink(exec_addr, addr);
+            let state = self.dylink.as_mut().unwrap();
+            state
+                .pending_funcs
+                .retain(|(_, pending)| *pending != symbol);
+        }
+        Ok(())
+    }
+
+    fn run_dylink_initializers(&mut self, module_index: ModuleIndex) -> Result<()> {
+        let module = self
+            .module(module_index)
+            .ok_or(StoreError::ModuleNotLoaded(module_index))?
+            .defined()
+            .unwrap();
+        let exported = |name: &str| module.exported_func(name.to_string()).ok().flatten();
+        let mut initializers = vec!["__wasm_apply_data_relocs", "__wasm_apply_relocs"];
+        if exported("_start").is_none() {
+            initializers.push("__wasm_call_ctors");
+            initializers.push("__post_instantiate");
+        }
+        let initializers = initializers
+            .into_iter()
+            .filter_map(|name| exported(name).map(|addr| (name, addr)))
+            .collect::<Vec<_>>();
+        for (name, addr) in initializers {
+            simple_invoke_func(addr, vec![], self)
+                .map_err(|err| StoreError::FailedToInitializeLibrary(name.to_string(), err))?;
+        }
+        Ok(())
+    }
+}
+
 impl std::fmt::Debug for Store {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "")
@@ -1,4 +1,5 @@
 use super::address::FuncAddr;
+use wasmparser::{ResizableLimits, TableType, Type};
 
 #[derive(Debug)]
 pub enum Error {
@@ -7,6 +8,7 @@ pub enum Error {
         /* memory size */ usize,
     ),
     UninitializedElement(usize),
+    GrowOverMaximumSize(usize),
 }
 
 impl std::fmt::Display for Error {
@@ -25,6 +27,9 @@ impl std::fmt::Display for Error {
             Self::UninitializedElement(addr) => {
                 write!(f, "uninitialized element, try to access {}", addr)
             }
+            Self::GrowOverMaximumSize(max) => {
+                write!(f, "table can't grow over maximum size {}", max)
+            }
         }
     }
 }
@@ -62,10 +67,36 @@ impl TableInstance {
         Ok(())
     }
 
+    pub fn grow(&mut self, n: usize) -> Result<()> {
+        let len = self.buffer_len() + n;
+        if let Some(max) = self.max {
+            if len > max {
+                return Err(Error::GrowOverMaximumSize(max));
+            }
+        }
+        self.buffer.resize(len, None);
+        Ok(())
+    }
+
     pub fn buffer_len(&self) -> usize {
         self.buffer.len()
     }
 
+    pub fn contents(&self) -> &[Option<FuncAddr>] {
+        &self.buffer
+    }
+
+    /// Type with the current size as the minimum
+    pub fn ty(&self) -> TableType {
+        TableType {
+            element_type: Type::AnyFunc,
+            limits: ResizableLimits {
+                initial: self.buffer_len() as u32,
+                maximum: self.max.map(|max| max as u32),
+            },
+        }
+    }
+
     pub fn get_at(&self, index: usize) -> Result<FuncAddr> {
         self.buffer
             .get(index)
@@ -0,0 +1,496 @@
+use super::executor::{eval_const_expr_with, ConstExprError, ConstValue};
+use super::module::{decode_init_expr, Module};
+use super::value::Value;
+use std::ops::Range;
+use wasmparser::{
+    BinaryReader, BinaryReaderError, DataKind, ElementKind, GlobalType, ImportSectionEntryType,
+    InitExpr, ModuleReader, ParserInput, ParserState, SectionCode, Type, ValidatingParser,
+    WasmDecoder,
+};
+
+/// An error found while decoding or validating a module binary.
+///
+/// `Malformed` means the binary couldn't be decoded, and `Invalid` means it
+/// was decoded but violates the validation rules of the spec.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    Malformed {
+        message: String,
+        /// Byte offset in the module binary
+        offset: usize,
+        /// Index in the function index space, if the error is in a function body
+        func_index: Option<u32>,
+    },
+    Invalid {
+        message: String,
+        offset: usize,
+        func_index: Option<u32>,
+    },
+}
+
+impl ValidationError {
+    pub fn message(&self) -> &str {
+        match self {
+            Self::Malformed { message, .. } | Self::Invalid { message, .. } => message,
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::Malformed { offset, .. } | Self::Invalid { offset, .. } => *offset,
+        }
+    }
+
+    pub fn func_index(&self) -> Option<u32> {
+        match self {
+            Self::Malformed { func_index, .. } | Self::Invalid { func_index, .. } => *func_index,
+        }
+    }
+
+    pub fn is_malformed(&self) -> bool {
+        match self {
+            Self::Malformed { .. } => true,
+            Self::Invalid { .. } => false,
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = if self.is_malformed() {
+            "malformed"
+        } else {
+            "invalid"
+        };
+        write!(
+            f,
+            "{} module: {} at offset 0x{:x}",
+            kind,
+            self.message(),
+            self.offset()
+        )?;
+        if let Some(func_index) = self.func_index() {
+            write!(f, " in func[{}]", func_index)?;
+        }
+        Ok(())
+    }
+}
+
+// The validator reports these while parsing, but the spec treats them as
+// decoding errors.
+const MALFORMED_VALIDATOR_MESSAGES: &[&str] = &[
+    "section out of order",
+    "function and code section have inconsistent lengths",
+    "bad wasm file version",
+];
+
+/// Decode `bytes` and validate the result
+pub fn decode_validated(bytes: &[u8]) -> Result<Module, ValidationError> {
+    decode_validated_with(bytes, true)
+}
+
+/// Decode `bytes` and validate everything but function bodies, which are
+/// decoded on first call instead. An invalid body is reported only when it
+/// fails to decode, and may otherwise trap or misbehave while running.
+pub fn decode_validated_sections(bytes: &[u8]) -> Result<Module, ValidationError> {
+    decode_validated_with(bytes, false)
+}
+
+fn decode_validated_with(bytes: &[u8], bodies: bool) -> Result<Module, ValidationError> {
+    let module = Module::from_bytes(bytes).map_err(|err| {
+        let (message, offset) = match err.downcast_ref::<BinaryReaderError>() {
+            Some(err) => (err.message().to_string(), err.offset()),
+            None => (err.to_string(), 0),
+        };
+        ValidationError::Malformed {
+            func_index: func_index_at(bytes, offset),
+            message,
+            offset,
+        }
+    })?;
+    let extended_exprs = check_extended_const_exprs(bytes)?;
+    let lowered = if extended_exprs.is_empty() {
+        None
+    } else {
+        Some(lower_const_exprs(bytes, &extended_exprs))
+    };
+    let validated = match lowered {
+        Some((ref lowered, _)) => lowered.as_slice(),
+        None => bytes,
+    };
+    validate_binary(validated, bodies).map_err(|err| {
+        let message = err.message().to_string();
+        let offset = match lowered {
+            Some((_, ref chunks)) => original_offset(chunks, err.offset()),
+            None => err.offset(),
+        };
+        let func_index = func_index_at(bytes, offset);
+        if MALFORMED_VALIDATOR_MESSAGES.contains(&message.as_str()) {
+            ValidationError::Malformed {
+                message,
+                offset,
+                func_index,
+            }
+        } else {
+            ValidationError::Invalid {
+                message,
+                offset,
+                func_index,
+            }
+        }
+    })?;
+    Ok(module)
+}
+
+/// `wasmparser::validate`, optionally skipping function bodies
+fn validate_binary(bytes: &[u8], bodies: bool) -> Result<(), BinaryReaderError> {
+    if bodies {
+        return wasmparser::validate(bytes, None);
+    }
+    let mut parser = ValidatingParser::new(bytes, None);
+    let mut input = ParserInput::Default;
+    loop {
+        match *parser.read_with_input(input) {
+            ParserState::EndWasm => return Ok(()),
+            ParserState::Error(ref err) => return Err(err.clone()),
+            ParserState::BeginFunctionBody { .. } => input = ParserInput::SkipFunctionBody,
+            _ => input = ParserInput::Default,
+        }
+    }
+}
+
+/// An init expression using instructions of the extended-const proposal
+struct ExtendedConstExpr {
+    range: Range<usize>,
+    ty: Type,
+}
+
+/// The validator of wasmparser predates the extended-const proposal, so
+/// init expressions with more than one instruction are checked here instead
+fn check_extended_const_exprs(bytes: &[u8]) -> Result<Vec<ExtendedConstExpr>, ValidationError> {
+    let mut exprs = Vec::new();
+    let mut globals: Vec<GlobalType> = Vec::new();
+    let mut check = |init_expr: &InitExpr, ty: Type, globals: &[GlobalType]| {
+        let range = init_expr.get_binary_reader().range();
+        let range = range.start..range.end;
+        let insts = match decode_init_expr(init_expr) {
+            Ok(insts) => insts,
+            // Decoding errors are reported by the validator
+            Err(_) => return Ok(()),
+        };
+        // Instructions end with `end`
+        if insts.len() <= 2 {
+            return Ok(());
+        }
+        let global = |index: u32| match globals.get(index as usize) {
+            None => Err(ConstExprError::UnknownGlobal(index)),
+            Some(global) if global.mutable => Err(ConstExprError::MutableGlobal(index)),
+            Some(global) => {
+                Value::zero(global.content_type).map_err(|_| ConstExprError::ReferenceValue)
+            }
+        };
+        let actual = match eval_const_expr_with(&insts, global) {
+            Ok(ConstValue::Num(value)) => value.value_type(),
+            Ok(ConstValue::FuncRef(_)) => Type::AnyFunc,
+            Err(err) => {
+                return Err(ValidationError::Invalid {
+                    message: err.to_string(),
+                    offset: range.start,
+                    func_index: None,
+                })
+            }
+        };
+        if actual != ty {
+            return Err(ValidationError::Invalid {
+                message: ConstExprError::TypeMismatch {
+                    expected: ty,
+                    actual,
+                }
+                .to_string(),
+                offset: range.start,
+                func_index: None,
+            });
+        }
+        exprs.push(ExtendedConstExpr { range, ty });
+        Ok(())
+    };
+
+    // Malformed sections are reported by the validator, so stop checking
+    // at the first reader error
+    let mut reader = match ModuleReader::new(bytes) {
+        Ok(reader) => reader,
+        Err(_) => return Ok(Vec::new()),
+    };
+    while !reader.eof() {
+        let section = match reader.read() {
+            Ok(section) => section,
+            Err(_) => break,
+        };
+        match section.code {
+            SectionCode::Import => {
+                for entry in section
+                    .get_import_section_reader()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Result::ok)
+                {
+                    if let ImportSectionEntryType::Global(ty) = entry.ty {
+                        globals.push(ty);
+                    }
+                }
+            }
+            SectionCode::Global => {
+                for entry in section
+                    .get_global_section_reader()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Result::ok)
+                {
+                    check(&entry.init_expr, entry.ty.content_type, &globals)?;
+                    globals.push(entry.ty);
+                }
+            }
+            SectionCode::Element => {
+                for entry in section
+                    .get_element_section_reader()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Result::ok)
+                {
+                    if let ElementKind::Active { init_expr, .. } = entry.kind {
+                        check(&init_expr, Type::I32, &globals)?;
+                    }
+                }
+            }
+            SectionCode::Data => {
+                for entry in section
+                    .get_data_section_reader()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Result::ok)
+                {
+                    if let DataKind::Active { init_expr, .. } = entry.kind {
+                        check(&init_expr, Type::I32, &globals)?;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(exprs)
+}
+
+/// A run of bytes copied from the original binary to the lowered one
+struct Chunk {
+    original: usize,
+    lowered: usize,
+    len: usize,
+}
+
+/// Replace checked init expressions with a single constant of the same type
+/// so that wasmparser can validate the rest of the module
+fn lower_const_exprs(bytes: &[u8], exprs: &[ExtendedConstExpr]) -> (Vec<u8>, Vec<Chunk>) {
+    let mut lowered = bytes[..8].to_vec();
+    let mut chunks = vec![Chunk {
+        original: 0,
+        lowered: 0,
+        len: 8,
+    }];
+    let mut position = 8;
+    while position < bytes.len() {
+        let mut reader = BinaryReader::new_with_offset(&bytes[position + 1..], position + 1);
+        let size = match reader.read_var_u32() {
+            Ok(size) => size as usize,
+            Err(_) => break,
+        };
+        let start = reader.original_position();
+        let end = std::cmp::min(start + size, bytes.len());
+
+        let mut content = Vec::new();
+        let mut content_chunks = Vec::new();
+        let mut copied = start;
+        for expr in exprs
+            .iter()
+            .filter(|expr| start <= expr.range.start && expr.range.end <= end)
+        {
+            content_chunks.push((copied, content.len(), expr.range.start - copied));
+            content.extend_from_slice(&bytes[copied..expr.range.start]);
+            content.extend_from_slice(&const_expr_bytes(expr.ty));
+            copied = expr.range.end;
+        }
+        content_chunks.push((copied, content.len(), end - copied));
+        content.extend_from_slice(&bytes[copied..end]);
+
+        chunks.push(Chunk {
+            original: position,
+            lowered: lowered.len(),
+            len: 1,
+        });
+        lowered.push(bytes[position]);
+        write_var_u32(&mut lowered, content.len() as u32);
+        let base = lowered.len();
+        for (original, offset, len) in content_chunks {
+            chunks.push(Chunk {
+                original,
+                lowered: base + offset,
+                len,
+            });
+        }
+        lowered.extend_from_slice(&content);
+        position = end;
+    }
+    (lowered, chunks)
+}
+
+fn const_expr_bytes(ty: Type) -> Vec<u8> {
+    const END: u8 = 0x0b;
+    match ty {
+        Type::I32 => vec![0x41, 0x00, END],
+        Type::I64 => vec![0x42, 0x00, END],
+        Type::F32 => vec![0x43, 0x00, 0x00, 0x00, 0x00, END],
+        Type::F64 => vec![0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, END],
+        _ => vec![0xd0, END],
+    }
+}
+
+fn write_var_u32(buffer: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buffer.push(byte);
+            return;
+        }
+        buffer.push(byte | 0x80);
+    }
+}
+
+/// Map an offset in the lowered binary back to the original binary
+fn original_offset(chunks: &[Chunk], offset: usize) -> usize {
+    let mut result = 0;
+    for chunk in chunks {
+        if chunk.lowered > offset {
+            break;
+        }
+        result = if offset < chunk.lowered + chunk.len {
+            chunk.original + (offset - chunk.lowered)
+        } else {
+            chunk.original + chunk.len
+        };
+    }
+    result
+}
+
+/// Check that `bytes` is a well-formed and valid module
+pub fn validate(bytes: &[u8]) -> Result<(), ValidationError> {
+    decode_validated(bytes).map(|_| ())
+}
+
+/// Check `bytes` like `validate` except for function bodies
+pub fn validate_sections(bytes: &[u8]) -> Result<(), ValidationError> {
+    decode_validated_sections(bytes).map(|_| ())
+}
+
+/// Find the function whose body contains `offset`
+fn func_index_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    let mut reader = ModuleReader::new(bytes).ok()?;
+    let mut imported_funcs = 0;
+    while !reader.eof() {
+        let section = reader.read().ok()?;
+        match section.code {
+            SectionCode::Import => {
+                for entry in section.get_import_section_reader().ok()? {
+                    if let ImportSectionEntryType::Function(_) = entry.ok()?.ty {
+                        imported_funcs += 1;
+                    }
+                }
+            }
+            SectionCode::Code => {
+                let section = section.get_code_section_reader().ok()?;
+                for (index, body) in section.into_iter().enumerate() {
+                    let range = body.ok()?.range();
+                    if range.start <= offset && offset <= range.end {
+                        return Some(imported_funcs + index as u32);
+                    }
+                }
+                return None;
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (import "env" "f" (func))
+    // (global i32 (i32.add (i32.const 1) (i32.const 2)))
+    // (func)
+    // (func (drop (i32.const 0)) (drop))
+    #[rustfmt::skip]
+    const INVALID_AFTER_EXTENDED_CONST: [u8; 54] = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+        0x02, 0x09, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x01, 0x66, 0x00, 0x00,
+        0x03, 0x03, 0x02, 0x00, 0x00,
+        0x06, 0x09, 0x01, 0x7f, 0x00, 0x41, 0x01, 0x41, 0x02, 0x6a, 0x0b,
+        0x0a, 0x0b, 0x02,
+        0x02, 0x00, 0x0b,
+        // The second `drop` at 52 pops from an empty stack
+        0x06, 0x00, 0x41, 0x00, 0x1a, 0x1a, 0x0b,
+    ];
+
+    #[test]
+    fn remap_error_after_lowered_expr() {
+        let err = validate(&INVALID_AFTER_EXTENDED_CONST).unwrap_err();
+        assert_eq!(
+            err,
+            ValidationError::Invalid {
+                message: "type mismatch: not enough operands".to_string(),
+                offset: 52,
+                func_index: Some(2),
+            }
+        );
+    }
+
+    #[test]
+    fn skip_invalid_bodies() {
+        assert!(validate_sections(&INVALID_AFTER_EXTENDED_CONST).is_ok());
+        // Sections other than bodies are still checked
+        let mut bytes = INVALID_AFTER_EXTENDED_CONST;
+        bytes[27] = 0x03;
+        let err = validate_sections(&bytes).unwrap_err();
+        assert!(err.is_malformed());
+    }
+
+    #[test]
+    fn lowered_offsets_map_back() {
+        let bytes = &INVALID_AFTER_EXTENDED_CONST;
+        let exprs = check_extended_const_exprs(bytes).unwrap();
+        assert_eq!(exprs.len(), 1);
+        assert_eq!(exprs[0].range, 35..41);
+        let (lowered, chunks) = lower_const_exprs(bytes, &exprs);
+        // The global section shrinks by three bytes
+        assert_eq!(lowered.len(), bytes.len() - 3);
+        assert_eq!(
+            &lowered[30..39],
+            &[0x06, 0x06, 0x01, 0x7f, 0x00, 0x41, 0x00, 0x0b, 0x0a]
+        );
+        for offset in 0..35 {
+            assert_eq!(original_offset(&chunks, offset), offset);
+        }
+        // Offsets in the lowered expression map to its start, and what
+        // follows it shifts by three bytes
+        for offset in 35..38 {
+            assert_eq!(original_offset(&chunks, offset), 35);
+        }
+        for offset in 38..lowered.len() {
+            assert_eq!(original_offset(&chunks, offset), offset + 3);
+            assert_eq!(lowered[offset], bytes[offset + 3]);
+        }
+    }
+}
@@ -9,6 +9,17 @@ pub enum Value {
 }
 
 impl Value {
+    /// The default value of locals and stubbed imports
+    pub fn zero(ty: Type) -> Result<Self, Error> {
+        match ty {
+            Type::I32 => Ok(Value::I32(0)),
+            Type::I64 => Ok(Value::I64(0)),
+            Type::F32 => Ok(Value::F32(0)),
+            Type::F64 => Ok(Value::F64(0)),
+            _ => Err(Error::UnsupportedType(ty)),
+        }
+    }
+
     pub fn value_type(&self) -> Type {
         match self {
             Value::I32(_) => Type::I32,
@@ -348,14 +359,19 @@ pub enum Error {
     ZeroDivision,
     InvalidConversionToInt,
     IntegerOverflow,
+    /// Values of the type can't be represented yet
+    UnsupportedType(Type),
 }
 
+impl std::error::Error for Error {}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::ZeroDivision => write!(f, "integer divide by zero"),
             Self::InvalidConversionToInt => write!(f, "invalid conversion to integer"),
             Self::IntegerOverflow => write!(f, "integer overflow"),
+            Self::UnsupportedType(ty) => write!(f, "unsupported value type {:?}", ty),
         }
     }
 }
@@ -5,837 +5,809 @@ use wasi_common::{WasiCtx, WasiCtxBuilder};
 use wasminspect_vm::*;
 use wasmparser::{FuncType, Type};
 
+mod preview0;
+mod signatures;
+mod stdio;
+mod trace;
+mod vfs;
+pub use stdio::{CapturedOutput, OutputRedirect};
+pub use trace::WasiTrace;
+pub use vfs::{VirtualFile, VirtualFs};
+
 pub struct WasiContext {
     ctx: RefCell<WasiCtx>,
+    trace: RefCell<WasiTrace>,
+    vfs: Option<RefCell<VirtualFs>>,
+    stdio: RefCell<stdio::Stdio>,
+}
+
+impl WasiContext {
+    /// Output of the program to the streams redirected with
+    /// `OutputRedirect::Capture`
+    pub fn captured_output(&self) -> std::cell::Ref<'_, CapturedOutput> {
+        std::cell::Ref::map(self.stdio.borrow(), |stdio| stdio.captured())
+    }
+
+    /// The in-memory filesystem if the program runs on it
+    pub fn vfs(&self) -> Option<std::cell::Ref<'_, VirtualFs>> {
+        self.vfs.as_ref().map(|vfs| vfs.borrow())
+    }
+
+    /// Change which calls are logged while the program is running
+    pub fn set_trace(&self, trace: WasiTrace) {
+        *self.trace.borrow_mut() = trace;
+    }
+
+    /// Write `bytes` to stdout (1) or stderr (2) of the program as if it
+    /// called `fd_write`, following the redirection of the stream. Returns
+    /// the errno.
+    pub fn write_stdio(&self, fd: u32, bytes: &[u8]) -> u16 {
+        let (args, mut mem) = stdio::fd_write_call(fd, bytes);
+        match self.stdio.borrow_mut().call("fd_write", &args, &mut mem) {
+            Some(errno) => errno,
+            // The iovec and the written size laid out by `fd_write_call`
+            None => unsafe { fd_write(&mut self.ctx.borrow_mut(), &mut mem, fd, 0, 1, 8) },
+        }
+    }
 }
 
+/// What the program sees through WASI
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WasiConfig {
+    /// Arguments including the program name
+    pub args: Vec<String>,
+    pub envs: Vec<(String, String)>,
+    /// Host directories and the guest paths they are preopened as
+    pub preopen_dirs: Vec<(String, String)>,
+    pub trace: WasiTrace,
+    /// Serve files from an in-memory filesystem seeded with this directory
+    /// or tar archive instead of the host filesystem
+    pub vfs: Option<String>,
+    /// Read stdin from this host file instead of the debugger's stdin
+    pub stdin: Option<String>,
+    pub stdout: OutputRedirect,
+    pub stderr: OutputRedirect,
+}
+
+#[derive(Debug)]
+pub enum WasiError {
+    FailedToOpenDir(String, std::io::Error),
+    FailedToOpenFile(String, std::io::Error),
+    FailedToBuildContext(String),
+    FailedToLoadVfs(String, std::io::Error),
+    /// Host directories can't be preopened with the in-memory filesystem
+    PreopenDirWithVfs,
+}
+
+impl std::error::Error for WasiError {}
+
+impl std::fmt::Display for WasiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FailedToOpenDir(dir, err) => write!(f, "failed to open {}: {}", dir, err),
+            Self::FailedToOpenFile(file, err) => write!(f, "failed to open {}: {}", file, err),
+            Self::FailedToBuildContext(err) => write!(f, "failed to build WASI context: {}", err),
+            Self::FailedToLoadVfs(path, err) => write!(f, "failed to load {}: {}", path, err),
+            Self::PreopenDirWithVfs => write!(
+                f,
+                "host directories can't be preopened with the in-memory filesystem"
+            ),
+        }
+    }
+}
+
+/// Instantiate WASI without arguments, environment variables and
+/// preopened directories
 pub fn instantiate_wasi() -> (WasiContext, HashMap<String, HostValue>) {
-    let builder = WasiCtxBuilder::new().inherit_stdio();
-    let wasi_ctx = builder.build().unwrap();
-    let mut module: HashMap<String, HostValue> = HashMap::new();
+    instantiate_wasi_with(&WasiConfig::default()).unwrap()
+}
 
-    fn define_wasi_fn<
+pub fn instantiate_wasi_with(
+    config: &WasiConfig,
+) -> Result<(WasiContext, HashMap<String, HostValue>), WasiError> {
+    let vfs = match config.vfs {
+        Some(_) if !config.preopen_dirs.is_empty() => return Err(WasiError::PreopenDirWithVfs),
+        Some(ref path) => Some(
+            VirtualFs::load(path).map_err(|err| WasiError::FailedToLoadVfs(path.clone(), err))?,
+        ),
+        None => None,
+    };
+    let mut builder = WasiCtxBuilder::new().inherit_stdio().args(&config.args);
+    for (key, value) in &config.envs {
+        builder = builder.env(key, value);
+    }
+    if let Some(ref path) = config.stdin {
+        let file = std::fs::File::open(path)
+            .map_err(|err| WasiError::FailedToOpenFile(path.clone(), err))?;
+        builder = builder.stdin(file);
+    }
+    if let OutputRedirect::File(ref path) = config.stdout {
+        let file = std::fs::File::create(path)
+            .map_err(|err| WasiError::FailedToOpenFile(path.clone(), err))?;
+        builder = builder.stdout(file);
+    }
+    if let OutputRedirect::File(ref path) = config.stderr {
+        let file = std::fs::File::create(path)
+            .map_err(|err| WasiError::FailedToOpenFile(path.clone(), err))?;
+        builder = builder.stderr(file);
+    }
+    for (host_path, guest_path) in &config.preopen_dirs {
+        let dir = std::fs::File::open(host_path)
+            .map_err(|err| WasiError::FailedToOpenDir(host_path.clone(), err))?;
+        builder = builder.preopened_dir(dir, guest_path);
+    }
+    let wasi_ctx = builder
+        .build()
+        .map_err(|err| WasiError::FailedToBuildContext(err.to_string()))?;
+    let context = WasiContext {
+        ctx: RefCell::new(wasi_ctx),
+        trace: RefCell::new(config.trace.clone()),
+        vfs: vfs.map(RefCell::new),
+        stdio: RefCell::new(stdio::Stdio::new(
+            config.stdout.clone(),
+            config.stderr.clone(),
+        )),
+    };
+    Ok((context, host_module(Snapshot::Preview1)))
+}
+
+/// Versions of the WASI API
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Snapshot {
+    /// `wasi_snapshot_preview1`
+    Preview1,
+    /// `wasi_unstable`, also known as preview0
+    Preview0,
+}
+
+impl Snapshot {
+    /// Name of the module programs import the functions from
+    pub fn module_name(&self) -> &'static str {
+        match self {
+            Self::Preview1 => "wasi_snapshot_preview1",
+            Self::Preview0 => "wasi_unstable",
+        }
+    }
+}
+
+struct WasiModule {
+    snapshot: Snapshot,
+    funcs: HashMap<String, HostValue>,
+}
+
+impl WasiModule {
+    /// Define `name` with its signature in the snapshot. Functions the
+    /// snapshot doesn't have are skipped.
+    fn define<F>(&mut self, name: &'static str, f: F)
+    where
         F: Fn(
                 &[WasmValue],
                 &mut Vec<WasmValue>,
                 &mut HostContext,
                 &mut WasiCtx,
             ) -> Result<(), Trap>
+            + Send
             + 'static,
-    >(
-        args_ty: Vec<Type>,
-        ret_ty: Option<Type>,
-        f: F,
-    ) -> HostValue {
+    {
+        let (params, results) = match signatures::find(self.snapshot, name) {
+            Some(signature) => signature,
+            None => return,
+        };
         let ty = FuncType {
             form: Type::Func,
-            params: args_ty.into_boxed_slice(),
-            returns: ret_ty
-                .map(|t| vec![t])
-                .unwrap_or_default()
-                .into_boxed_slice(),
+            params: params.into(),
+            returns: results.into(),
         };
-        return HostValue::Func(HostFuncBody::new(ty, move |args, ret, ctx, store| {
+        let snapshot = self.snapshot;
+        let func = HostFuncBody::new(ty, move |args, ret, ctx, store| {
             let wasi_ctx = store.get_embed_context::<WasiContext>().unwrap();
-            let mut wasi_ctx = wasi_ctx.ctx.borrow_mut();
-            f(args, ret, ctx, &mut *wasi_ctx)
-        }));
+            let adapted = match snapshot {
+                Snapshot::Preview1 => None,
+                Snapshot::Preview0 => Some(preview0::Adapted::new(name, args, ctx.mem)),
+            };
+            let args = adapted.as_ref().map_or(args, |adapted| &adapted.args);
+            // Calls served here rather than by wasi-common
+            let errno = wasi_ctx.stdio.borrow_mut().call(name, args, ctx.mem);
+            let errno = match wasi_ctx.vfs {
+                Some(ref vfs) if errno.is_none() => vfs.borrow_mut().call(name, args, ctx.mem),
+                _ => errno,
+            };
+            let result = match errno {
+                Some(errno) => {
+                    ret.push(WasmValue::I32(errno as i32));
+                    Ok(())
+                }
+                None => f(args, ret, ctx, &mut wasi_ctx.ctx.borrow_mut()),
+            };
+            if let Some(ref adapted) = adapted {
+                adapted.finish(ret, ctx.mem);
+            }
+            if wasi_ctx.trace.borrow().traces(name) {
+                trace::print_call(name, args, ret, &result, ctx, store);
+            }
+            result
+        });
+        self.funcs.insert(name.to_string(), HostValue::Func(func));
     }
+}
+
+/// WASI functions of `snapshot`. They run on the `WasiContext` embedded in
+/// the store.
+pub fn host_module(snapshot: Snapshot) -> HashMap<String, HostValue> {
+    let mut module = WasiModule {
+        snapshot,
+        funcs: HashMap::new(),
+    };
+
+    // Exiting the debugger process is not what the program wants, so stop
+    // the execution and report the status instead
+    module.define("proc_exit", |args, _ret, _ctx, _wasi_ctx| {
+        Err(Trap::Exit(args[0].as_i32().unwrap()))
+    });
 
-    let func = define_wasi_fn(vec![Type::I32], None, |args, _ret, _ctx, _wasi_ctx| {
+    module.define("args_get", |args, ret, ctx, wasi_ctx| {
         unsafe {
-            proc_exit(args[0].as_i32().unwrap() as u32);
+            let result = args_get(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
         }
         Ok(())
     });
-    module.insert("proc_exit".to_string(), func);
 
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = args_get(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("args_get".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = args_sizes_get(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("args_sizes_get".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, _| {
-            unsafe {
-                let result = clock_res_get(
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("clock_res_get".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I64, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, _| {
-            unsafe {
-                let result = clock_time_get(
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i64().unwrap() as u64,
-                    args[2].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("clock_time_get".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = environ_get(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("environ_get".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = environ_sizes_get(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("environ_sizes_get".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32],
-        Some(Type::I32),
-        |args, ret, _ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_close(wasi_ctx, args[0].as_i32().unwrap() as u32);
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_close".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_fdstat_get(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_fdstat_get".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, _ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_fdstat_set_flags(
-                    wasi_ctx,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u16,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_fdstat_set_flags".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_tell(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_tell".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I64, Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_seek(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i64().unwrap(),
-                    args[2].as_i32().unwrap() as u8,
-                    args[3].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_seek".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_prestat_get(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_prestat_get".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_prestat_dir_name(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_prestat_dir_name".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32, Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_read(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                    args[3].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_read".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32, Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_write(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                    args[3].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_write".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![
-            Type::I32,
-            Type::I32,
-            Type::I32,
-            Type::I32,
-            Type::I32,
-            Type::I64,
-            Type::I64,
-            Type::I32,
-            Type::I32,
-        ],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = path_open(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                    args[3].as_i32().unwrap() as u32,
-                    args[4].as_i32().unwrap() as u16,
-                    args[5].as_i64().unwrap() as u64,
-                    args[6].as_i64().unwrap() as u64,
-                    args[7].as_i32().unwrap() as u16,
-                    args[8].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("path_open".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, _wasi_ctx| {
-            unsafe {
-                let result = random_get(
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("random_get".to_string(), func);
+    module.define("args_sizes_get", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = args_sizes_get(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
 
-    let func = define_wasi_fn(vec![], Some(Type::I32), |_args, ret, _ctx, _wasi_ctx| {
+    module.define("clock_res_get", |args, ret, ctx, _| {
+        unsafe {
+            let result = clock_res_get(
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("clock_time_get", |args, ret, ctx, _| {
+        unsafe {
+            let result = clock_time_get(
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i64().unwrap() as u64,
+                args[2].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("environ_get", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = environ_get(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("environ_sizes_get", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = environ_sizes_get(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_close", |args, ret, _ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_close(wasi_ctx, args[0].as_i32().unwrap() as u32);
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_fdstat_get", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_fdstat_get(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_fdstat_set_flags", |args, ret, _ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_fdstat_set_flags(
+                wasi_ctx,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u16,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_tell", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_tell(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_seek", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_seek(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i64().unwrap(),
+                args[2].as_i32().unwrap() as u8,
+                args[3].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_prestat_get", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_prestat_get(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_prestat_dir_name", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_prestat_dir_name(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_read", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_read(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+                args[3].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_write", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_write(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+                args[3].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("path_open", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = path_open(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+                args[3].as_i32().unwrap() as u32,
+                args[4].as_i32().unwrap() as u16,
+                args[5].as_i64().unwrap() as u64,
+                args[6].as_i64().unwrap() as u64,
+                args[7].as_i32().unwrap() as u16,
+                args[8].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("random_get", |args, ret, ctx, _wasi_ctx| {
+        unsafe {
+            let result = random_get(
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("sched_yield", |_args, ret, _ctx, _wasi_ctx| {
         unsafe {
             let result = sched_yield();
             ret.push(WasmValue::I32(result as i32));
         }
         Ok(())
     });
-    module.insert("sched_yield".to_string(), func);
 
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32, Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = poll_oneoff(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                    args[3].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("poll_oneoff".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_filestat_get(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_filestat_get".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32, Type::I32, Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = path_filestat_get(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                    args[3].as_i32().unwrap() as u32,
-                    args[4].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("path_filestat_get".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = path_create_directory(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("path_create_directory".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = path_unlink_file(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("path_unlink_file".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I64, Type::I64],
-        Some(Type::I32),
-        |args, ret, _ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_allocate(
-                    wasi_ctx,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i64().unwrap() as u64,
-                    args[2].as_i64().unwrap() as u64,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_allocate".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I64, Type::I64, Type::I32],
-        Some(Type::I32),
-        |args, ret, _ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_advise(
-                    wasi_ctx,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i64().unwrap() as u64,
-                    args[2].as_i64().unwrap() as u64,
-                    args[3].as_i32().unwrap() as u8,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_advise".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32],
-        Some(Type::I32),
-        |args, ret, _ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_datasync(wasi_ctx, args[0].as_i32().unwrap() as u32);
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_datasync".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32],
-        Some(Type::I32),
-        |args, ret, _ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_sync(wasi_ctx, args[0].as_i32().unwrap() as u32);
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_sync".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I64, Type::I64],
-        Some(Type::I32),
-        |args, ret, _ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_fdstat_set_rights(
-                    wasi_ctx,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i64().unwrap() as u64,
-                    args[2].as_i64().unwrap() as u64,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_fdstat_set_rights".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I64],
-        Some(Type::I32),
-        |args, ret, _ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_filestat_set_size(
-                    wasi_ctx,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i64().unwrap() as u64,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_filestat_set_size".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I64, Type::I64, Type::I32],
-        Some(Type::I32),
-        |args, ret, _ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_filestat_set_times(
-                    wasi_ctx,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i64().unwrap() as u64,
-                    args[2].as_i64().unwrap() as u64,
-                    args[3].as_i32().unwrap() as u16,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_filestat_set_times".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32, Type::I32, Type::I64, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_pread(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                    args[3].as_i64().unwrap() as u64,
-                    args[4].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_pread".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32, Type::I32, Type::I64, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_pwrite(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                    args[3].as_i64().unwrap() as u64,
-                    args[4].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_pwrite".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32, Type::I32, Type::I64, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_readdir(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                    args[3].as_i64().unwrap() as u64,
-                    args[4].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_readdir".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, _ctx, wasi_ctx| {
-            unsafe {
-                let result = fd_renumber(
-                    wasi_ctx,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("fd_renumber".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![
-            Type::I32,
-            Type::I32,
-            Type::I32,
-            Type::I32,
-            Type::I64,
-            Type::I64,
-            Type::I32,
-        ],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = path_filestat_set_times(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                    args[3].as_i32().unwrap() as u32,
-                    args[4].as_i64().unwrap() as u64,
-                    args[5].as_i64().unwrap() as u64,
-                    args[6].as_i32().unwrap() as u16,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("path_filestat_set_times".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![
-            Type::I32,
-            Type::I32,
-            Type::I32,
-            Type::I32,
-            Type::I32,
-            Type::I32,
-            Type::I32,
-        ],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = path_link(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                    args[3].as_i32().unwrap() as u32,
-                    args[4].as_i32().unwrap() as u32,
-                    args[5].as_i32().unwrap() as u32,
-                    args[6].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("path_link".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![
-            Type::I32,
-            Type::I32,
-            Type::I32,
-            Type::I32,
-            Type::I32,
-            Type::I32,
-        ],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = path_readlink(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                    args[3].as_i32().unwrap() as u32,
-                    args[4].as_i32().unwrap() as u32,
-                    args[5].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("path_readlink".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = path_remove_directory(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("path_remove_directory".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![
-            Type::I32,
-            Type::I32,
-            Type::I32,
-            Type::I32,
-            Type::I32,
-            Type::I32,
-        ],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = path_rename(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                    args[3].as_i32().unwrap() as u32,
-                    args[4].as_i32().unwrap() as u32,
-                    args[5].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
-            Ok(())
-        },
-    );
-    module.insert("path_rename".to_string(), func);
-
-    let func = define_wasi_fn(
-        vec![Type::I32, Type::I32, Type::I32, Type::I32, Type::I32],
-        Some(Type::I32),
-        |args, ret, ctx, wasi_ctx| {
-            unsafe {
-                let result = path_symlink(
-                    wasi_ctx,
-                    ctx.mem,
-                    args[0].as_i32().unwrap() as u32,
-                    args[1].as_i32().unwrap() as u32,
-                    args[2].as_i32().unwrap() as u32,
-                    args[3].as_i32().unwrap() as u32,
-                    args[4].as_i32().unwrap() as u32,
-                );
-                ret.push(WasmValue::I32(result as i32));
-            }
+    module.define("poll_oneoff", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = poll_oneoff(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+                args[3].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_filestat_get", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_filestat_get(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("path_filestat_get", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = path_filestat_get(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+                args[3].as_i32().unwrap() as u32,
+                args[4].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("path_create_directory", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = path_create_directory(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("path_unlink_file", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = path_unlink_file(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_allocate", |args, ret, _ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_allocate(
+                wasi_ctx,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i64().unwrap() as u64,
+                args[2].as_i64().unwrap() as u64,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_advise", |args, ret, _ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_advise(
+                wasi_ctx,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i64().unwrap() as u64,
+                args[2].as_i64().unwrap() as u64,
+                args[3].as_i32().unwrap() as u8,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_datasync", |args, ret, _ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_datasync(wasi_ctx, args[0].as_i32().unwrap() as u32);
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_sync", |args, ret, _ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_sync(wasi_ctx, args[0].as_i32().unwrap() as u32);
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_fdstat_set_rights", |args, ret, _ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_fdstat_set_rights(
+                wasi_ctx,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i64().unwrap() as u64,
+                args[2].as_i64().unwrap() as u64,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_filestat_set_size", |args, ret, _ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_filestat_set_size(
+                wasi_ctx,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i64().unwrap() as u64,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_filestat_set_times", |args, ret, _ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_filestat_set_times(
+                wasi_ctx,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i64().unwrap() as u64,
+                args[2].as_i64().unwrap() as u64,
+                args[3].as_i32().unwrap() as u16,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_pread", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_pread(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+                args[3].as_i64().unwrap() as u64,
+                args[4].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_pwrite", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_pwrite(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+                args[3].as_i64().unwrap() as u64,
+                args[4].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_readdir", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_readdir(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+                args[3].as_i64().unwrap() as u64,
+                args[4].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("fd_renumber", |args, ret, _ctx, wasi_ctx| {
+        unsafe {
+            let result = fd_renumber(
+                wasi_ctx,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("path_filestat_set_times", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = path_filestat_set_times(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+                args[3].as_i32().unwrap() as u32,
+                args[4].as_i64().unwrap() as u64,
+                args[5].as_i64().unwrap() as u64,
+                args[6].as_i32().unwrap() as u16,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("path_link", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = path_link(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+                args[3].as_i32().unwrap() as u32,
+                args[4].as_i32().unwrap() as u32,
+                args[5].as_i32().unwrap() as u32,
+                args[6].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("path_readlink", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = path_readlink(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+                args[3].as_i32().unwrap() as u32,
+                args[4].as_i32().unwrap() as u32,
+                args[5].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("path_remove_directory", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = path_remove_directory(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("path_rename", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = path_rename(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+                args[3].as_i32().unwrap() as u32,
+                args[4].as_i32().unwrap() as u32,
+                args[5].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    module.define("path_symlink", |args, ret, ctx, wasi_ctx| {
+        unsafe {
+            let result = path_symlink(
+                wasi_ctx,
+                ctx.mem,
+                args[0].as_i32().unwrap() as u32,
+                args[1].as_i32().unwrap() as u32,
+                args[2].as_i32().unwrap() as u32,
+                args[3].as_i32().unwrap() as u32,
+                args[4].as_i32().unwrap() as u32,
+            );
+            ret.push(WasmValue::I32(result as i32));
+        }
+        Ok(())
+    });
+
+    // Signals can't be delivered to the program
+    module.define("proc_raise", |_args, ret, _ctx, _wasi_ctx| {
+        ret.push(WasmValue::I32(vfs::ENOTSUP as i32));
+        Ok(())
+    });
+
+    // No socket is given to the program, so no descriptor is a socket
+    for &name in &["sock_accept", "sock_recv", "sock_send", "sock_shutdown"] {
+        module.define(name, |_args, ret, _ctx, _wasi_ctx| {
+            ret.push(WasmValue::I32(vfs::ENOTSOCK as i32));
             Ok(())
-        },
-    );
-    module.insert("path_symlink".to_string(), func);
-    let context = WasiContext {
-        ctx: RefCell::new(wasi_ctx),
-    };
-    (context, module)
+        });
+    }
+
+    debug_assert_eq!(module.funcs.len(), signatures::of(snapshot).len());
+    module.funcs
 }
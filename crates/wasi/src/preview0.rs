@@ -0,0 +1,200 @@
+use super::vfs::{arg, slice, write_bytes};
+use wasminspect_vm::WasmValue;
+
+// `wasi_unstable` calls are served by the preview1 implementations. Calls
+// passing structs or enums which changed in preview1 are rewritten before
+// the call and converted back after it.
+
+const FILESTAT_SIZE: usize = 56;
+const PREVIEW1_FILESTAT_SIZE: usize = 64;
+const SUBSCRIPTION_SIZE: usize = 56;
+const PREVIEW1_SUBSCRIPTION_SIZE: usize = 48;
+const EVENTTYPE_CLOCK: u8 = 0;
+
+/// A preview0 call rewritten for preview1
+pub(crate) struct Adapted {
+    pub(crate) args: Vec<WasmValue>,
+    /// Filestat buffer to convert back to the preview0 layout
+    filestat: Option<u64>,
+    /// Guest memory overwritten for the preview1 call
+    saved: Option<(u64, Vec<u8>)>,
+}
+
+impl Adapted {
+    pub(crate) fn new(name: &str, args: &[WasmValue], mem: &mut [u8]) -> Self {
+        let mut adapted = Self {
+            args: args.to_vec(),
+            filestat: None,
+            saved: None,
+        };
+        match name {
+            "fd_seek" => {
+                // CUR, END, SET in preview0 and SET, CUR, END in preview1
+                if let Some(WasmValue::I32(whence)) = adapted.args.get_mut(2) {
+                    *whence = match *whence {
+                        0 => 1,
+                        1 => 2,
+                        2 => 0,
+                        other => other,
+                    };
+                }
+            }
+            "fd_filestat_get" => adapted.filestat(arg(args, 1), mem),
+            "path_filestat_get" => adapted.filestat(arg(args, 4), mem),
+            "poll_oneoff" => adapted.subscriptions(arg(args, 0), arg(args, 2), mem),
+            _ => (),
+        }
+        adapted
+    }
+
+    /// The preview1 filestat is longer, so keep the bytes after the preview0
+    /// one which the call overwrites
+    fn filestat(&mut self, buf: u64, mem: &[u8]) {
+        self.filestat = Some(buf);
+        let tail = buf + FILESTAT_SIZE as u64;
+        let tail_len = (PREVIEW1_FILESTAT_SIZE - FILESTAT_SIZE) as u64;
+        if let Ok(bytes) = slice(mem, tail, tail_len) {
+            self.saved = Some((tail, bytes.to_vec()));
+        }
+    }
+
+    /// Rewrite subscriptions in place. Preview1 dropped the identifier of
+    /// clock subscriptions, so the rewritten ones fit in the same buffer.
+    fn subscriptions(&mut self, ptr: u64, count: u64, mem: &mut [u8]) {
+        let original = match slice(mem, ptr, count * SUBSCRIPTION_SIZE as u64) {
+            Ok(bytes) => bytes.to_vec(),
+            Err(_) => return,
+        };
+        for (i, sub) in original.chunks(SUBSCRIPTION_SIZE).enumerate() {
+            let mut rewritten = [0; PREVIEW1_SUBSCRIPTION_SIZE];
+            // userdata and the event type
+            rewritten[0..16].copy_from_slice(&sub[0..16]);
+            if sub[8] == EVENTTYPE_CLOCK {
+                // clock id, timeout, precision and flags
+                rewritten[16..42].copy_from_slice(&sub[24..50]);
+            } else {
+                // fd
+                rewritten[16..20].copy_from_slice(&sub[16..20]);
+            }
+            let dst = ptr + (i * PREVIEW1_SUBSCRIPTION_SIZE) as u64;
+            let _ = write_bytes(mem, dst, &rewritten);
+        }
+        self.saved = Some((ptr, original));
+    }
+
+    /// Convert the results of the preview1 call back for preview0
+    pub(crate) fn finish(&self, ret: &[WasmValue], mem: &mut [u8]) {
+        let succeeded = ret.first() == Some(&WasmValue::I32(0));
+        if let (Some(buf), true) = (self.filestat, succeeded) {
+            if let Ok(stat) = slice(mem, buf, PREVIEW1_FILESTAT_SIZE as u64) {
+                let mut converted = [0; FILESTAT_SIZE];
+                // dev, ino and filetype
+                converted[0..17].copy_from_slice(&stat[0..17]);
+                // nlink is u32 in preview0
+                converted[20..24].copy_from_slice(&stat[24..28]);
+                // size, atim, mtim and ctim
+                converted[24..56].copy_from_slice(&stat[32..64]);
+                let _ = write_bytes(mem, buf, &converted);
+            }
+        }
+        if let Some((ptr, ref bytes)) = self.saved {
+            let _ = write_bytes(mem, ptr, bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use WasmValue::I32;
+
+    fn put(mem: &mut [u8], offset: usize, bytes: &[u8]) {
+        mem[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn get_u64(mem: &[u8], offset: usize) -> u64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&mem[offset..offset + 8]);
+        u64::from_le_bytes(bytes)
+    }
+
+    #[test]
+    fn remap_whence() {
+        for (preview0, preview1) in &[(0, 1), (1, 2), (2, 0), (5, 5)] {
+            let args = [I32(3), WasmValue::I64(0), I32(*preview0), I32(0)];
+            let adapted = Adapted::new("fd_seek", &args, &mut []);
+            assert_eq!(adapted.args[2], I32(*preview1));
+        }
+    }
+
+    #[test]
+    fn convert_filestat() {
+        let mut mem = vec![0xaa; PREVIEW1_FILESTAT_SIZE];
+        let adapted = Adapted::new("fd_filestat_get", &[I32(3), I32(0)], &mut mem);
+
+        // What the preview1 call writes
+        for (offset, value) in &[(0, 1), (8, 2), (24, 3), (32, 5), (40, 6), (48, 7), (56, 8)] {
+            put(&mut mem, *offset, &(*value as u64).to_le_bytes());
+        }
+        put(&mut mem, 16, &[4, 0, 0, 0, 0, 0, 0, 0]);
+        adapted.finish(&[I32(0)], &mut mem);
+
+        assert_eq!(get_u64(&mem, 0), 1);
+        assert_eq!(get_u64(&mem, 8), 2);
+        assert_eq!(mem[16], 4);
+        assert_eq!(&mem[20..24], &3u32.to_le_bytes());
+        for (offset, value) in &[(24, 5), (32, 6), (40, 7), (48, 8)] {
+            assert_eq!(get_u64(&mem, *offset), *value);
+        }
+        // Bytes past the preview0 filestat are restored
+        assert_eq!(&mem[FILESTAT_SIZE..], &[0xaa; 8]);
+    }
+
+    #[test]
+    fn keep_filestat_of_failed_call() {
+        let mut mem = vec![0xaa; PREVIEW1_FILESTAT_SIZE];
+        let adapted = Adapted::new(
+            "path_filestat_get",
+            &[I32(3), I32(0), I32(0), I32(0), I32(0)],
+            &mut mem,
+        );
+        put(&mut mem, 56, &[0; 8]);
+        adapted.finish(&[I32(8)], &mut mem);
+        assert_eq!(mem, vec![0xaa; PREVIEW1_FILESTAT_SIZE]);
+    }
+
+    #[test]
+    fn rewrite_subscriptions() {
+        let mut mem = vec![0; 2 * SUBSCRIPTION_SIZE];
+        // A clock subscription with an identifier, then an fd_read one
+        put(&mut mem, 0, &0x1111u64.to_le_bytes());
+        mem[8] = EVENTTYPE_CLOCK;
+        put(&mut mem, 16, &0xdeadu64.to_le_bytes());
+        put(&mut mem, 24, &1u32.to_le_bytes());
+        put(&mut mem, 32, &1000u64.to_le_bytes());
+        put(&mut mem, 40, &10u64.to_le_bytes());
+        put(&mut mem, 48, &1u16.to_le_bytes());
+        put(&mut mem, 56, &0x2222u64.to_le_bytes());
+        mem[64] = 1;
+        put(&mut mem, 72, &5u32.to_le_bytes());
+        let original = mem.clone();
+
+        let args = [I32(0), I32(512), I32(2), I32(0)];
+        let adapted = Adapted::new("poll_oneoff", &args, &mut mem);
+        assert_eq!(adapted.args, args.to_vec());
+
+        // Preview1 subscriptions are 48 bytes without the identifier
+        assert_eq!(get_u64(&mem, 0), 0x1111);
+        assert_eq!(mem[8], EVENTTYPE_CLOCK);
+        assert_eq!(&mem[16..20], &1u32.to_le_bytes());
+        assert_eq!(get_u64(&mem, 24), 1000);
+        assert_eq!(get_u64(&mem, 32), 10);
+        assert_eq!(&mem[40..42], &1u16.to_le_bytes());
+        assert_eq!(get_u64(&mem, 48), 0x2222);
+        assert_eq!(mem[56], 1);
+        assert_eq!(&mem[64..68], &5u32.to_le_bytes());
+
+        adapted.finish(&[I32(0)], &mut mem);
+        assert_eq!(mem, original);
+    }
+}
@@ -0,0 +1,145 @@
+use super::Snapshot;
+use wasmparser::Type;
+
+// Core wasm signatures of WASI functions, lowered from the witx definitions
+// of each snapshot in the same order. Every function returns an errno
+// except `proc_exit`.
+
+pub(crate) type Signature = (&'static str, &'static [Type], &'static [Type]);
+
+const I32: Type = Type::I32;
+const I64: Type = Type::I64;
+const ERRNO: &[Type] = &[I32];
+
+/// `phases/snapshot/witx/wasi_snapshot_preview1.witx`
+const PREVIEW1: &[Signature] = &[
+    ("args_get", &[I32, I32], ERRNO),
+    ("args_sizes_get", &[I32, I32], ERRNO),
+    ("environ_get", &[I32, I32], ERRNO),
+    ("environ_sizes_get", &[I32, I32], ERRNO),
+    ("clock_res_get", &[I32, I32], ERRNO),
+    ("clock_time_get", &[I32, I64, I32], ERRNO),
+    ("fd_advise", &[I32, I64, I64, I32], ERRNO),
+    ("fd_allocate", &[I32, I64, I64], ERRNO),
+    ("fd_close", &[I32], ERRNO),
+    ("fd_datasync", &[I32], ERRNO),
+    ("fd_fdstat_get", &[I32, I32], ERRNO),
+    ("fd_fdstat_set_flags", &[I32, I32], ERRNO),
+    ("fd_fdstat_set_rights", &[I32, I64, I64], ERRNO),
+    ("fd_filestat_get", &[I32, I32], ERRNO),
+    ("fd_filestat_set_size", &[I32, I64], ERRNO),
+    ("fd_filestat_set_times", &[I32, I64, I64, I32], ERRNO),
+    ("fd_pread", &[I32, I32, I32, I64, I32], ERRNO),
+    ("fd_prestat_get", &[I32, I32], ERRNO),
+    ("fd_prestat_dir_name", &[I32, I32, I32], ERRNO),
+    ("fd_pwrite", &[I32, I32, I32, I64, I32], ERRNO),
+    ("fd_read", &[I32, I32, I32, I32], ERRNO),
+    ("fd_readdir", &[I32, I32, I32, I64, I32], ERRNO),
+    ("fd_renumber", &[I32, I32], ERRNO),
+    ("fd_seek", &[I32, I64, I32, I32], ERRNO),
+    ("fd_sync", &[I32], ERRNO),
+    ("fd_tell", &[I32, I32], ERRNO),
+    ("fd_write", &[I32, I32, I32, I32], ERRNO),
+    ("path_create_directory", &[I32, I32, I32], ERRNO),
+    ("path_filestat_get", &[I32, I32, I32, I32, I32], ERRNO),
+    (
+        "path_filestat_set_times",
+        &[I32, I32, I32, I32, I64, I64, I32],
+        ERRNO,
+    ),
+    ("path_link", &[I32, I32, I32, I32, I32, I32, I32], ERRNO),
+    (
+        "path_open",
+        &[I32, I32, I32, I32, I32, I64, I64, I32, I32],
+        ERRNO,
+    ),
+    ("path_readlink", &[I32, I32, I32, I32, I32, I32], ERRNO),
+    ("path_remove_directory", &[I32, I32, I32], ERRNO),
+    ("path_rename", &[I32, I32, I32, I32, I32, I32], ERRNO),
+    ("path_symlink", &[I32, I32, I32, I32, I32], ERRNO),
+    ("path_unlink_file", &[I32, I32, I32], ERRNO),
+    ("poll_oneoff", &[I32, I32, I32, I32], ERRNO),
+    ("proc_exit", &[I32], &[]),
+    ("proc_raise", &[I32], ERRNO),
+    ("sched_yield", &[], ERRNO),
+    ("random_get", &[I32, I32], ERRNO),
+    ("sock_accept", &[I32, I32, I32], ERRNO),
+    ("sock_recv", &[I32, I32, I32, I32, I32, I32], ERRNO),
+    ("sock_send", &[I32, I32, I32, I32, I32], ERRNO),
+    ("sock_shutdown", &[I32, I32], ERRNO),
+];
+
+/// `phases/old/snapshot_0/witx/wasi_unstable.witx`. The lowered types are
+/// the same as preview1 where both have the function, but some structs and
+/// enums differ, which `preview0::Adapted` takes care of.
+const PREVIEW0: &[Signature] = &[
+    ("args_get", &[I32, I32], ERRNO),
+    ("args_sizes_get", &[I32, I32], ERRNO),
+    ("environ_get", &[I32, I32], ERRNO),
+    ("environ_sizes_get", &[I32, I32], ERRNO),
+    ("clock_res_get", &[I32, I32], ERRNO),
+    ("clock_time_get", &[I32, I64, I32], ERRNO),
+    ("fd_advise", &[I32, I64, I64, I32], ERRNO),
+    ("fd_allocate", &[I32, I64, I64], ERRNO),
+    ("fd_close", &[I32], ERRNO),
+    ("fd_datasync", &[I32], ERRNO),
+    ("fd_fdstat_get", &[I32, I32], ERRNO),
+    ("fd_fdstat_set_flags", &[I32, I32], ERRNO),
+    ("fd_fdstat_set_rights", &[I32, I64, I64], ERRNO),
+    ("fd_filestat_get", &[I32, I32], ERRNO),
+    ("fd_filestat_set_size", &[I32, I64], ERRNO),
+    ("fd_filestat_set_times", &[I32, I64, I64, I32], ERRNO),
+    ("fd_pread", &[I32, I32, I32, I64, I32], ERRNO),
+    ("fd_prestat_get", &[I32, I32], ERRNO),
+    ("fd_prestat_dir_name", &[I32, I32, I32], ERRNO),
+    ("fd_pwrite", &[I32, I32, I32, I64, I32], ERRNO),
+    ("fd_read", &[I32, I32, I32, I32], ERRNO),
+    ("fd_readdir", &[I32, I32, I32, I64, I32], ERRNO),
+    ("fd_renumber", &[I32, I32], ERRNO),
+    ("fd_seek", &[I32, I64, I32, I32], ERRNO),
+    ("fd_sync", &[I32], ERRNO),
+    ("fd_tell", &[I32, I32], ERRNO),
+    ("fd_write", &[I32, I32, I32, I32], ERRNO),
+    ("path_create_directory", &[I32, I32, I32], ERRNO),
+    ("path_filestat_get", &[I32, I32, I32, I32, I32], ERRNO),
+    (
+        "path_filestat_set_times",
+        &[I32, I32, I32, I32, I64, I64, I32],
+        ERRNO,
+    ),
+    ("path_link", &[I32, I32, I32, I32, I32, I32, I32], ERRNO),
+    (
+        "path_open",
+        &[I32, I32, I32, I32, I32, I64, I64, I32, I32],
+        ERRNO,
+    ),
+    ("path_readlink", &[I32, I32, I32, I32, I32, I32], ERRNO),
+    ("path_remove_directory", &[I32, I32, I32], ERRNO),
+    ("path_rename", &[I32, I32, I32, I32, I32, I32], ERRNO),
+    ("path_symlink", &[I32, I32, I32, I32, I32], ERRNO),
+    ("path_unlink_file", &[I32, I32, I32], ERRNO),
+    ("poll_oneoff", &[I32, I32, I32, I32], ERRNO),
+    ("proc_exit", &[I32], &[]),
+    ("proc_raise", &[I32], ERRNO),
+    ("sched_yield", &[], ERRNO),
+    ("random_get", &[I32, I32], ERRNO),
+    ("sock_recv", &[I32, I32, I32, I32, I32, I32], ERRNO),
+    ("sock_send", &[I32, I32, I32, I32, I32], ERRNO),
+    ("sock_shutdown", &[I32, I32], ERRNO),
+];
+
+/// All functions of `snapshot`
+pub(crate) fn of(snapshot: Snapshot) -> &'static [Signature] {
+    match snapshot {
+        Snapshot::Preview1 => PREVIEW1,
+        Snapshot::Preview0 => PREVIEW0,
+    }
+}
+
+/// Parameter and result types of `name` in `snapshot`
+pub(crate) fn find(snapshot: Snapshot, name: &str) -> Option<(&'static [Type], &'static [Type])> {
+    of(snapshot)
+        .iter()
+        .find(|(func, _, _)| *func == name)
+        .map(|(_, params, results)| (*params, *results))
+}
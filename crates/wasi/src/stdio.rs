@@ -0,0 +1,256 @@
+use super::vfs::{arg, iovecs, slice, write_bytes, Errno, ESUCCESS};
+use std::io::Write;
+use wasminspect_vm::WasmValue;
+
+/// Where the output of the program to stdout or stderr goes
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum OutputRedirect {
+    /// Write to the same stream of the debugger
+    #[default]
+    Inherit,
+    /// Write to the same stream of the debugger, prefixing each line with
+    /// the stream name
+    Prefix,
+    /// Keep in memory to read later
+    Capture,
+    /// Write to a host file, given as `file:PATH` or a path with `/` or `.`
+    File(String),
+}
+
+impl std::str::FromStr for OutputRedirect {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inherit" => Ok(Self::Inherit),
+            "prefix" => Ok(Self::Prefix),
+            "capture" => Ok(Self::Capture),
+            _ if s.starts_with("file:") && s.len() > "file:".len() => {
+                Ok(Self::File(s["file:".len()..].to_string()))
+            }
+            // Anything else which looks like a path rather than a misspelled
+            // word
+            path if path.contains('/') || path.contains('.') => Ok(Self::File(path.to_string())),
+            _ => Err(format!(
+                "unknown output redirect '{}', expected inherit, prefix, capture or file:PATH",
+                s
+            )),
+        }
+    }
+}
+
+/// Output kept by `OutputRedirect::Capture`. Only the last
+/// `MAX_CAPTURED_BYTES` of each stream are kept.
+#[derive(Debug, Clone, Default)]
+pub struct CapturedOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// Bytes dropped from the start of `stdout`
+    pub stdout_dropped: usize,
+    /// Bytes dropped from the start of `stderr`
+    pub stderr_dropped: usize,
+}
+
+const MAX_CAPTURED_BYTES: usize = 1 << 20;
+
+const STDOUT_FD: u64 = 1;
+const STDERR_FD: u64 = 2;
+
+/// Arguments and memory of an `fd_write` call writing `bytes` to `fd`: an
+/// iovec at 0 pointing to `bytes` at 12, and the written size at 8
+pub(crate) fn fd_write_call(fd: u32, bytes: &[u8]) -> ([WasmValue; 4], Vec<u8>) {
+    let mut mem = Vec::with_capacity(12 + bytes.len());
+    mem.extend_from_slice(&12u32.to_le_bytes());
+    mem.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    mem.extend_from_slice(&0u32.to_le_bytes());
+    mem.extend_from_slice(bytes);
+    let args = [
+        WasmValue::I32(fd as i32),
+        WasmValue::I32(0),
+        WasmValue::I32(1),
+        WasmValue::I32(8),
+    ];
+    (args, mem)
+}
+
+/// Writes to stdout and stderr which are not left to wasi-common
+pub(crate) struct Stdio {
+    stdout: OutputRedirect,
+    stderr: OutputRedirect,
+    captured: CapturedOutput,
+    /// Whether the next output to stdout and stderr starts a line
+    line_start: [bool; 2],
+}
+
+impl Stdio {
+    pub(crate) fn new(stdout: OutputRedirect, stderr: OutputRedirect) -> Self {
+        Self {
+            stdout,
+            stderr,
+            captured: CapturedOutput::default(),
+            line_start: [true, true],
+        }
+    }
+
+    pub(crate) fn captured(&self) -> &CapturedOutput {
+        &self.captured
+    }
+
+    /// Serve a WASI call if it writes to a captured or prefixed stream,
+    /// returning its errno
+    pub(crate) fn call(&mut self, name: &str, args: &[WasmValue], mem: &mut [u8]) -> Option<u16> {
+        if name != "fd_write" {
+            return None;
+        }
+        let redirect = match arg(args, 0) {
+            STDOUT_FD => &self.stdout,
+            STDERR_FD => &self.stderr,
+            _ => return None,
+        };
+        match redirect {
+            OutputRedirect::Prefix | OutputRedirect::Capture => (),
+            OutputRedirect::Inherit | OutputRedirect::File(_) => return None,
+        }
+        Some(match self.fd_write(args, mem) {
+            Ok(_) => ESUCCESS,
+            Err(errno) => errno,
+        })
+    }
+
+    fn fd_write(&mut self, args: &[WasmValue], mem: &mut [u8]) -> Result<(), Errno> {
+        let mut bytes = Vec::new();
+        for (buf, len) in iovecs(mem, arg(args, 1), arg(args, 2))? {
+            bytes.extend_from_slice(slice(mem, buf, len)?);
+        }
+        let fd = arg(args, 0);
+        let captured = &mut self.captured;
+        let (redirect, captured, dropped, name) = if fd == STDOUT_FD {
+            let (buf, dropped) = (&mut captured.stdout, &mut captured.stdout_dropped);
+            (&self.stdout, buf, dropped, "stdout")
+        } else {
+            let (buf, dropped) = (&mut captured.stderr, &mut captured.stderr_dropped);
+            (&self.stderr, buf, dropped, "stderr")
+        };
+        match redirect {
+            OutputRedirect::Capture => {
+                captured.extend_from_slice(&bytes);
+                if captured.len() > MAX_CAPTURED_BYTES {
+                    let excess = captured.len() - MAX_CAPTURED_BYTES;
+                    captured.drain(..excess);
+                    *dropped += excess;
+                }
+            }
+            _ => {
+                let line_start = &mut self.line_start[fd as usize - 1];
+                let prefixed = prefix_lines(&bytes, name, line_start);
+                // Failing to show the output is not the program's problem
+                let _ = if fd == STDOUT_FD {
+                    std::io::stdout().write_all(&prefixed)
+                } else {
+                    std::io::stderr().write_all(&prefixed)
+                };
+            }
+        }
+        write_bytes(mem, arg(args, 3), &(bytes.len() as u32).to_le_bytes())
+    }
+}
+
+/// Prefix each line of `bytes` like `[stdout] `. `line_start` tells whether
+/// the previous output ended a line, and is updated for the next one.
+fn prefix_lines(bytes: &[u8], name: &str, line_start: &mut bool) -> Vec<u8> {
+    let mut prefixed = Vec::new();
+    for byte in bytes {
+        if *line_start {
+            prefixed.extend_from_slice(format!("[{}] ", name).as_bytes());
+        }
+        prefixed.push(*byte);
+        *line_start = *byte == b'\n';
+    }
+    prefixed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_output_redirect() {
+        let file = |path: &str| Ok(OutputRedirect::File(path.to_string()));
+        let cases = [
+            ("inherit", Ok(OutputRedirect::Inherit)),
+            ("prefix", Ok(OutputRedirect::Prefix)),
+            ("capture", Ok(OutputRedirect::Capture)),
+            ("file:out", file("out")),
+            ("file:capture", file("capture")),
+            ("out.txt", file("out.txt")),
+            ("logs/out", file("logs/out")),
+        ];
+        for (s, expected) in cases.iter() {
+            assert_eq!(s.parse::<OutputRedirect>(), *expected, "{}", s);
+        }
+        for s in ["", "captrue", "file:"].iter() {
+            assert!(s.parse::<OutputRedirect>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn prefix_each_line() {
+        // Outputs written in order, and what each of them shows
+        let cases: &[(&[u8], &str)] = &[
+            (b"hello\n", "[stdout] hello\n"),
+            (b"a\nb", "[stdout] a\n[stdout] b"),
+            (b"c\n\n", "c\n[stdout] \n"),
+            (b"", ""),
+            (b"d", "[stdout] d"),
+        ];
+        let mut line_start = true;
+        for (bytes, expected) in cases {
+            let prefixed = prefix_lines(bytes, "stdout", &mut line_start);
+            assert_eq!(String::from_utf8(prefixed).unwrap(), *expected);
+        }
+        assert!(!line_start);
+    }
+
+    #[test]
+    fn capture_keeps_the_last_bytes() {
+        let mut stdio = Stdio::new(OutputRedirect::Capture, OutputRedirect::Inherit);
+        let len = MAX_CAPTURED_BYTES / 2 + 1;
+        // An iovec at 0 pointing `len` bytes at 16, and the written size at 8
+        let mut mem = vec![b'x'; 16 + len];
+        mem[0..4].copy_from_slice(&16u32.to_le_bytes());
+        mem[4..8].copy_from_slice(&(len as u32).to_le_bytes());
+        let args = [
+            WasmValue::I32(STDOUT_FD as i32),
+            WasmValue::I32(0),
+            WasmValue::I32(1),
+            WasmValue::I32(8),
+        ];
+        for _ in 0..3 {
+            assert_eq!(stdio.call("fd_write", &args, &mut mem), Some(ESUCCESS));
+        }
+        let captured = stdio.captured();
+        assert_eq!(captured.stdout.len(), MAX_CAPTURED_BYTES);
+        assert_eq!(captured.stdout_dropped, 3 * len - MAX_CAPTURED_BYTES);
+        assert!(captured.stderr.is_empty());
+
+        // Inherited streams are left to wasi-common
+        let args = [
+            WasmValue::I32(STDERR_FD as i32),
+            WasmValue::I32(0),
+            WasmValue::I32(1),
+            WasmValue::I32(8),
+        ];
+        assert_eq!(stdio.call("fd_write", &args, &mut mem), None);
+    }
+
+    #[test]
+    fn write_through_fd_write_call() {
+        let mut stdio = Stdio::new(OutputRedirect::Inherit, OutputRedirect::Capture);
+        let (args, mut mem) = fd_write_call(STDERR_FD as u32, b"warning\n");
+        assert_eq!(stdio.call("fd_write", &args, &mut mem), Some(ESUCCESS));
+        assert_eq!(&mem[8..12], &8u32.to_le_bytes());
+        assert_eq!(stdio.captured().stderr, b"warning\n");
+
+        let (args, mut mem) = fd_write_call(STDOUT_FD as u32, b"log\n");
+        assert_eq!(stdio.call("fd_write", &args, &mut mem), None);
+    }
+}
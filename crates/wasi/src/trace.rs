@@ -0,0 +1,572 @@
+use wasminspect_vm::{HostContext, ProgramCounter, Store, Trap, WasmValue};
+
+/// Which WASI calls are logged to stderr
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WasiTrace {
+    pub enabled: bool,
+    /// Log only the functions with these names if not empty
+    pub filter: Vec<String>,
+}
+
+impl WasiTrace {
+    pub fn traces(&self, name: &str) -> bool {
+        self.enabled && (self.filter.is_empty() || self.filter.iter().any(|f| f == name))
+    }
+}
+
+/// How to decode a parameter of a WASI function
+#[derive(Clone, Copy)]
+enum Param {
+    Int,
+    Ptr,
+    /// A string given by a pointer and the following length
+    Str,
+    /// A string written by the call to the buffer given by a pointer and the
+    /// following length
+    StrOut,
+    /// Buffers read by the call, given by a pointer to iovecs and the
+    /// following count
+    Iovecs,
+    /// Buffers filled by the call, given by a pointer to iovecs and the
+    /// following count. The filled size is stored at the last parameter.
+    IovecsOut,
+    ClockId,
+    Advice,
+    Whence,
+    Rights,
+    OFlags,
+    FdFlags,
+    LookupFlags,
+    FstFlags,
+}
+
+fn params(name: &str) -> Option<&'static [Param]> {
+    use Param::*;
+    let params: &'static [Param] = match name {
+        "proc_exit" | "proc_raise" => &[Int],
+        "args_get" | "environ_get" => &[Ptr, Ptr],
+        "args_sizes_get" | "environ_sizes_get" => &[Ptr, Ptr],
+        "clock_res_get" => &[ClockId, Ptr],
+        "clock_time_get" => &[ClockId, Int, Ptr],
+        "fd_advise" => &[Int, Int, Int, Advice],
+        "fd_allocate" => &[Int, Int, Int],
+        "fd_close" | "fd_datasync" | "fd_sync" => &[Int],
+        "fd_fdstat_get" | "fd_filestat_get" | "fd_prestat_get" | "fd_tell" => &[Int, Ptr],
+        "fd_fdstat_set_flags" => &[Int, FdFlags],
+        "fd_fdstat_set_rights" => &[Int, Rights, Rights],
+        "fd_filestat_set_size" => &[Int, Int],
+        "fd_filestat_set_times" => &[Int, Int, Int, FstFlags],
+        "fd_pread" => &[Int, IovecsOut, Int, Ptr],
+        "fd_prestat_dir_name" => &[Int, StrOut],
+        "fd_pwrite" => &[Int, Iovecs, Int, Ptr],
+        "fd_read" => &[Int, IovecsOut, Ptr],
+        "fd_readdir" => &[Int, Ptr, Int, Int, Ptr],
+        "fd_renumber" => &[Int, Int],
+        "fd_seek" => &[Int, Int, Whence, Ptr],
+        "fd_write" => &[Int, Iovecs, Ptr],
+        "path_create_directory" | "path_remove_directory" | "path_unlink_file" => &[Int, Str],
+        "path_filestat_get" => &[Int, LookupFlags, Str, Ptr],
+        "path_filestat_set_times" => &[Int, LookupFlags, Str, Int, Int, FstFlags],
+        "path_link" => &[Int, LookupFlags, Str, Int, Str],
+        "path_open" => &[Int, LookupFlags, Str, OFlags, Rights, Rights, FdFlags, Ptr],
+        "path_readlink" => &[Int, Str, Ptr, Int, Ptr],
+        "path_rename" => &[Int, Str, Int, Str],
+        "path_symlink" => &[Str, Int, Str],
+        "poll_oneoff" => &[Ptr, Ptr, Int, Ptr],
+        "random_get" => &[Ptr, Int],
+        "sched_yield" => &[],
+        "sock_accept" => &[Int, FdFlags, Ptr],
+        "sock_recv" => &[Int, Ptr, Int, Int, Ptr, Ptr],
+        "sock_send" => &[Int, Iovecs, Int, Ptr],
+        "sock_shutdown" => &[Int, Int],
+        _ => return None,
+    };
+    Some(params)
+}
+
+const ERRNO_NAMES: [&str; 77] = [
+    "ESUCCESS",
+    "E2BIG",
+    "EACCES",
+    "EADDRINUSE",
+    "EADDRNOTAVAIL",
+    "EAFNOSUPPORT",
+    "EAGAIN",
+    "EALREADY",
+    "EBADF",
+    "EBADMSG",
+    "EBUSY",
+    "ECANCELED",
+    "ECHILD",
+    "ECONNABORTED",
+    "ECONNREFUSED",
+    "ECONNRESET",
+    "EDEADLK",
+    "EDESTADDRREQ",
+    "EDOM",
+    "EDQUOT",
+    "EEXIST",
+    "EFAULT",
+    "EFBIG",
+    "EHOSTUNREACH",
+    "EIDRM",
+    "EILSEQ",
+    "EINPROGRESS",
+    "EINTR",
+    "EINVAL",
+    "EIO",
+    "EISCONN",
+    "EISDIR",
+    "ELOOP",
+    "EMFILE",
+    "EMLINK",
+    "EMSGSIZE",
+    "EMULTIHOP",
+    "ENAMETOOLONG",
+    "ENETDOWN",
+    "ENETRESET",
+    "ENETUNREACH",
+    "ENFILE",
+    "ENOBUFS",
+    "ENODEV",
+    "ENOENT",
+    "ENOEXEC",
+    "ENOLCK",
+    "ENOLINK",
+    "ENOMEM",
+    "ENOMSG",
+    "ENOPROTOOPT",
+    "ENOSPC",
+    "ENOSYS",
+    "ENOTCONN",
+    "ENOTDIR",
+    "ENOTEMPTY",
+    "ENOTRECOVERABLE",
+    "ENOTSOCK",
+    "ENOTSUP",
+    "ENOTTY",
+    "ENXIO",
+    "EOVERFLOW",
+    "EOWNERDEAD",
+    "EPERM",
+    "EPIPE",
+    "EPROTO",
+    "EPROTONOSUPPORT",
+    "EPROTOTYPE",
+    "ERANGE",
+    "EROFS",
+    "ESPIPE",
+    "ESRCH",
+    "ESTALE",
+    "ETIMEDOUT",
+    "ETXTBSY",
+    "EXDEV",
+    "ENOTCAPABLE",
+];
+
+const CLOCK_NAMES: [&str; 4] = [
+    "REALTIME",
+    "MONOTONIC",
+    "PROCESS_CPUTIME_ID",
+    "THREAD_CPUTIME_ID",
+];
+const ADVICE_NAMES: [&str; 6] = [
+    "NORMAL",
+    "SEQUENTIAL",
+    "RANDOM",
+    "WILLNEED",
+    "DONTNEED",
+    "NOREUSE",
+];
+const WHENCE_NAMES: [&str; 3] = ["SET", "CUR", "END"];
+const RIGHT_NAMES: [&str; 29] = [
+    "FD_DATASYNC",
+    "FD_READ",
+    "FD_SEEK",
+    "FD_FDSTAT_SET_FLAGS",
+    "FD_SYNC",
+    "FD_TELL",
+    "FD_WRITE",
+    "FD_ADVISE",
+    "FD_ALLOCATE",
+    "PATH_CREATE_DIRECTORY",
+    "PATH_CREATE_FILE",
+    "PATH_LINK_SOURCE",
+    "PATH_LINK_TARGET",
+    "PATH_OPEN",
+    "FD_READDIR",
+    "PATH_READLINK",
+    "PATH_RENAME_SOURCE",
+    "PATH_RENAME_TARGET",
+    "PATH_FILESTAT_GET",
+    "PATH_FILESTAT_SET_SIZE",
+    "PATH_FILESTAT_SET_TIMES",
+    "FD_FILESTAT_GET",
+    "FD_FILESTAT_SET_SIZE",
+    "FD_FILESTAT_SET_TIMES",
+    "PATH_SYMLINK",
+    "PATH_REMOVE_DIRECTORY",
+    "PATH_UNLINK_FILE",
+    "POLL_FD_READWRITE",
+    "SOCK_SHUTDOWN",
+];
+const ALL_RIGHTS: u64 = (1 << RIGHT_NAMES.len()) - 1;
+const OFLAG_NAMES: [&str; 4] = ["CREAT", "DIRECTORY", "EXCL", "TRUNC"];
+const FDFLAG_NAMES: [&str; 5] = ["APPEND", "DSYNC", "NONBLOCK", "RSYNC", "SYNC"];
+const LOOKUPFLAG_NAMES: [&str; 1] = ["SYMLINK_FOLLOW"];
+const FSTFLAG_NAMES: [&str; 4] = ["ATIM", "ATIM_NOW", "MTIM", "MTIM_NOW"];
+
+/// Bytes of a buffer shown before eliding the rest
+const MAX_SHOWN_BYTES: usize = 32;
+
+/// Print a call of the WASI function `name` made by the program, like
+/// `fd_write(1, ["hello\n"], 0x1ffec) = 0 at main (0x1a3)`
+pub(crate) fn print_call(
+    name: &str,
+    args: &[WasmValue],
+    results: &[WasmValue],
+    result: &Result<(), Trap>,
+    ctx: &HostContext,
+    store: &Store,
+) {
+    let errno = results.first().and_then(|value| value.as_i32());
+    let args = match params(name) {
+        Some(params) => decode_args(params, args, errno == Some(0), ctx.mem),
+        None => args.iter().map(|arg| format!("{:?}", arg)).collect(),
+    };
+    let result = match (result, errno) {
+        (Ok(_), Some(0)) => "0".to_string(),
+        (Ok(_), Some(errno)) => match ERRNO_NAMES.get(errno as usize) {
+            Some(errno_name) => format!("{} {}", errno, errno_name),
+            None => errno.to_string(),
+        },
+        (Ok(_), None) => "void".to_string(),
+        (Err(_), _) => "?".to_string(),
+    };
+    let location = ctx
+        .caller
+        .and_then(|caller| caller_location(caller, store))
+        .map(|location| format!(" at {}", location))
+        .unwrap_or_default();
+    eprintln!("{}({}) = {}{}", name, args.join(", "), result, location);
+}
+
+fn decode_args(params: &[Param], args: &[WasmValue], succeeded: bool, mem: &[u8]) -> Vec<String> {
+    let values: Vec<u64> = args
+        .iter()
+        .map(|arg| match arg {
+            WasmValue::I32(v) => *v as u32 as u64,
+            WasmValue::I64(v) => *v as u64,
+            WasmValue::F32(v) => *v as u64,
+            WasmValue::F64(v) => *v,
+        })
+        .collect();
+    let mut decoded = Vec::new();
+    let mut index = 0;
+    for param in params {
+        let value = match values.get(index) {
+            Some(value) => *value,
+            None => break,
+        };
+        let next = values.get(index + 1).cloned().unwrap_or_default();
+        let arg = &args[index];
+        index += 1;
+        let arg = match param {
+            // 64-bit integers are file offsets and timestamps, which can be
+            // negative for `fd_seek`
+            Param::Int => match arg {
+                WasmValue::I64(v) => v.to_string(),
+                _ => value.to_string(),
+            },
+            Param::Ptr => format!("0x{:x}", value),
+            Param::Str => {
+                index += 1;
+                quote(read_bytes(mem, value, next))
+            }
+            Param::StrOut if succeeded => {
+                index += 1;
+                quote(read_bytes(mem, value, next))
+            }
+            Param::StrOut => {
+                index += 1;
+                format!("0x{:x}", value)
+            }
+            Param::Iovecs => {
+                index += 1;
+                iovecs(mem, value, next, None)
+            }
+            Param::IovecsOut if succeeded => {
+                index += 1;
+                let filled = values.last().and_then(|ptr| read_u32(mem, *ptr));
+                iovecs(mem, value, next, filled.or(Some(0)))
+            }
+            Param::IovecsOut => {
+                index += 1;
+                format!("0x{:x}", value)
+            }
+            Param::ClockId => enum_name(value, &CLOCK_NAMES),
+            Param::Advice => enum_name(value, &ADVICE_NAMES),
+            Param::Whence => enum_name(value, &WHENCE_NAMES),
+            // Programs usually ask for every right by passing all ones
+            Param::Rights if value & ALL_RIGHTS == ALL_RIGHTS => "ALL".to_string(),
+            Param::Rights => flags(value, &RIGHT_NAMES),
+            Param::OFlags => flags(value, &OFLAG_NAMES),
+            Param::FdFlags => flags(value, &FDFLAG_NAMES),
+            Param::LookupFlags => flags(value, &LOOKUPFLAG_NAMES),
+            Param::FstFlags => flags(value, &FSTFLAG_NAMES),
+        };
+        decoded.push(arg);
+    }
+    decoded
+}
+
+/// Contents of iovecs at `ptr`, showing `limit` bytes in total if given
+fn iovecs(mem: &[u8], ptr: u64, count: u64, mut limit: Option<u32>) -> String {
+    let mut bufs = Vec::new();
+    for i in 0..count {
+        let iovec = ptr + i * 8;
+        let (buf, len) = match (read_u32(mem, iovec), read_u32(mem, iovec + 4)) {
+            (Some(buf), Some(len)) => (buf, len),
+            _ => return format!("0x{:x}", ptr),
+        };
+        let len = match limit {
+            Some(ref mut limit) => {
+                let len = std::cmp::min(len, *limit);
+                *limit -= len;
+                len
+            }
+            None => len,
+        };
+        bufs.push(quote(read_bytes(mem, buf as u64, len as u64)));
+    }
+    format!("[{}]", bufs.join(", "))
+}
+
+fn read_bytes(mem: &[u8], ptr: u64, len: u64) -> Option<&[u8]> {
+    let start = ptr as usize;
+    let end = start.checked_add(len as usize)?;
+    mem.get(start..end)
+}
+
+fn read_u32(mem: &[u8], ptr: u64) -> Option<u32> {
+    let bytes = read_bytes(mem, ptr, 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn quote(bytes: Option<&[u8]>) -> String {
+    let bytes = match bytes {
+        Some(bytes) => bytes,
+        None => return "<out of bounds>".to_string(),
+    };
+    let mut quoted = String::from("\"");
+    for byte in bytes.iter().take(MAX_SHOWN_BYTES) {
+        match byte {
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            0x20..=0x7e => quoted.push(*byte as char),
+            _ => quoted.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    quoted.push('"');
+    if bytes.len() > MAX_SHOWN_BYTES {
+        quoted.push_str("...");
+    }
+    quoted
+}
+
+fn enum_name(value: u64, names: &[&str]) -> String {
+    match names.get(value as usize) {
+        Some(name) => name.to_string(),
+        None => value.to_string(),
+    }
+}
+
+fn flags(value: u64, names: &[&str]) -> String {
+    if value == 0 {
+        return "0".to_string();
+    }
+    let mut set = Vec::new();
+    let mut unknown = value;
+    for (bit, name) in names.iter().enumerate() {
+        if value & (1 << bit) != 0 {
+            set.push(name.to_string());
+            unknown &= !(1 << bit);
+        }
+    }
+    if unknown != 0 {
+        set.push(format!("0x{:x}", unknown));
+    }
+    set.join("|")
+}
+
+/// Name and code offset of the calling function
+fn caller_location(caller: ProgramCounter, store: &Store) -> Option<String> {
+    let func = store.func_global(caller.exec_addr()).defined()?;
+    let inst = func
+        .instructions()
+        .ok()?
+        .get(caller.inst_index().0 as usize)?;
+    Some(format!("{} (0x{:x})", func.name(), inst.offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Memory with two iovecs at 0 pointing "he" and "llo" at 16, and the
+    /// filled size 4 at 0x40
+    fn memory() -> Vec<u8> {
+        let mut mem = vec![0; 72];
+        for (offset, value) in &[(0, 16), (4, 2), (8, 18), (12, 3), (0x40, 4)] {
+            mem[*offset..*offset + 4].copy_from_slice(&(*value as u32).to_le_bytes());
+        }
+        mem[16..21].copy_from_slice(b"hello");
+        mem
+    }
+
+    #[test]
+    fn test_quote() {
+        let long = [b'a'; MAX_SHOWN_BYTES + 1];
+        let cases: &[(Option<&[u8]>, &str)] = &[
+            (Some(b"hi"), "\"hi\""),
+            (Some(b"\"\\\n\r\t"), r#""\"\\\n\r\t""#),
+            (Some(&[0x00, 0x7f, 0xff]), r#""\x00\x7f\xff""#),
+            (Some(&long), "\"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\"..."),
+            (None, "<out of bounds>"),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(quote(*bytes), *expected);
+        }
+    }
+
+    #[test]
+    fn test_flags() {
+        let cases: &[(u64, &str)] = &[
+            (0, "0"),
+            (0b1001, "CREAT|TRUNC"),
+            (0b10001, "CREAT|0x10"),
+            (0x30, "0x30"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(flags(*value, &OFLAG_NAMES), *expected);
+        }
+    }
+
+    #[test]
+    fn test_iovecs() {
+        let mem = memory();
+        let cases: &[(u64, u64, Option<u32>, &str)] = &[
+            (0, 2, None, r#"["he", "llo"]"#),
+            (0, 2, Some(3), r#"["he", "l"]"#),
+            (0, 0, None, "[]"),
+            (8, 1, None, r#"["llo"]"#),
+            // The second iovec is past the end of memory
+            (0x40, 2, None, "0x40"),
+            (0x1000, 1, None, "0x1000"),
+        ];
+        for (ptr, count, limit, expected) in cases {
+            assert_eq!(iovecs(&mem, *ptr, *count, *limit), *expected);
+        }
+        // A buffer out of memory
+        let mut mem = mem;
+        mem[0..4].copy_from_slice(&0x1000u32.to_le_bytes());
+        assert_eq!(iovecs(&mem, 0, 1, None), "[<out of bounds>]");
+    }
+
+    #[test]
+    fn test_decode_args() {
+        use WasmValue::{I32, I64};
+        let mem = memory();
+        let cases: &[(&str, &[WasmValue], bool, &[&str])] = &[
+            (
+                "fd_write",
+                &[I32(1), I32(0), I32(2), I32(0x40)],
+                true,
+                &["1", r#"["he", "llo"]"#, "0x40"],
+            ),
+            (
+                "fd_read",
+                &[I32(3), I32(0), I32(2), I32(0x40)],
+                true,
+                &["3", r#"["he", "ll"]"#, "0x40"],
+            ),
+            (
+                "fd_read",
+                &[I32(3), I32(0), I32(2), I32(0x40)],
+                false,
+                &["3", "0x0", "0x40"],
+            ),
+            (
+                "fd_seek",
+                &[I32(3), I64(-2), I32(2), I32(8)],
+                true,
+                &["3", "-2", "END", "0x8"],
+            ),
+            (
+                "fd_advise",
+                &[I32(3), I64(0), I64(0), I32(9)],
+                true,
+                &["3", "0", "0", "9"],
+            ),
+            (
+                "fd_prestat_dir_name",
+                &[I32(3), I32(16), I32(5)],
+                true,
+                &["3", "\"hello\""],
+            ),
+            (
+                "fd_prestat_dir_name",
+                &[I32(3), I32(16), I32(5)],
+                false,
+                &["3", "0x10"],
+            ),
+            (
+                "clock_time_get",
+                &[I32(1), I64(1000), I32(8)],
+                true,
+                &["MONOTONIC", "1000", "0x8"],
+            ),
+            (
+                "path_open",
+                &[
+                    I32(3),
+                    I32(1),
+                    I32(16),
+                    I32(2),
+                    I32(0b1001),
+                    I64(-1),
+                    I64(0b10),
+                    I32(0),
+                    I32(8),
+                ],
+                true,
+                &[
+                    "3",
+                    "SYMLINK_FOLLOW",
+                    "\"he\"",
+                    "CREAT|TRUNC",
+                    "ALL",
+                    "FD_READ",
+                    "0",
+                    "0x8",
+                ],
+            ),
+            // A missing argument stops decoding
+            ("fd_write", &[I32(1)], true, &["1"]),
+            ("fd_close", &[], true, &[]),
+        ];
+        for (name, args, succeeded, expected) in cases {
+            let params = params(name).unwrap();
+            assert_eq!(
+                decode_args(params, args, *succeeded, &mem),
+                *expected,
+                "{}",
+                name
+            );
+        }
+        assert!(params("unknown").is_none());
+    }
+}
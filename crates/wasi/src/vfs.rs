@@ -0,0 +1,958 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::path::Path;
+use wasminspect_vm::WasmValue;
+
+/// A file of `VirtualFs`
+#[derive(Debug, Clone, Default)]
+pub struct VirtualFile {
+    pub data: Vec<u8>,
+    /// Created by the program rather than loaded from the seed
+    pub created: bool,
+    /// Written by the program
+    pub modified: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Dir,
+    File(VirtualFile),
+}
+
+struct OpenFile {
+    path: String,
+    offset: u64,
+    append: bool,
+    /// Guest path of a preopened directory
+    preopen: Option<&'static str>,
+}
+
+/// An in-memory filesystem served to the program instead of the host one.
+///
+/// The root directory is preopened as `/` and `.`, so that both absolute
+/// and relative paths resolve into it. Standard I/O is left to wasi-common.
+pub struct VirtualFs {
+    /// Nodes by their path from the root without a leading `/`. The root
+    /// itself is the empty path.
+    nodes: BTreeMap<String, Node>,
+    fds: HashMap<u32, OpenFile>,
+    next_fd: u32,
+}
+
+pub(crate) type Errno = u16;
+
+pub(crate) const ESUCCESS: Errno = 0;
+const EBADF: Errno = 8;
+const EBUSY: Errno = 10;
+const EEXIST: Errno = 20;
+const EFAULT: Errno = 21;
+const EFBIG: Errno = 22;
+const EILSEQ: Errno = 25;
+const EINVAL: Errno = 28;
+const EISDIR: Errno = 31;
+const ENOENT: Errno = 44;
+const ENOMEM: Errno = 48;
+const ENOTDIR: Errno = 54;
+const ENOTEMPTY: Errno = 55;
+pub(crate) const ENOTSOCK: Errno = 57;
+pub(crate) const ENOTSUP: Errno = 58;
+const ENOTCAPABLE: Errno = 76;
+
+const FILETYPE_DIRECTORY: u8 = 3;
+const FILETYPE_REGULAR_FILE: u8 = 4;
+
+const OFLAGS_CREAT: u64 = 1;
+const OFLAGS_DIRECTORY: u64 = 2;
+const OFLAGS_EXCL: u64 = 4;
+const OFLAGS_TRUNC: u64 = 8;
+const FDFLAGS_APPEND: u64 = 1;
+
+const WHENCE_SET: u64 = 0;
+const WHENCE_CUR: u64 = 1;
+const WHENCE_END: u64 = 2;
+
+const ALL_RIGHTS: u64 = 0x1fff_ffff;
+
+/// Guest paths the root is preopened as, from fd 3
+const PREOPENS: [&str; 2] = ["/", "."];
+/// Descriptors below this are standard I/O
+const FIRST_FD: u32 = 3;
+
+/// Largest size the program can make a file, so that a bogus size from
+/// the guest can't exhaust the host memory
+const MAX_FILE_SIZE: u64 = 1 << 30;
+
+const FILESTAT_SIZE: usize = 64;
+const TAR_BLOCK_SIZE: usize = 512;
+
+impl Default for VirtualFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualFs {
+    /// An empty filesystem
+    pub fn new() -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(String::new(), Node::Dir);
+        let mut fds = HashMap::new();
+        for (index, preopen) in PREOPENS.iter().enumerate() {
+            let file = OpenFile {
+                path: String::new(),
+                offset: 0,
+                append: false,
+                preopen: Some(preopen),
+            };
+            fds.insert(FIRST_FD + index as u32, file);
+        }
+        Self {
+            nodes,
+            fds,
+            next_fd: FIRST_FD + PREOPENS.len() as u32,
+        }
+    }
+
+    /// A filesystem seeded with the contents of a host directory or a tar
+    /// archive
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        let mut vfs = Self::new();
+        if Path::new(path).is_dir() {
+            vfs.load_dir(Path::new(path), "")?;
+        } else {
+            vfs.load_tar(std::fs::File::open(path)?)?;
+        }
+        Ok(vfs)
+    }
+
+    fn load_dir(&mut self, host_path: &Path, path: &str) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(host_path)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = join(path, &name);
+            if entry.file_type()?.is_dir() {
+                self.insert_seed(&path, Node::Dir);
+                self.load_dir(&entry.path(), &path)?;
+            } else {
+                let file = VirtualFile {
+                    data: std::fs::read(entry.path())?,
+                    ..VirtualFile::default()
+                };
+                self.insert_seed(&path, Node::File(file));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read regular files and directories from a ustar archive
+    fn load_tar<R: Read>(&mut self, mut reader: R) -> std::io::Result<()> {
+        let mut header = [0; TAR_BLOCK_SIZE];
+        loop {
+            reader.read_exact(&mut header)?;
+            if header.iter().all(|byte| *byte == 0) {
+                return Ok(());
+            }
+            let mut name = tar_string(&header[0..100]);
+            // Only POSIX archives have the prefix field, which GNU ones use
+            // for other fields
+            if &header[257..263] == b"ustar\0" {
+                let prefix = tar_string(&header[345..500]);
+                if !prefix.is_empty() {
+                    name = format!("{}/{}", prefix, name);
+                }
+            }
+            let size = tar_size(&header[124..136])?;
+            let mut data = vec![0; size];
+            reader.read_exact(&mut data)?;
+            let padding = (TAR_BLOCK_SIZE - size % TAR_BLOCK_SIZE) % TAR_BLOCK_SIZE;
+            reader.read_exact(&mut vec![0; padding])?;
+
+            let path = match normalize("", &name) {
+                Ok(path) if !path.is_empty() => path,
+                _ => continue,
+            };
+            match header[156] {
+                b'0' | 0 => {
+                    let file = VirtualFile {
+                        data,
+                        ..VirtualFile::default()
+                    };
+                    self.insert_seed(&path, Node::File(file));
+                }
+                b'5' => self.insert_seed(&path, Node::Dir),
+                // Links, devices and extended headers are not supported
+                _ => continue,
+            }
+        }
+    }
+
+    /// Insert a node with its missing parent directories
+    fn insert_seed(&mut self, path: &str, node: Node) {
+        let mut parent = path;
+        while let Some(index) = parent.rfind('/') {
+            parent = &parent[..index];
+            self.nodes.entry(parent.to_string()).or_insert(Node::Dir);
+        }
+        self.nodes.insert(path.to_string(), node);
+    }
+
+    /// Files by their path from the root
+    pub fn files(&self) -> impl Iterator<Item = (&str, &VirtualFile)> {
+        self.nodes.iter().filter_map(|(path, node)| match node {
+            Node::File(file) => Some((path.as_str(), file)),
+            Node::Dir => None,
+        })
+    }
+
+    pub fn file(&self, path: &str) -> Option<&VirtualFile> {
+        match self.nodes.get(&normalize("", path).ok()?) {
+            Some(Node::File(file)) => Some(file),
+            _ => None,
+        }
+    }
+
+    /// Serve a WASI call if it is a filesystem call on a descriptor of this
+    /// filesystem, returning its errno
+    pub(crate) fn call(&mut self, name: &str, args: &[WasmValue], mem: &mut [u8]) -> Option<u16> {
+        let fd_index = match name {
+            "path_symlink" => 2,
+            _ if name.starts_with("fd_") || name.starts_with("path_") => 0,
+            _ => return None,
+        };
+        if arg(args, fd_index) < FIRST_FD as u64 {
+            return None;
+        }
+        Some(match self.dispatch(name, args, mem) {
+            Ok(_) => ESUCCESS,
+            Err(errno) => errno,
+        })
+    }
+
+    fn dispatch(&mut self, name: &str, args: &[WasmValue], mem: &mut [u8]) -> Result<(), Errno> {
+        let a = |index| arg(args, index);
+        match name {
+            "fd_prestat_get" => self.fd_prestat_get(a(0), mem, a(1)),
+            "fd_prestat_dir_name" => self.fd_prestat_dir_name(a(0), mem, a(1), a(2)),
+            "fd_fdstat_get" => self.fd_fdstat_get(a(0), mem, a(1)),
+            "fd_fdstat_set_flags" => self.fd_fdstat_set_flags(a(0), a(1)),
+            "fd_close" => self.fds.remove(&(a(0) as u32)).map(|_| ()).ok_or(EBADF),
+            "fd_sync" | "fd_datasync" | "fd_advise" => self.open_file(a(0)).map(|_| ()),
+            "fd_allocate" => self.fd_allocate(a(0), a(1), a(2)),
+            "fd_read" => self.fd_read(a(0), mem, a(1), a(2), None, a(3)),
+            "fd_pread" => self.fd_read(a(0), mem, a(1), a(2), Some(a(3)), a(4)),
+            "fd_write" => self.fd_write(a(0), mem, a(1), a(2), None, a(3)),
+            "fd_pwrite" => self.fd_write(a(0), mem, a(1), a(2), Some(a(3)), a(4)),
+            "fd_seek" => self.fd_seek(a(0), a(1) as i64, a(2), mem, a(3)),
+            "fd_tell" => self.fd_seek(a(0), 0, WHENCE_CUR, mem, a(1)),
+            "fd_filestat_get" => {
+                let path = self.open_file(a(0))?.path.clone();
+                self.write_filestat(&path, mem, a(1))
+            }
+            "fd_filestat_set_size" => self.fd_filestat_set_size(a(0), a(1)),
+            "fd_readdir" => self.fd_readdir(a(0), mem, a(1), a(2), a(3), a(4)),
+            "path_open" => {
+                let path = self.resolve(a(0), mem, a(2), a(3))?;
+                self.path_open(path, a(4), a(7), mem, a(8))
+            }
+            "path_filestat_get" => {
+                let path = self.resolve(a(0), mem, a(2), a(3))?;
+                self.write_filestat(&path, mem, a(4))
+            }
+            "path_create_directory" => {
+                let path = self.resolve(a(0), mem, a(1), a(2))?;
+                self.path_create_directory(path)
+            }
+            "path_remove_directory" => {
+                let path = self.resolve(a(0), mem, a(1), a(2))?;
+                self.path_remove_directory(path)
+            }
+            "path_unlink_file" => {
+                let path = self.resolve(a(0), mem, a(1), a(2))?;
+                self.path_unlink_file(path)
+            }
+            "path_rename" => {
+                let old_path = self.resolve(a(0), mem, a(1), a(2))?;
+                let new_path = self.resolve(a(3), mem, a(4), a(5))?;
+                self.path_rename(old_path, new_path)
+            }
+            _ => Err(ENOTSUP),
+        }
+    }
+
+    fn open_file(&self, fd: u64) -> Result<&OpenFile, Errno> {
+        self.fds.get(&(fd as u32)).ok_or(EBADF)
+    }
+
+    fn open_file_mut(&mut self, fd: u64) -> Result<&mut OpenFile, Errno> {
+        self.fds.get_mut(&(fd as u32)).ok_or(EBADF)
+    }
+
+    fn node_mut(&mut self, path: &str) -> Result<&mut Node, Errno> {
+        self.nodes.get_mut(path).ok_or(EBADF)
+    }
+
+    fn file_mut(&mut self, path: &str) -> Result<&mut VirtualFile, Errno> {
+        match self.node_mut(path)? {
+            Node::File(file) => Ok(file),
+            Node::Dir => Err(EISDIR),
+        }
+    }
+
+    /// Path of `path` relative to the directory opened as `dir_fd`
+    fn resolve(&self, dir_fd: u64, mem: &[u8], ptr: u64, len: u64) -> Result<String, Errno> {
+        let dir = &self.open_file(dir_fd)?.path;
+        match self.nodes.get(dir) {
+            Some(Node::Dir) => (),
+            _ => return Err(ENOTDIR),
+        }
+        let path = std::str::from_utf8(slice(mem, ptr, len)?).map_err(|_| EILSEQ)?;
+        normalize(dir, path)
+    }
+
+    fn check_parent(&self, path: &str) -> Result<(), Errno> {
+        if path.is_empty() {
+            return Err(EBUSY);
+        }
+        let parent = path.rfind('/').map(|index| &path[..index]).unwrap_or("");
+        match self.nodes.get(parent) {
+            Some(Node::Dir) => Ok(()),
+            Some(Node::File(_)) => Err(ENOTDIR),
+            None => Err(ENOENT),
+        }
+    }
+
+    fn fd_prestat_get(&self, fd: u64, mem: &mut [u8], buf: u64) -> Result<(), Errno> {
+        let name = self.open_file(fd)?.preopen.ok_or(EBADF)?;
+        // `prestat` is a tagged union whose only variant is a directory
+        write_bytes(mem, buf, &[0])?;
+        write_bytes(mem, buf + 4, &(name.len() as u32).to_le_bytes())
+    }
+
+    fn fd_prestat_dir_name(
+        &self,
+        fd: u64,
+        mem: &mut [u8],
+        buf: u64,
+        len: u64,
+    ) -> Result<(), Errno> {
+        let name = self.open_file(fd)?.preopen.ok_or(EBADF)?;
+        if len < name.len() as u64 {
+            return Err(EINVAL);
+        }
+        write_bytes(mem, buf, name.as_bytes())
+    }
+
+    fn fd_fdstat_get(&self, fd: u64, mem: &mut [u8], buf: u64) -> Result<(), Errno> {
+        let file = self.open_file(fd)?;
+        let filetype = match self.nodes.get(&file.path) {
+            Some(Node::Dir) => FILETYPE_DIRECTORY,
+            Some(Node::File(_)) => FILETYPE_REGULAR_FILE,
+            None => return Err(EBADF),
+        };
+        let flags = if file.append { FDFLAGS_APPEND } else { 0 } as u16;
+        write_bytes(mem, buf, &[filetype, 0])?;
+        write_bytes(mem, buf + 2, &flags.to_le_bytes())?;
+        write_bytes(mem, buf + 8, &ALL_RIGHTS.to_le_bytes())?;
+        write_bytes(mem, buf + 16, &ALL_RIGHTS.to_le_bytes())
+    }
+
+    fn fd_fdstat_set_flags(&mut self, fd: u64, flags: u64) -> Result<(), Errno> {
+        self.open_file_mut(fd)?.append = flags & FDFLAGS_APPEND != 0;
+        Ok(())
+    }
+
+    fn fd_allocate(&mut self, fd: u64, offset: u64, len: u64) -> Result<(), Errno> {
+        let path = self.open_file(fd)?.path.clone();
+        let file = self.file_mut(&path)?;
+        let size = offset.checked_add(len).ok_or(EFBIG)?;
+        if (file.data.len() as u64) < size {
+            resize(file, size)?;
+        }
+        Ok(())
+    }
+
+    fn fd_read(
+        &mut self,
+        fd: u64,
+        mem: &mut [u8],
+        iovs: u64,
+        iovs_len: u64,
+        offset: Option<u64>,
+        nread: u64,
+    ) -> Result<(), Errno> {
+        let open_file = self.open_file(fd)?;
+        let file = match self.nodes.get(&open_file.path) {
+            Some(Node::File(file)) => file,
+            Some(Node::Dir) => return Err(EISDIR),
+            None => return Err(EBADF),
+        };
+        let mut position = offset.unwrap_or(open_file.offset);
+        let mut read = 0;
+        for (buf, len) in iovecs(mem, iovs, iovs_len)? {
+            let size = file.data.len() as u64;
+            let start = std::cmp::min(position, size);
+            let end = std::cmp::min(position.checked_add(len).ok_or(EINVAL)?, size);
+            write_bytes(mem, buf, &file.data[start as usize..end as usize])?;
+            read += end - start;
+            position = std::cmp::max(position, end);
+            if end - start < len {
+                break;
+            }
+        }
+        write_bytes(mem, nread, &(read as u32).to_le_bytes())?;
+        if offset.is_none() {
+            self.open_file_mut(fd)?.offset = position;
+        }
+        Ok(())
+    }
+
+    fn fd_write(
+        &mut self,
+        fd: u64,
+        mem: &mut [u8],
+        iovs: u64,
+        iovs_len: u64,
+        offset: Option<u64>,
+        nwritten: u64,
+    ) -> Result<(), Errno> {
+        let mut bytes = Vec::new();
+        for (buf, len) in iovecs(mem, iovs, iovs_len)? {
+            bytes.extend_from_slice(slice(mem, buf, len)?);
+        }
+        let open_file = self.open_file(fd)?;
+        let (path, append, current) = (open_file.path.clone(), open_file.append, open_file.offset);
+        let file = self.file_mut(&path)?;
+        let position = match offset {
+            Some(offset) => offset,
+            None if append => file.data.len() as u64,
+            None => current,
+        };
+        let end = position.checked_add(bytes.len() as u64).ok_or(EFBIG)?;
+        if (file.data.len() as u64) < end {
+            resize(file, end)?;
+        }
+        file.data[position as usize..end as usize].copy_from_slice(&bytes);
+        file.modified = true;
+        write_bytes(mem, nwritten, &(bytes.len() as u32).to_le_bytes())?;
+        if offset.is_none() {
+            self.open_file_mut(fd)?.offset = end;
+        }
+        Ok(())
+    }
+
+    fn fd_seek(
+        &mut self,
+        fd: u64,
+        offset: i64,
+        whence: u64,
+        mem: &mut [u8],
+        new_offset: u64,
+    ) -> Result<(), Errno> {
+        let open_file = self.open_file(fd)?;
+        let size = match self.nodes.get(&open_file.path) {
+            Some(Node::File(file)) => file.data.len() as i64,
+            _ => 0,
+        };
+        let base = match whence {
+            WHENCE_SET => 0,
+            WHENCE_CUR => open_file.offset as i64,
+            WHENCE_END => size,
+            _ => return Err(EINVAL),
+        };
+        let position = base.checked_add(offset).filter(|p| *p >= 0).ok_or(EINVAL)?;
+        write_bytes(mem, new_offset, &(position as u64).to_le_bytes())?;
+        self.open_file_mut(fd)?.offset = position as u64;
+        Ok(())
+    }
+
+    fn fd_filestat_set_size(&mut self, fd: u64, size: u64) -> Result<(), Errno> {
+        let path = self.open_file(fd)?.path.clone();
+        resize(self.file_mut(&path)?, size)
+    }
+
+    fn fd_readdir(
+        &self,
+        fd: u64,
+        mem: &mut [u8],
+        buf: u64,
+        buf_len: u64,
+        cookie: u64,
+        bufused: u64,
+    ) -> Result<(), Errno> {
+        let dir = &self.open_file(fd)?.path;
+        match self.nodes.get(dir) {
+            Some(Node::Dir) => (),
+            _ => return Err(ENOTDIR),
+        }
+        let mut entries = vec![
+            (".".to_string(), FILETYPE_DIRECTORY),
+            ("..".to_string(), FILETYPE_DIRECTORY),
+        ];
+        for (path, node) in self.nodes.iter() {
+            if path.is_empty() || parent(path) != dir.as_str() {
+                continue;
+            }
+            let filetype = match node {
+                Node::Dir => FILETYPE_DIRECTORY,
+                Node::File(_) => FILETYPE_REGULAR_FILE,
+            };
+            let name = path.rsplit('/').next().unwrap_or_default();
+            entries.push((name.to_string(), filetype));
+        }
+        // Entries are written until the buffer is full, and the last one may
+        // be truncated
+        let mut bytes = Vec::new();
+        for (index, (name, filetype)) in entries.iter().enumerate().skip(cookie as usize) {
+            if bytes.len() >= buf_len as usize {
+                break;
+            }
+            let ino = inode(&join(dir, name));
+            bytes.extend_from_slice(&(index as u64 + 1).to_le_bytes());
+            bytes.extend_from_slice(&ino.to_le_bytes());
+            bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&[*filetype, 0, 0, 0]);
+            bytes.extend_from_slice(name.as_bytes());
+        }
+        bytes.truncate(buf_len as usize);
+        write_bytes(mem, buf, &bytes)?;
+        write_bytes(mem, bufused, &(bytes.len() as u32).to_le_bytes())
+    }
+
+    fn path_open(
+        &mut self,
+        path: String,
+        oflags: u64,
+        fdflags: u64,
+        mem: &mut [u8],
+        opened_fd: u64,
+    ) -> Result<(), Errno> {
+        let exclusive = oflags & (OFLAGS_CREAT | OFLAGS_EXCL) == OFLAGS_CREAT | OFLAGS_EXCL;
+        match self.nodes.get_mut(&path) {
+            Some(_) if exclusive => return Err(EEXIST),
+            Some(Node::Dir) if oflags & OFLAGS_TRUNC != 0 => return Err(EISDIR),
+            Some(Node::Dir) => (),
+            Some(Node::File(_)) if oflags & OFLAGS_DIRECTORY != 0 => return Err(ENOTDIR),
+            Some(Node::File(file)) => {
+                if oflags & OFLAGS_TRUNC != 0 {
+                    file.data.clear();
+                    file.modified = true;
+                }
+            }
+            None if oflags & OFLAGS_CREAT == 0 => return Err(ENOENT),
+            None => {
+                self.check_parent(&path)?;
+                let file = VirtualFile {
+                    data: Vec::new(),
+                    created: true,
+                    modified: true,
+                };
+                self.nodes.insert(path.clone(), Node::File(file));
+            }
+        }
+        let fd = self.next_fd;
+        write_bytes(mem, opened_fd, &fd.to_le_bytes())?;
+        self.next_fd += 1;
+        let file = OpenFile {
+            path,
+            offset: 0,
+            append: fdflags & FDFLAGS_APPEND != 0,
+            preopen: None,
+        };
+        self.fds.insert(fd, file);
+        Ok(())
+    }
+
+    fn write_filestat(&self, path: &str, mem: &mut [u8], buf: u64) -> Result<(), Errno> {
+        let (filetype, size) = match self.nodes.get(path) {
+            Some(Node::Dir) => (FILETYPE_DIRECTORY, 0),
+            Some(Node::File(file)) => (FILETYPE_REGULAR_FILE, file.data.len() as u64),
+            None => return Err(ENOENT),
+        };
+        // Timestamps are left zero to keep runs reproducible
+        let mut bytes = [0; FILESTAT_SIZE];
+        bytes[8..16].copy_from_slice(&inode(path).to_le_bytes());
+        bytes[16] = filetype;
+        bytes[24..32].copy_from_slice(&1u64.to_le_bytes());
+        bytes[32..40].copy_from_slice(&size.to_le_bytes());
+        write_bytes(mem, buf, &bytes)
+    }
+
+    fn path_create_directory(&mut self, path: String) -> Result<(), Errno> {
+        if self.nodes.contains_key(&path) {
+            return Err(EEXIST);
+        }
+        self.check_parent(&path)?;
+        self.nodes.insert(path, Node::Dir);
+        Ok(())
+    }
+
+    fn path_remove_directory(&mut self, path: String) -> Result<(), Errno> {
+        self.check_parent(&path)?;
+        match self.nodes.get(&path) {
+            Some(Node::Dir) => (),
+            Some(Node::File(_)) => return Err(ENOTDIR),
+            None => return Err(ENOENT),
+        }
+        if self
+            .nodes
+            .keys()
+            .any(|other| parent(other) == path && *other != path)
+        {
+            return Err(ENOTEMPTY);
+        }
+        self.nodes.remove(&path);
+        Ok(())
+    }
+
+    fn path_unlink_file(&mut self, path: String) -> Result<(), Errno> {
+        match self.nodes.get(&path) {
+            Some(Node::File(_)) => (),
+            Some(Node::Dir) => return Err(EISDIR),
+            None => return Err(ENOENT),
+        }
+        self.nodes.remove(&path);
+        Ok(())
+    }
+
+    fn path_rename(&mut self, old_path: String, new_path: String) -> Result<(), Errno> {
+        self.check_parent(&old_path)?;
+        self.check_parent(&new_path)?;
+        let old_prefix = format!("{}/", old_path);
+        if new_path.starts_with(&old_prefix) {
+            return Err(EINVAL);
+        }
+        match (self.nodes.get(&old_path), self.nodes.get(&new_path)) {
+            (None, _) => return Err(ENOENT),
+            (Some(Node::File(_)), Some(Node::Dir)) => return Err(EISDIR),
+            (Some(Node::Dir), Some(Node::File(_))) => return Err(ENOTDIR),
+            (Some(Node::Dir), Some(Node::Dir)) => {
+                let new_prefix = format!("{}/", new_path);
+                if self.nodes.keys().any(|path| path.starts_with(&new_prefix)) {
+                    return Err(ENOTEMPTY);
+                }
+            }
+            _ => (),
+        }
+        // Move the node and everything under it
+        let moved: Vec<String> = self
+            .nodes
+            .keys()
+            .filter(|path| **path == old_path || path.starts_with(&old_prefix))
+            .cloned()
+            .collect();
+        for path in moved {
+            let node = self.nodes.remove(&path).unwrap();
+            let renamed = format!("{}{}", new_path, &path[old_path.len()..]);
+            self.nodes.insert(renamed, node);
+        }
+        for file in self.fds.values_mut() {
+            if file.path == old_path || file.path.starts_with(&old_prefix) {
+                file.path = format!("{}{}", new_path, &file.path[old_path.len()..]);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resize a file the program writes to, up to `MAX_FILE_SIZE`
+fn resize(file: &mut VirtualFile, size: u64) -> Result<(), Errno> {
+    if size > MAX_FILE_SIZE {
+        return Err(EFBIG);
+    }
+    let size = size as usize;
+    if size > file.data.len() {
+        file.data
+            .try_reserve(size - file.data.len())
+            .map_err(|_| ENOMEM)?;
+    }
+    file.data.resize(size, 0);
+    file.modified = true;
+    Ok(())
+}
+
+pub(crate) fn arg(args: &[WasmValue], index: usize) -> u64 {
+    match args.get(index) {
+        Some(WasmValue::I32(v)) => *v as u32 as u64,
+        Some(WasmValue::I64(v)) => *v as u64,
+        _ => 0,
+    }
+}
+
+/// Resolve `path` relative to the directory `dir` without leaving the root
+fn normalize(dir: &str, path: &str) -> Result<String, Errno> {
+    let mut components: Vec<&str> = dir.split('/').filter(|c| !c.is_empty()).collect();
+    for component in path.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                components.pop().ok_or(ENOTCAPABLE)?;
+            }
+            _ => components.push(component),
+        }
+    }
+    Ok(components.join("/"))
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+fn parent(path: &str) -> &str {
+    path.rfind('/').map(|index| &path[..index]).unwrap_or("")
+}
+
+/// A stable serial number of the node at `path`
+fn inode(path: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    hasher.finish()
+}
+
+pub(crate) fn slice(mem: &[u8], ptr: u64, len: u64) -> Result<&[u8], Errno> {
+    mem.get(ptr as usize..(ptr + len) as usize).ok_or(EFAULT)
+}
+
+pub(crate) fn write_bytes(mem: &mut [u8], ptr: u64, bytes: &[u8]) -> Result<(), Errno> {
+    mem.get_mut(ptr as usize..ptr as usize + bytes.len())
+        .ok_or(EFAULT)?
+        .copy_from_slice(bytes);
+    Ok(())
+}
+
+/// Buffer addresses and lengths of the iovecs at `ptr`
+pub(crate) fn iovecs(mem: &[u8], ptr: u64, count: u64) -> Result<Vec<(u64, u64)>, Errno> {
+    let bytes = slice(mem, ptr, count * 8)?;
+    Ok(bytes
+        .chunks(8)
+        .map(|iovec| {
+            let buf = u32::from_le_bytes([iovec[0], iovec[1], iovec[2], iovec[3]]);
+            let len = u32::from_le_bytes([iovec[4], iovec[5], iovec[6], iovec[7]]);
+            (buf as u64, len as u64)
+        })
+        .collect())
+}
+
+fn tar_string(field: &[u8]) -> String {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+fn tar_size(field: &[u8]) -> std::io::Result<usize> {
+    let size = tar_string(field);
+    if size.trim().is_empty() {
+        return Ok(0);
+    }
+    usize::from_str_radix(size.trim(), 8).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("invalid size in tar header: {:?}", size),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use WasmValue::{I32, I64};
+
+    /// Scratch memory: the path at 0, iovecs at 16, data at 64 and results
+    /// from 128
+    const PATH: u64 = 0;
+    const IOVS: u64 = 16;
+    const DATA: u64 = 64;
+    const RESULT: u64 = 128;
+
+    fn call(vfs: &mut VirtualFs, mem: &mut [u8], name: &str, args: &[WasmValue]) -> Errno {
+        vfs.call(name, args, mem).unwrap()
+    }
+
+    fn result(mem: &[u8]) -> u64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&mem[RESULT as usize..RESULT as usize + 8]);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// Open `file.txt` in the root, creating it
+    fn open(vfs: &mut VirtualFs, mem: &mut [u8]) -> i32 {
+        mem[..8].copy_from_slice(b"file.txt");
+        let args = [
+            I32(3),
+            I32(0),
+            I32(PATH as i32),
+            I32(8),
+            I32(OFLAGS_CREAT as i32),
+            I64(ALL_RIGHTS as i64),
+            I64(ALL_RIGHTS as i64),
+            I32(0),
+            I32(RESULT as i32),
+        ];
+        assert_eq!(call(vfs, mem, "path_open", &args), ESUCCESS);
+        result(mem) as i32
+    }
+
+    /// Point a single iovec at `len` bytes of the data area
+    fn iovec(mem: &mut [u8], len: u32) {
+        mem[IOVS as usize..IOVS as usize + 4].copy_from_slice(&(DATA as u32).to_le_bytes());
+        mem[IOVS as usize + 4..IOVS as usize + 8].copy_from_slice(&len.to_le_bytes());
+    }
+
+    fn write(vfs: &mut VirtualFs, mem: &mut [u8], fd: i32, bytes: &[u8]) -> Errno {
+        mem[DATA as usize..DATA as usize + bytes.len()].copy_from_slice(bytes);
+        iovec(mem, bytes.len() as u32);
+        let args = [I32(fd), I32(IOVS as i32), I32(1), I32(RESULT as i32)];
+        call(vfs, mem, "fd_write", &args)
+    }
+
+    fn read(vfs: &mut VirtualFs, mem: &mut [u8], fd: i32, len: u32) -> Vec<u8> {
+        iovec(mem, len);
+        let args = [I32(fd), I32(IOVS as i32), I32(1), I32(RESULT as i32)];
+        assert_eq!(call(vfs, mem, "fd_read", &args), ESUCCESS);
+        let read = result(mem) as u32 as usize;
+        mem[DATA as usize..DATA as usize + read].to_vec()
+    }
+
+    fn seek(vfs: &mut VirtualFs, mem: &mut [u8], fd: i32, offset: i64, whence: u64) -> Errno {
+        let args = [I32(fd), I64(offset), I32(whence as i32), I32(RESULT as i32)];
+        call(vfs, mem, "fd_seek", &args)
+    }
+
+    fn data(vfs: &VirtualFs) -> &[u8] {
+        &vfs.file("file.txt").unwrap().data
+    }
+
+    #[test]
+    fn write_seek_and_read() {
+        let (mut vfs, mut mem) = (VirtualFs::new(), vec![0; 256]);
+        let fd = open(&mut vfs, &mut mem);
+        assert_eq!(write(&mut vfs, &mut mem, fd, b"hello"), ESUCCESS);
+        assert_eq!(result(&mem) as u32, 5);
+        assert_eq!(data(&vfs), b"hello");
+
+        assert_eq!(seek(&mut vfs, &mut mem, fd, 1, WHENCE_SET), ESUCCESS);
+        assert_eq!(read(&mut vfs, &mut mem, fd, 3), b"ell");
+        assert_eq!(seek(&mut vfs, &mut mem, fd, -2, WHENCE_END), ESUCCESS);
+        assert_eq!(result(&mem), 3);
+        assert_eq!(read(&mut vfs, &mut mem, fd, 8), b"lo");
+        // Reading at the end leaves the offset there
+        assert_eq!(read(&mut vfs, &mut mem, fd, 8), b"");
+        assert_eq!(seek(&mut vfs, &mut mem, fd, 0, WHENCE_CUR), ESUCCESS);
+        assert_eq!(result(&mem), 5);
+
+        // Writing past the end fills the gap with zeros
+        assert_eq!(seek(&mut vfs, &mut mem, fd, 7, WHENCE_SET), ESUCCESS);
+        assert_eq!(write(&mut vfs, &mut mem, fd, b"!"), ESUCCESS);
+        assert_eq!(data(&vfs), b"hello\0\0!");
+
+        assert_eq!(seek(&mut vfs, &mut mem, fd, -1, WHENCE_SET), EINVAL);
+        assert_eq!(seek(&mut vfs, &mut mem, fd, 0, 3), EINVAL);
+    }
+
+    #[test]
+    fn allocate_and_truncate() {
+        let (mut vfs, mut mem) = (VirtualFs::new(), vec![0; 256]);
+        let fd = open(&mut vfs, &mut mem);
+        assert_eq!(write(&mut vfs, &mut mem, fd, b"hello"), ESUCCESS);
+
+        let allocate = |vfs: &mut VirtualFs, mem: &mut [u8], offset: u64, len: u64| {
+            let args = [I32(fd), I64(offset as i64), I64(len as i64)];
+            call(vfs, mem, "fd_allocate", &args)
+        };
+        assert_eq!(allocate(&mut vfs, &mut mem, 4, 4), ESUCCESS);
+        assert_eq!(data(&vfs), b"hello\0\0\0");
+        // Allocating within the file keeps its size
+        assert_eq!(allocate(&mut vfs, &mut mem, 0, 2), ESUCCESS);
+        assert_eq!(data(&vfs).len(), 8);
+
+        let set_size = |vfs: &mut VirtualFs, mem: &mut [u8], size: u64| {
+            let args = [I32(fd), I64(size as i64)];
+            call(vfs, mem, "fd_filestat_set_size", &args)
+        };
+        assert_eq!(set_size(&mut vfs, &mut mem, 2), ESUCCESS);
+        assert_eq!(data(&vfs), b"he");
+        assert_eq!(set_size(&mut vfs, &mut mem, 4), ESUCCESS);
+        assert_eq!(data(&vfs), b"he\0\0");
+    }
+
+    #[test]
+    fn sizes_overflowing_or_over_the_cap() {
+        let (mut vfs, mut mem) = (VirtualFs::new(), vec![0; 256]);
+        let fd = open(&mut vfs, &mut mem);
+        assert_eq!(write(&mut vfs, &mut mem, fd, b"hello"), ESUCCESS);
+
+        let cases: &[(&str, Vec<WasmValue>, Errno)] = &[
+            ("fd_allocate", vec![I32(fd), I64(-1), I64(2)], EFBIG),
+            (
+                "fd_allocate",
+                vec![I32(fd), I64(0), I64(MAX_FILE_SIZE as i64 + 1)],
+                EFBIG,
+            ),
+            ("fd_filestat_set_size", vec![I32(fd), I64(-1)], EFBIG),
+            (
+                "fd_filestat_set_size",
+                vec![I32(fd), I64(MAX_FILE_SIZE as i64 + 1)],
+                EFBIG,
+            ),
+            (
+                "fd_pwrite",
+                vec![
+                    I32(fd),
+                    I32(IOVS as i32),
+                    I32(1),
+                    I64(-2),
+                    I32(RESULT as i32),
+                ],
+                EFBIG,
+            ),
+            (
+                "fd_pwrite",
+                vec![
+                    I32(fd),
+                    I32(IOVS as i32),
+                    I32(1),
+                    I64(MAX_FILE_SIZE as i64),
+                    I32(RESULT as i32),
+                ],
+                EFBIG,
+            ),
+            (
+                "fd_pread",
+                vec![
+                    I32(fd),
+                    I32(IOVS as i32),
+                    I32(1),
+                    I64(-2),
+                    I32(RESULT as i32),
+                ],
+                EINVAL,
+            ),
+        ];
+        iovec(&mut mem, 4);
+        for (name, args, errno) in cases {
+            assert_eq!(
+                call(&mut vfs, &mut mem, name, args),
+                *errno,
+                "{} {:?}",
+                name,
+                args
+            );
+            assert_eq!(data(&vfs), b"hello");
+        }
+
+        // Reading far past the end reads nothing
+        let args = [
+            I32(fd),
+            I32(IOVS as i32),
+            I32(1),
+            I64(1 << 40),
+            I32(RESULT as i32),
+        ];
+        assert_eq!(call(&mut vfs, &mut mem, "fd_pread", &args), ESUCCESS);
+        assert_eq!(result(&mem) as u32, 0);
+    }
+}
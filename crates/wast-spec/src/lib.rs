@@ -5,8 +5,10 @@ use std::path::Path;
 use std::str;
 mod spectest;
 pub use spectest::instantiate_spectest;
-use wasminspect_vm::{simple_invoke_func, FuncAddr, ModuleIndex, WasmInstance, WasmValue};
-use wasmparser::{validate, ModuleReader};
+use wasminspect_vm::{
+    simple_invoke_func, FuncAddr, ModuleIndex, ValidationError, WasmInstance, WasmValue,
+};
+use wasmparser::ModuleReader;
 
 pub struct WastContext {
     module_index_by_name: HashMap<String, ModuleIndex>,
@@ -43,17 +45,12 @@ impl WastContext {
         }
         return Ok(None);
     }
-    pub fn instantiate<'a>(&self, bytes: &'a [u8]) -> Result<ModuleReader<'a>> {
-        validate(bytes, None)?;
-        Ok(ModuleReader::new(bytes)?)
-    }
     fn module(&mut self, module_name: Option<&str>, bytes: &[u8]) -> Result<()> {
-        let module = self.instantiate(&bytes)?;
-        let start_section = Self::extract_start_section(bytes)?;
         let module_index = self
             .instance
-            .load_module_from_module(module_name.map(|n| n.to_string()), module)
-            .map_err(|e| anyhow!("Failed to instantiate: {}", e))?;
+            .load_module_from_bytes(module_name.map(|n| n.to_string()), bytes)
+            .context("Failed to instantiate")?;
+        let start_section = Self::extract_start_section(bytes)?;
         if let Some(start_section) = start_section {
             let func_addr = FuncAddr::new_unsafe(module_index, start_section as usize);
             simple_invoke_func(func_addr, vec![], &mut self.instance.store)
@@ -154,15 +151,14 @@ impl WastContext {
                         }
                         Err(e) => e,
                     };
-                    let error_message = format!("{:?}", err);
-                    if !error_message.contains(&message) {
-                        // TODO: change to panic!
-                        println!(
-                            "{}\nassert_malformed: expected {}, got {}",
+                    match err.downcast_ref::<ValidationError>() {
+                        Some(err) if spec_message_matches(err.message(), message) => {}
+                        _ => panic!(
+                            "{}\nassert_malformed: expected {}, got {:?}",
                             context(span),
                             message,
-                            error_message
-                        )
+                            err
+                        ),
                     }
                 }
                 AssertUnlinkable {
@@ -209,15 +205,14 @@ impl WastContext {
                         Ok(()) => panic!("{}\nexpected module to fail to build", context(span)),
                         Err(e) => e,
                     };
-                    let error_message = format!("{:?}", err);
-                    if !error_message.contains(&message) {
-                        // TODO: change to panic!
-                        println!(
-                            "{}\nassert_invalid: expected {}, got {}",
+                    match err.downcast_ref::<ValidationError>() {
+                        Some(err) if spec_message_matches(err.message(), message) => {}
+                        _ => panic!(
+                            "{}\nassert_invalid: expected {}, got {:?}",
                             context(span),
                             message,
-                            error_message
-                        )
+                            err
+                        ),
                     }
                 }
             }
@@ -274,12 +269,11 @@ impl WastContext {
             }
             wast::WastExecute::Module(mut module) => {
                 let binary = module.encode()?;
-                let module = self.instantiate(&binary)?;
-                let start_section = Self::extract_start_section(&binary)?;
                 let module_index = self
                     .instance
-                    .load_module_from_module(None, module)
+                    .load_module_from_bytes(None, &binary)
                     .map_err(|e| anyhow!("{}", e))?;
+                let start_section = Self::extract_start_section(&binary)?;
                 if let Some(start_section) = start_section {
                     let func_addr = FuncAddr::new_unsafe(module_index, start_section as usize);
                     return Ok(
@@ -294,6 +288,65 @@ impl WastContext {
     }
 }
 
+// wasmparser words some errors differently from the spec interpreter.
+// Each entry lists the wasmparser messages accepted for a spec message.
+const SPEC_MESSAGE_ALIASES: &[(&str, &[&str])] = &[
+    ("magic header not detected", &["Bad magic number"]),
+    (
+        "unknown binary version",
+        &["Bad version number", "bad wasm file version"],
+    ),
+    (
+        "unexpected end",
+        &[
+            "Unexpected EOF",
+            "unexpected end of function",
+            "Section body extends past end of file",
+        ],
+    ),
+    (
+        "length out of bounds",
+        &["Unexpected EOF", "Section body extends past end of file"],
+    ),
+    (
+        "section size mismatch",
+        &[
+            "Unexpected data at the end of the section",
+            "Section body extends past end of file",
+        ],
+    ),
+    ("integer representation too long", &["Invalid var_"]),
+    ("integer too large", &["Invalid var_"]),
+    ("malformed section id", &["Invalid section code"]),
+    ("malformed import kind", &["Invalid external kind"]),
+    ("malformed UTF-8 encoding", &["invalid UTF-8 encoding"]),
+    ("zero flag expected", &["reserved byte must be zero"]),
+    (
+        "too many locals",
+        &[
+            "locals exceed maximum",
+            "locals_total is out of bounds",
+            "local_count is out of bounds",
+        ],
+    ),
+    ("junk after last section", &["section out of order"]),
+    (
+        "unexpected content after last section",
+        &["section out of order"],
+    ),
+    ("start function", &["invlid start function type"]),
+];
+
+fn spec_message_matches(actual: &str, expected: &str) -> bool {
+    if actual.contains(expected) {
+        return true;
+    }
+    SPEC_MESSAGE_ALIASES
+        .iter()
+        .filter(|(spec, _)| expected.starts_with(spec))
+        .any(|(_, aliases)| aliases.iter().any(|alias| actual.contains(alias)))
+}
+
 fn val_matches(actual: &WasmValue, expected: &wast::AssertExpression) -> Result<bool> {
     Ok(match (actual, expected) {
         (WasmValue::I32(a), wast::AssertExpression::I32(x)) => a == x,
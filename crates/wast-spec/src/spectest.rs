@@ -1,6 +1,4 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
-use std::rc::Rc;
 use wasminspect_vm::*;
 use wasmparser::{FuncType, GlobalType, Type};
 
@@ -82,7 +80,7 @@ pub fn instantiate_spectest() -> HashMap<String, HostValue> {
     }));
     module.insert("print_f64_f64".to_string(), func);
 
-    let create_glbal = |value, ty| Rc::new(RefCell::new(HostGlobal::new(value, ty)));
+    let create_glbal = |value, ty| HostGlobal::new(value, ty);
     module.insert(
         "global_i32".to_string(),
         HostValue::Global(create_glbal(
@@ -124,10 +122,10 @@ pub fn instantiate_spectest() -> HashMap<String, HostValue> {
         )),
     );
 
-    let table = Rc::new(RefCell::new(HostTable::new(10, Some(20))));
+    let table = HostTable::new(10, Some(20));
     module.insert("table".to_string(), HostValue::Table(table));
 
-    let mem = Rc::new(RefCell::new(HostMemory::new(1, Some(2))));
+    let mem = HostMemory::new(1, Some(2)).expect("failed to allocate spectest memory");
     module.insert("memory".to_string(), HostValue::Mem(mem));
     module
 }
@@ -1,6 +1,7 @@
 use env_logger;
 use structopt::StructOpt;
-use wasminspect_cli;
+use wasminspect_cli::{self, parse_env, DebuggerOptions, OutputRedirect, WasiConfig, WasiTrace};
+use wasminspect_vm::{ImportStub, ResourceLimits};
 
 #[derive(StructOpt)]
 struct Opts {
@@ -10,13 +11,114 @@ struct Opts {
     /// Tells the debugger to read in and execute the debugger commands in given file, after wasm file has been loaded
     #[structopt(short, long)]
     source: Option<String>,
+    /// Load the wasm file even if it fails validation
+    #[structopt(long)]
+    skip_validation: bool,
+    /// Validate all function bodies on load. By default they are left unchecked until first called
+    #[structopt(long)]
+    validate_bodies: bool,
+    /// Stub imports which no module provides. Calls to them either trap or log and return zeros
+    #[structopt(long, value_name = "trap|log", possible_values = &["trap", "log"])]
+    stub_missing_imports: Option<ImportStub>,
+    /// Load another module before FILE so that it can import from it, like `libfoo=libfoo.wasm`
+    #[structopt(long = "module", value_name = "NAME=FILE", number_of_values = 1, parse(try_from_str = parse_module))]
+    modules: Vec<(String, String)>,
+    /// Fail to allocate memories over this many bytes in total
+    #[structopt(long, value_name = "BYTES")]
+    max_memory: Option<usize>,
+    /// Fail to allocate a table with more elements than this, per table
+    #[structopt(long, value_name = "N")]
+    max_table_elements: Option<usize>,
+    /// Fail to instantiate more modules than this
+    #[structopt(long, value_name = "N")]
+    max_instances: Option<usize>,
+    /// Preopen a host directory for WASI, as GUEST_DIR if given
+    #[structopt(long = "dir", value_name = "HOST_DIR[:GUEST_DIR]", number_of_values = 1, parse(from_str = parse_dir))]
+    dirs: Vec<(String, String)>,
+    /// Pass an environment variable to WASI
+    #[structopt(long = "env", value_name = "KEY=VALUE", number_of_values = 1, parse(try_from_str = parse_env))]
+    envs: Vec<(String, String)>,
+    /// Serve files to WASI from memory, seeded with a host directory or a tar archive
+    #[structopt(long, value_name = "DIR|TAR")]
+    vfs: Option<String>,
+    /// Log WASI calls made by the program to stderr
+    #[structopt(long)]
+    trace_wasi: bool,
+    /// Log only calls to these WASI functions, like `fd_write,path_open`
+    #[structopt(long, value_name = "FUNCTIONS", use_delimiter = true)]
+    trace_wasi_filter: Vec<String>,
+    /// Read the program's stdin from a file instead of the terminal
+    #[structopt(long, value_name = "FILE")]
+    stdin: Option<String>,
+    /// Where the program's stdout goes. `prefix` marks each line with `[stdout]` and `capture` keeps it for `process output`
+    #[structopt(
+        long,
+        value_name = "inherit|prefix|capture|file:PATH",
+        default_value = "prefix"
+    )]
+    stdout: OutputRedirect,
+    /// Where the program's stderr goes, like --stdout
+    #[structopt(
+        long,
+        value_name = "inherit|prefix|capture|file:PATH",
+        default_value = "prefix"
+    )]
+    stderr: OutputRedirect,
+    /// Stop at the start of `_initialize` when running a WASI reactor
+    #[structopt(long)]
+    stop_at_initialize: bool,
+    /// Arguments passed to the program
+    #[structopt(last = true)]
+    args: Vec<String>,
+}
+
+fn parse_module(arg: &str) -> Result<(String, String), String> {
+    let mut parts = arg.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(name), Some(file)) if !name.is_empty() => Ok((name.to_string(), file.to_string())),
+        _ => Err(format!("expected NAME=FILE: {}", arg)),
+    }
+}
+
+fn parse_dir(arg: &str) -> (String, String) {
+    let mut parts = arg.splitn(2, ':');
+    let host = parts.next().unwrap_or_default().to_string();
+    let guest = parts
+        .next()
+        .map(str::to_string)
+        .unwrap_or_else(|| host.clone());
+    (host, guest)
 }
 
 fn main() {
     env_logger::init_from_env(env_logger::Env::default().default_filter_or("warn"));
 
     let opts = Opts::from_args();
-    match wasminspect_cli::run_loop(opts.filepath, opts.source) {
+    let options = DebuggerOptions {
+        skip_validation: opts.skip_validation,
+        validate_bodies: opts.validate_bodies,
+        import_stub: opts.stub_missing_imports,
+        limits: ResourceLimits {
+            max_memory_bytes: opts.max_memory,
+            max_table_elements: opts.max_table_elements,
+            max_instances: opts.max_instances,
+        },
+        wasi: WasiConfig {
+            args: opts.filepath.iter().cloned().chain(opts.args).collect(),
+            envs: opts.envs,
+            preopen_dirs: opts.dirs,
+            trace: WasiTrace {
+                enabled: opts.trace_wasi || !opts.trace_wasi_filter.is_empty(),
+                filter: opts.trace_wasi_filter,
+            },
+            vfs: opts.vfs,
+            stdin: opts.stdin,
+            stdout: opts.stdout,
+            stderr: opts.stderr,
+        },
+        stop_at_initialize: opts.stop_at_initialize,
+    };
+    match wasminspect_cli::run_loop(opts.filepath, opts.source, options, opts.modules) {
         Err(err) => println!("{:?}", err),
         _ => {}
     }
@@ -4,7 +4,7 @@ use wast_spec::instantiate_spectest;
 
 use std::path::Path;
 
-fn run_wasm(filename: &str, func: &str, args: Vec<WasmValue>, results: Vec<WasmValue>) {
+fn instantiate(filename: &str) -> (WasmInstance, ModuleIndex) {
     let example_dir = Path::new(file!()).parent().unwrap().join("simple-example");
     let mut instance = WasmInstance::new();
     let spectest = instantiate_spectest();
@@ -16,6 +16,11 @@ fn run_wasm(filename: &str, func: &str, args: Vec<WasmValue>, results: Vec<WasmV
         )
         .ok()
         .unwrap();
+    (instance, module_index)
+}
+
+fn run_wasm(filename: &str, func: &str, args: Vec<WasmValue>, results: Vec<WasmValue>) {
+    let (mut instance, module_index) = instantiate(filename);
     match instance.run(module_index, Some(func.to_string()), args) {
         Ok(result) => assert_eq!(result, results),
         Err(err) => panic!("{}", err),
@@ -51,3 +56,645 @@ fn test_calc_call() {
         vec![WasmValue::I32(7)],
     );
 }
+
+#[test]
+fn test_calc_step_execution() {
+    let (instance, module_index) = instantiate("calc.wasm");
+    let module = instance
+        .store
+        .module(module_index)
+        .unwrap()
+        .defined()
+        .unwrap();
+    let func_addr = module
+        .exported_func("call_add".to_string())
+        .ok()
+        .unwrap()
+        .unwrap();
+    let args = vec![WasmValue::I32(3), WasmValue::I32(4)];
+    let mut execution = Execution::new(func_addr, args, &instance.store).unwrap();
+    let interceptor = NopInterceptor::new();
+
+    // local.get 0, local.get 1, call $add
+    for _ in 0..3 {
+        let reason = execution.step(&instance.store, &interceptor).unwrap();
+        assert_eq!(reason, StopReason::Step);
+    }
+    assert_eq!(execution.frames().len(), 2);
+    assert_eq!(execution.locals(), &[WasmValue::I32(3), WasmValue::I32(4)]);
+
+    let reason = execution.step_out(&instance.store, &interceptor).unwrap();
+    assert_eq!(reason, StopReason::Step);
+    assert_eq!(execution.frames().len(), 1);
+    assert_eq!(execution.stack_values(), vec![&WasmValue::I32(7)]);
+
+    let reason = execution.resume(&instance.store, &interceptor).unwrap();
+    assert_eq!(reason, StopReason::Finish(vec![WasmValue::I32(7)]));
+    assert!(execution.is_finished());
+}
+
+#[test]
+fn test_calc_on_worker_threads() {
+    let handles: Vec<_> = (0..4)
+        .map(|i| {
+            let (mut instance, module_index) = instantiate("calc.wasm");
+            std::thread::spawn(move || {
+                let args = vec![WasmValue::I32(i), WasmValue::I32(i)];
+                instance.run(module_index, Some("add".to_string()), args)
+            })
+        })
+        .collect();
+    for (i, handle) in handles.into_iter().enumerate() {
+        let result = handle.join().unwrap().ok().unwrap();
+        assert_eq!(result, vec![WasmValue::I32(i as i32 * 2)]);
+    }
+}
+
+#[test]
+fn test_calc_instantiate_many_times() {
+    let example_dir = Path::new(file!()).parent().unwrap().join("simple-example");
+    let bytes = std::fs::read(example_dir.join("calc.wasm")).unwrap();
+    let module = Module::from_bytes(&bytes).unwrap();
+    for i in 0..3 {
+        let mut instance = WasmInstance::new();
+        instance.load_host_module("spectest".to_string(), instantiate_spectest());
+        let module_index = instance.instantiate_module(None, &module).unwrap();
+        let args = vec![WasmValue::I32(i), WasmValue::I32(1)];
+        let result = instance.run(module_index, Some("add".to_string()), args);
+        assert_eq!(result.ok().unwrap(), vec![WasmValue::I32(i + 1)]);
+    }
+}
+
+#[test]
+fn test_calc_lazy_decoding() {
+    let example_dir = Path::new(file!()).parent().unwrap().join("simple-example");
+    let bytes = std::fs::read(example_dir.join("calc.wasm")).unwrap();
+    let module = Module::from_bytes(&bytes).unwrap();
+    assert!(module.funcs().iter().all(|f| !f.code.is_decoded()));
+
+    let mut instance = WasmInstance::new();
+    instance.load_host_module("spectest".to_string(), instantiate_spectest());
+    let module_index = instance.instantiate_module(None, &module).unwrap();
+    let args = vec![WasmValue::I32(1), WasmValue::I32(2)];
+    let result = instance.run(module_index, Some("call_add".to_string()), args);
+    assert_eq!(result.ok().unwrap(), vec![WasmValue::I32(3)]);
+
+    // add, mul, call_add, print_added
+    let decoded: Vec<bool> = module.funcs().iter().map(|f| f.code.is_decoded()).collect();
+    assert_eq!(decoded, vec![true, false, true, false]);
+}
+
+#[test]
+fn test_malformed_body_is_reported_on_call() {
+    #[rustfmt::skip]
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // (type (func))
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+        // (func (type 0))
+        0x03, 0x02, 0x01, 0x00,
+        // (export "run" (func 0))
+        0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e, 0x00, 0x00,
+        // an unknown opcode
+        0x0a, 0x05, 0x01, 0x03, 0x00, 0xff, 0x0b,
+    ];
+    let module = Module::from_bytes(&bytes).unwrap();
+    assert!(!module.funcs()[0].code.is_decoded());
+
+    let mut instance = WasmInstance::new();
+    let module_index = instance.instantiate_module(None, &module).unwrap();
+    let err = instance
+        .run(module_index, Some("run".to_string()), vec![])
+        .unwrap_err();
+    assert!(err.to_string().ends_with("Unknown opcode (at offset 32)"));
+}
+
+#[test]
+fn test_validation_error_location() {
+    #[rustfmt::skip]
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // (type (func))
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+        // (func (type 0))
+        0x03, 0x02, 0x01, 0x00,
+        // i32.add with an empty stack
+        0x0a, 0x05, 0x01, 0x03, 0x00, 0x6a, 0x0b,
+    ];
+    let mut instance = WasmInstance::new();
+    let err = instance.load_module_from_bytes(None, &bytes).unwrap_err();
+    let err = err.downcast::<ValidationError>().unwrap();
+    assert!(!err.is_malformed());
+    assert_eq!(err.func_index(), Some(0));
+    assert_eq!(err.offset(), 23);
+
+    let err = validate(&bytes[..20]).unwrap_err();
+    assert!(err.is_malformed());
+
+    instance.store.set_validation(false);
+    assert!(instance.load_module_from_bytes(None, &bytes).is_ok());
+}
+
+/// Exports `run`, which calls the imported `env.foo`
+#[rustfmt::skip]
+const CALL_IMPORT_MODULE: [u8; 49] = [
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    // (type (func (result i32)))
+    0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f,
+    // (import "env" "foo" (func (type 0)))
+    0x02, 0x0b, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x03, 0x66, 0x6f, 0x6f, 0x00, 0x00,
+    // (func (type 0))
+    0x03, 0x02, 0x01, 0x00,
+    // (export "run" (func 1))
+    0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e, 0x00, 0x01,
+    // call 0
+    0x0a, 0x06, 0x01, 0x04, 0x00, 0x10, 0x00, 0x0b,
+];
+
+#[test]
+fn test_unresolved_import_stubs() {
+    let bytes = &CALL_IMPORT_MODULE;
+    let mut instance = WasmInstance::new();
+    let err = instance.load_module_from_bytes(None, bytes).unwrap_err();
+    match err.downcast_ref::<StoreError>() {
+        Some(StoreError::UnresolvedImports(imports)) => {
+            assert_eq!(imports.len(), 1);
+            assert_eq!(imports[0].module, "env");
+            assert_eq!(imports[0].field, "foo");
+        }
+        _ => panic!("unexpected error: {}", err),
+    }
+
+    instance.store.set_import_stub(Some(ImportStub::Trap));
+    let module_index = instance.load_module_from_bytes(None, bytes).unwrap();
+    let err = instance
+        .run(module_index, Some("run".to_string()), vec![])
+        .unwrap_err();
+    assert!(err.to_string().contains("unresolved import \"env.foo\""));
+
+    instance.store.set_import_stub(Some(ImportStub::Log));
+    let module_index = instance.load_module_from_bytes(None, bytes).unwrap();
+    let result = instance.run(module_index, Some("run".to_string()), vec![]);
+    assert_eq!(result.unwrap(), vec![WasmValue::I32(0)]);
+}
+
+#[test]
+fn test_replace_imported_func() {
+    let bytes = &CALL_IMPORT_MODULE;
+    let mut instance = WasmInstance::new();
+    instance.store.set_import_stub(Some(ImportStub::Trap));
+    let module_index = instance.load_module_from_bytes(None, bytes).unwrap();
+    let ty = match instance.store.func(FuncAddr::new_unsafe(module_index, 0)) {
+        Some((func, _)) => func.ty().clone(),
+        None => panic!("import is not linked"),
+    };
+    let body = HostFuncBody::new(ty, |_, results, _, _| {
+        results.push(WasmValue::I32(42));
+        Ok(())
+    });
+    instance
+        .store
+        .replace_func(
+            FuncAddr::new_unsafe(module_index, 0),
+            "env".to_string(),
+            "foo".to_string(),
+            body,
+        )
+        .unwrap();
+    let result = instance.run(module_index, Some("run".to_string()), vec![]);
+    assert_eq!(result.unwrap(), vec![WasmValue::I32(42)]);
+}
+
+#[test]
+fn test_exit_from_host_func() {
+    let bytes = &CALL_IMPORT_MODULE;
+    let mut instance = WasmInstance::new();
+    instance.store.set_import_stub(Some(ImportStub::Trap));
+    let module_index = instance.load_module_from_bytes(None, bytes).unwrap();
+    let ty = match instance.store.func(FuncAddr::new_unsafe(module_index, 0)) {
+        Some((func, _)) => func.ty().clone(),
+        None => panic!("import is not linked"),
+    };
+    let body = HostFuncBody::new(ty, |_, _, _, _| Err(Trap::Exit(3)));
+    instance
+        .store
+        .replace_func(
+            FuncAddr::new_unsafe(module_index, 0),
+            "env".to_string(),
+            "foo".to_string(),
+            body,
+        )
+        .unwrap();
+    match instance.run(module_index, Some("run".to_string()), vec![]) {
+        Err(WasmError::Exit(code)) => assert_eq!(code, 3),
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    let module = instance
+        .store
+        .module(module_index)
+        .unwrap()
+        .defined()
+        .unwrap();
+    let func_addr = module
+        .exported_func("run".to_string())
+        .ok()
+        .unwrap()
+        .unwrap();
+    let mut execution = Execution::new(func_addr, vec![], &instance.store).unwrap();
+    let interceptor = NopInterceptor::new();
+    let reason = execution.resume(&instance.store, &interceptor).unwrap();
+    assert_eq!(reason, StopReason::Exit(3));
+    assert!(execution.is_finished());
+    let reason = execution.step(&instance.store, &interceptor).unwrap();
+    assert_eq!(reason, StopReason::Exit(3));
+}
+
+// Exports the imported `env.abort` as "abort", so that it is the entry
+// function itself
+#[rustfmt::skip]
+const REEXPORT_IMPORT_MODULE: [u8; 40] = [
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    // (type (func))
+    0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+    // (import "env" "abort" (func (type 0)))
+    0x02, 0x0d, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x05, 0x61, 0x62, 0x6f, 0x72, 0x74, 0x00, 0x00,
+    // (export "abort" (func 0))
+    0x07, 0x09, 0x01, 0x05, 0x61, 0x62, 0x6f, 0x72, 0x74, 0x00, 0x00,
+];
+
+#[test]
+fn test_trap_of_host_entry_func() {
+    let mut instance = WasmInstance::new();
+    instance.store.set_import_stub(Some(ImportStub::Trap));
+    let module_index = instance
+        .load_module_from_bytes(None, &REEXPORT_IMPORT_MODULE)
+        .unwrap();
+    let err = instance
+        .run(module_index, Some("abort".to_string()), vec![])
+        .unwrap_err();
+    match err {
+        WasmError::ExecutionError(Trap::UnresolvedImport {
+            ref module,
+            ref field,
+        }) => {
+            assert_eq!((module.as_str(), field.as_str()), ("env", "abort"))
+        }
+        ref err => panic!("unexpected error: {:?}", err),
+    }
+
+    let ty = match instance.store.func(FuncAddr::new_unsafe(module_index, 0)) {
+        Some((func, _)) => func.ty().clone(),
+        None => panic!("import is not linked"),
+    };
+    let body = HostFuncBody::new(ty, |_, _, _, _| Err(Trap::Abort("boom".to_string())));
+    instance
+        .store
+        .replace_func(
+            FuncAddr::new_unsafe(module_index, 0),
+            "env".to_string(),
+            "abort".to_string(),
+            body,
+        )
+        .unwrap();
+    let err = instance
+        .run(module_index, Some("abort".to_string()), vec![])
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Failed to execute: aborted: boom");
+}
+
+// Side modules with a `dylink.0` section. The library stores 42 at its
+// `__memory_base` and exports it as `value`, and the main module adds
+// `value` read through `GOT.mem` to the result of the library's `get`.
+const DYLINK_LIB_MODULE: [u8; 122] = [
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x00, 0x0f, 0x08, 0x64, 0x79, 0x6c, 0x69, 0x6e,
+    0x6b, 0x2e, 0x30, 0x01, 0x04, 0x04, 0x02, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f,
+    0x02, 0x24, 0x02, 0x03, 0x65, 0x6e, 0x76, 0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00,
+    0x00, 0x03, 0x65, 0x6e, 0x76, 0x0d, 0x5f, 0x5f, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x5f, 0x62,
+    0x61, 0x73, 0x65, 0x03, 0x7f, 0x00, 0x03, 0x02, 0x01, 0x00, 0x06, 0x06, 0x01, 0x7f, 0x00, 0x41,
+    0x00, 0x0b, 0x07, 0x0f, 0x02, 0x03, 0x67, 0x65, 0x74, 0x00, 0x00, 0x05, 0x76, 0x61, 0x6c, 0x75,
+    0x65, 0x03, 0x01, 0x0a, 0x09, 0x01, 0x07, 0x00, 0x23, 0x00, 0x28, 0x02, 0x00, 0x0b, 0x0b, 0x0a,
+    0x01, 0x00, 0x23, 0x00, 0x0b, 0x04, 0x2a, 0x00, 0x00, 0x00,
+];
+const DYLINK_MAIN_MODULE: [u8; 103] = [
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x00, 0x0f, 0x08, 0x64, 0x79, 0x6c, 0x69, 0x6e,
+    0x6b, 0x2e, 0x30, 0x01, 0x04, 0x00, 0x02, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f,
+    0x02, 0x2a, 0x03, 0x03, 0x65, 0x6e, 0x76, 0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00,
+    0x00, 0x03, 0x65, 0x6e, 0x76, 0x03, 0x67, 0x65, 0x74, 0x00, 0x00, 0x07, 0x47, 0x4f, 0x54, 0x2e,
+    0x6d, 0x65, 0x6d, 0x05, 0x76, 0x61, 0x6c, 0x75, 0x65, 0x03, 0x7f, 0x01, 0x03, 0x02, 0x01, 0x00,
+    0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e, 0x00, 0x01, 0x0a, 0x0c, 0x01, 0x0a, 0x00, 0x23, 0x00,
+    0x28, 0x02, 0x00, 0x10, 0x00, 0x6a, 0x0b,
+];
+
+#[test]
+fn test_dylink_side_modules() {
+    let main = Module::from_bytes(&DYLINK_MAIN_MODULE).unwrap();
+    let lib = Module::from_bytes(&DYLINK_LIB_MODULE).unwrap();
+    assert_eq!(lib.dylink().unwrap().memory_size, 4);
+
+    let mut instance = WasmInstance::new();
+    // Imports of the main module are bound once the library is loaded
+    let module_index = instance.store.load_dylink_module(None, &main).unwrap();
+    let lib_index = instance.store.load_dylink_module(None, &lib).unwrap();
+    let result = instance.run(lib_index, Some("get".to_string()), vec![]);
+    assert_eq!(result.unwrap(), vec![WasmValue::I32(42)]);
+    let result = instance.run(module_index, Some("run".to_string()), vec![]);
+    assert_eq!(result.unwrap(), vec![WasmValue::I32(84)]);
+}
+
+// Exports `answer` initialized with `i64.mul`, and stores 42 at an offset
+// computed with `i32.add`
+#[rustfmt::skip]
+const EXTENDED_CONST_MODULE: [u8; 82] = [
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    // (type (func (result i32)))
+    0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f,
+    // (func (type 0))
+    0x03, 0x02, 0x01, 0x00,
+    // (memory 1)
+    0x05, 0x03, 0x01, 0x00, 0x01,
+    // (global i32 (i32.const 8))
+    // (global i64 (i64.mul (i64.const 6) (i64.const 7)))
+    0x06, 0x0e, 0x02, 0x7f, 0x00, 0x41, 0x08, 0x0b,
+    0x7e, 0x00, 0x42, 0x06, 0x42, 0x07, 0x7e, 0x0b,
+    // (export "answer" (global 1)) (export "load" (func 0))
+    0x07, 0x11, 0x02, 0x06, 0x61, 0x6e, 0x73, 0x77, 0x65, 0x72, 0x03, 0x01,
+    0x04, 0x6c, 0x6f, 0x61, 0x64, 0x00, 0x00,
+    // i32.const 12 i32.load8_u
+    0x0a, 0x09, 0x01, 0x07, 0x00, 0x41, 0x0c, 0x2d, 0x00, 0x00, 0x0b,
+    // (data (i32.add (global.get 0) (i32.const 4)) "\2a")
+    0x0b, 0x0a, 0x01, 0x00, 0x23, 0x00, 0x41, 0x04, 0x6a, 0x0b, 0x01, 0x2a,
+];
+
+#[test]
+fn test_extended_const_exprs() {
+    let mut instance = WasmInstance::new();
+    let module_index = instance
+        .load_module_from_bytes(None, &EXTENDED_CONST_MODULE)
+        .unwrap();
+    assert_eq!(
+        instance.get_global(module_index, "answer"),
+        Some(WasmValue::I64(42))
+    );
+    let result = instance.run(module_index, Some("load".to_string()), vec![]);
+    assert_eq!(result.unwrap(), vec![WasmValue::I32(42)]);
+
+    // Replace `i64.mul` with `i32.add`
+    let mut bytes = EXTENDED_CONST_MODULE;
+    bytes[38] = 0x6a;
+    let err = validate(&bytes).unwrap_err();
+    assert!(!err.is_malformed());
+    assert!(err.message().starts_with("type mismatch"));
+    assert_eq!(err.offset(), 34);
+}
+
+// `grow` returns the result of `memory.grow` on a memory of 1 page
+const MEMORY_GROW_MODULE: [u8; 45] = [
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f,
+    0x03, 0x02, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x08, 0x01, 0x04, 0x67, 0x72, 0x6f,
+    0x77, 0x00, 0x00, 0x0a, 0x08, 0x01, 0x06, 0x00, 0x20, 0x00, 0x40, 0x00, 0x0b,
+];
+
+#[test]
+fn test_resource_limits() {
+    let mut instance = WasmInstance::new();
+    instance.store.set_limits(ResourceLimits {
+        max_memory_bytes: Some(2 * WASM_PAGE_SIZE),
+        max_instances: Some(1),
+        ..ResourceLimits::default()
+    });
+    let module_index = instance
+        .load_module_from_bytes(None, &MEMORY_GROW_MODULE)
+        .unwrap();
+    let grow = |instance: &mut WasmInstance, delta: i32| {
+        instance
+            .run(
+                module_index,
+                Some("grow".to_string()),
+                vec![WasmValue::I32(delta)],
+            )
+            .unwrap()
+    };
+    assert_eq!(grow(&mut instance, 1), vec![WasmValue::I32(1)]);
+    assert_eq!(grow(&mut instance, 1), vec![WasmValue::I32(-1)]);
+    assert_eq!(instance.store.memory_bytes(), 2 * WASM_PAGE_SIZE);
+
+    let err = instance
+        .load_module_from_bytes(None, &MEMORY_GROW_MODULE)
+        .unwrap_err();
+    match err.downcast_ref::<LimitError>() {
+        Some(LimitError::Instances { limit: 1 }) => (),
+        _ => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn test_lazily_committed_memory() {
+    // Grows to 4GiB. Pages which are never touched cost no memory.
+    let mut memory = HostMemory::new(1, None).unwrap();
+    memory.store(8, &[42]).unwrap();
+    memory.grow(65535).unwrap();
+    assert_eq!(memory.page_count(), 65536);
+    assert_eq!(memory.load_as::<u8>(8).unwrap(), 42);
+    assert_eq!(
+        memory.load_as::<u64>(65536 * WASM_PAGE_SIZE - 8).unwrap(),
+        0
+    );
+    memory.store(65536 * WASM_PAGE_SIZE - 1, &[1]).unwrap();
+    assert!(memory.grow(1).is_err());
+}
+
+// `foo` returns 42 stored in its own memory
+const LOAD_MEMORY_MODULE: [u8; 53] = [
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, 0x03,
+    0x02, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x07, 0x01, 0x03, 0x66, 0x6f, 0x6f, 0x00,
+    0x00, 0x0a, 0x09, 0x01, 0x07, 0x00, 0x41, 0x00, 0x2d, 0x00, 0x00, 0x0b, 0x0b, 0x07, 0x01, 0x00,
+    0x41, 0x00, 0x0b, 0x01, 0x2a,
+];
+
+#[test]
+fn test_unload_modules() {
+    let mut instance = WasmInstance::new();
+    let usage = instance.store.usage();
+    for _ in 0..100 {
+        let provider = instance
+            .load_module_from_bytes(Some("env".to_string()), &LOAD_MEMORY_MODULE)
+            .unwrap();
+        let user = instance
+            .load_module_from_bytes(None, &CALL_IMPORT_MODULE)
+            .unwrap();
+
+        // `env.foo` keeps the memory of its module alive
+        instance.store.unload_module(provider).unwrap();
+        assert_eq!(instance.store.usage().mems, usage.mems + 1);
+        let result = instance.run(user, Some("run".to_string()), vec![]);
+        assert_eq!(result.unwrap(), vec![WasmValue::I32(42)]);
+
+        instance.store.unload_module(user).unwrap();
+        assert_eq!(instance.store.usage(), usage);
+        assert!(instance.store.unload_module(user).is_err());
+    }
+}
+
+#[rustfmt::skip]
+const CALL_INDIRECT_MODULE: [u8; 60] = [
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    // (type (func (result i32)))
+    0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f,
+    // (func (type 0)) (func (type 0))
+    0x03, 0x03, 0x02, 0x00, 0x00,
+    // (table 1 funcref)
+    0x04, 0x04, 0x01, 0x70, 0x00, 0x01,
+    // (export "foo" (func 0))
+    0x07, 0x07, 0x01, 0x03, 0x66, 0x6f, 0x6f, 0x00, 0x00,
+    // (elem (i32.const 0) 1)
+    0x09, 0x07, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x01, 0x01,
+    // i32.const 0 call_indirect (type 0)
+    0x0a, 0x0e, 0x02, 0x07, 0x00, 0x41, 0x00, 0x11, 0x00, 0x00, 0x0b,
+    // i32.const 42
+    0x04, 0x00, 0x41, 0x2a, 0x0b,
+];
+
+#[test]
+fn test_unloaded_module_types_stay_alive() {
+    let mut instance = WasmInstance::new();
+    let usage = instance.store.usage();
+    let mut indices = std::collections::HashSet::new();
+    for _ in 0..10 {
+        let provider = instance
+            .load_module_from_bytes(Some("env".to_string()), &CALL_INDIRECT_MODULE)
+            .unwrap();
+        let user = instance
+            .load_module_from_bytes(None, &CALL_IMPORT_MODULE)
+            .unwrap();
+        indices.insert(provider);
+        indices.insert(user);
+
+        // `env.foo` looks up its type in the unloaded module
+        instance.store.unload_module(provider).unwrap();
+        assert!(!instance.store.is_loaded(provider));
+        assert!(instance.store.module(provider).is_some());
+        assert_eq!(instance.store.exports(provider).len(), 1);
+        let result = instance.run(user, Some("run".to_string()), vec![]);
+        assert_eq!(result.unwrap(), vec![WasmValue::I32(42)]);
+
+        instance.store.unload_module(user).unwrap();
+        assert!(instance.store.module(provider).is_none());
+        assert!(instance.store.imports(user).is_empty());
+        assert!(instance.store.exports(provider).is_empty());
+        assert_eq!(instance.store.usage(), usage);
+    }
+    // Slots of collected modules are reused
+    assert_eq!(indices.len(), 2);
+}
+
+#[test]
+fn test_store_reflection() {
+    let mut instance = WasmInstance::new();
+    let provider = instance
+        .load_module_from_bytes(Some("env".to_string()), &LOAD_MEMORY_MODULE)
+        .unwrap();
+    let user = instance
+        .load_module_from_bytes(None, &CALL_IMPORT_MODULE)
+        .unwrap();
+    let store = &instance.store;
+
+    let modules: Vec<_> = store.modules().collect();
+    assert_eq!(modules.len(), 2);
+    assert_eq!(modules[0].name, Some("env".to_string()));
+    assert_eq!(modules[1].name, None);
+    assert!(!modules[1].is_host);
+
+    let imports = store.imports(user);
+    assert_eq!(imports.len(), 1);
+    assert_eq!(
+        format!(
+            "{}.{}: {}",
+            imports[0].module, imports[0].field, imports[0].ty
+        ),
+        "env.foo: func () -> (i32)"
+    );
+    let exports = store.exports(provider);
+    assert_eq!(exports.len(), 1);
+    assert_eq!(exports[0].name, "foo");
+    assert_eq!(exports[0].index, Some(0));
+
+    let funcs: Vec<_> = store.funcs(user).collect();
+    assert_eq!(funcs.len(), 2);
+    assert_eq!(funcs[0].0.index(), 0);
+    assert!(funcs[0].1.defined().is_some());
+    let memories: Vec<_> = store.memories(provider).collect();
+    assert_eq!(memories.len(), 1);
+    assert_eq!(memories[0].1.borrow().page_count(), 1);
+    assert_eq!(
+        format!("{}", ExternType::Memory(memories[0].1.borrow().ty())),
+        "memory 1.."
+    );
+    assert_eq!(store.globals(provider).count(), 0);
+    assert_eq!(store.tables(user).count(), 0);
+}
+
+// `(module $calc (global $base i32) (func $add (param $x i32) (local $sum i32)))`
+// with a name section including global names
+const NAMED_MODULE: [u8; 89] = [
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f,
+    0x03, 0x02, 0x01, 0x00, 0x06, 0x06, 0x01, 0x7f, 0x00, 0x41, 0x28, 0x0b, 0x0a, 0x0f, 0x01, 0x0d,
+    0x01, 0x01, 0x7f, 0x20, 0x00, 0x23, 0x00, 0x6a, 0x21, 0x01, 0x20, 0x01, 0x0b, 0x00, 0x2a, 0x04,
+    0x6e, 0x61, 0x6d, 0x65, 0x00, 0x05, 0x04, 0x63, 0x61, 0x6c, 0x63, 0x01, 0x06, 0x01, 0x00, 0x03,
+    0x61, 0x64, 0x64, 0x02, 0x0b, 0x01, 0x00, 0x02, 0x00, 0x01, 0x78, 0x01, 0x03, 0x73, 0x75, 0x6d,
+    0x07, 0x07, 0x01, 0x00, 0x04, 0x62, 0x61, 0x73, 0x65,
+];
+
+#[test]
+fn test_name_section() {
+    let module = decode_validated(&NAMED_MODULE).unwrap();
+    let names = module.names();
+    assert_eq!(names.module, Some("calc".to_string()));
+    assert_eq!(module.func_name(0), Some(&"add".to_string()));
+    assert_eq!(names.local_name(0, 0), Some(&"x".to_string()));
+    assert_eq!(names.local_name(0, 1), Some(&"sum".to_string()));
+    assert_eq!(names.global_index("base"), Some(0));
+
+    let mut store = Store::new();
+    let module_index = store.instantiate_module(None, &module).unwrap();
+    let (_, func) = store.funcs(module_index).next().unwrap();
+    let func = func.defined().unwrap();
+    assert_eq!(func.local_name(1), Some(&"sum".to_string()));
+    assert_eq!(func.local_name(2), None);
+}
+
+// A funcref global initialized with `ref.func 0`
+#[rustfmt::skip]
+const REF_FUNC_GLOBAL_MODULE: [u8; 39] = [
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    // (type (func))
+    0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+    // (func (type 0))
+    0x03, 0x02, 0x01, 0x00,
+    // (global funcref (ref.func 0))
+    0x06, 0x06, 0x01, 0x70, 0x00, 0xd2, 0x00, 0x0b,
+    // (export "f" (func 0))
+    0x07, 0x05, 0x01, 0x01, 0x66, 0x00, 0x00,
+    0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+];
+
+#[test]
+fn test_reference_globals_are_unsupported() {
+    let mut instance = WasmInstance::new();
+    let err = instance
+        .load_module_from_bytes(None, &REF_FUNC_GLOBAL_MODULE)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid module: reference types support is not enabled at offset 0x12"
+    );
+    // `ref.func` is evaluated, but `Value` has no reference type to hold it
+    instance.store.set_validation(false);
+    let err = instance
+        .load_module_from_bytes(None, &REF_FUNC_GLOBAL_MODULE)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "reference values are not supported in globals"
+    );
+}